@@ -1,4 +1,4 @@
-use bevy_aseprite_reader::Aseprite;
+use bevy_aseprite_reader::{raw::AsepriteParseMode, AsepriteCache};
 use heck::ToShoutySnekCase;
 use proc_macro::TokenStream;
 use proc_macro_error::abort;
@@ -13,6 +13,7 @@ struct AsepriteDeclaration {
     name: Ident,
     path: LitStr,
     prefix_path: Option<LitStr>,
+    embed: bool,
 }
 
 impl Parse for AsepriteDeclaration {
@@ -21,16 +22,26 @@ impl Parse for AsepriteDeclaration {
         let name: Ident = input.parse()?;
         input.parse::<Token!(,)>()?;
         let path: LitStr = input.parse()?;
-        let prefix_path: Option<LitStr> = match input.parse::<Token!(,)>() {
-            Ok(_) => Some(input.parse()?),
-            Err(_) => None,
-        };
+        let mut prefix_path: Option<LitStr> = None;
+        let mut embed = false;
+        while input.parse::<Token!(,)>().is_ok() {
+            if input.peek(LitStr) && prefix_path.is_none() {
+                prefix_path = Some(input.parse()?);
+                continue;
+            }
+            let option: Ident = input.parse()?;
+            match option.to_string().as_str() {
+                "embed" => embed = true,
+                _ => return Err(syn::Error::new(option.span(), "Expected `embed`")),
+            }
+        }
 
         Ok(AsepriteDeclaration {
             vis,
             name,
             path,
             prefix_path,
+            embed,
         })
     }
 }
@@ -43,6 +54,7 @@ pub fn aseprite(input: TokenStream) -> TokenStream {
         name,
         path,
         prefix_path,
+        embed,
     } = parse_macro_input!(input as AsepriteDeclaration);
 
     let prefix = match prefix_path {
@@ -50,7 +62,14 @@ pub fn aseprite(input: TokenStream) -> TokenStream {
         None => String::default(),
     };
 
-    let aseprite = match Aseprite::from_path(format!("{}assets/{}", prefix, path.value())) {
+    let file_path = format!("{}assets/{}", prefix, path.value());
+    // The same file is often declared in several modules, only its tags and
+    // slices are needed, unless it is embedded and has to parse fully
+    let mode = match embed {
+        true => AsepriteParseMode::Loose,
+        false => AsepriteParseMode::Metadata,
+    };
+    let aseprite = match AsepriteCache::global().get_with(&file_path, mode) {
         Ok(aseprite) => aseprite,
         Err(err) => {
             abort!(path, "Could not load file."; note = err);
@@ -58,10 +77,14 @@ pub fn aseprite(input: TokenStream) -> TokenStream {
     };
 
     let tags = aseprite.tags();
-    let tag_names = tags
+    let tag_names: Vec<_> = tags
         .all()
-        .map(|tag| format_ident!("{}", tag.name.TO_SHOUTY_SNEK_CASE()));
+        .map(|tag| format_ident!("{}", tag.name.TO_SHOUTY_SNEK_CASE()))
+        .collect();
     let tag_values = tags.all().map(|tag| &tag.name);
+    let tag_range_names = &tag_names;
+    let tag_range_starts = tags.all().map(|tag| tag.frames.from);
+    let tag_range_ends = tags.all().map(|tag| tag.frames.to + 1);
 
     let slices = aseprite.slices();
 
@@ -70,18 +93,82 @@ pub fn aseprite(input: TokenStream) -> TokenStream {
         .map(|slice| format_ident!("{}", slice.name.TO_SHOUTY_SNEK_CASE()));
     let slice_values = slices.get_all().map(|slice| &slice.name);
 
+    // Borders are whatever is left around the center, in case it goes past
+    // the slice bounds
+    let nine_patches = slices.get_all().filter_map(|slice| {
+        let center = slice.nine_patch_info.as_ref()?;
+        let name = format_ident!("{}", slice.name.TO_SHOUTY_SNEK_CASE());
+        let (x, y) = (center.x_center as f32, center.y_center as f32);
+        let (width, height) = (center.width as f32, center.height as f32);
+        let left = x.max(0.);
+        let top = y.max(0.);
+        let right = (slice.width as f32 - x - width).max(0.);
+        let bottom = (slice.height as f32 - y - height).max(0.);
+        let (max_x, max_y) = (x + width, y + height);
+        Some(quote! {
+            pub mod #name {
+                /// Center of the slice, relative to its top left corner
+                pub const CENTER: ::bevy::math::Rect = ::bevy::math::Rect {
+                    min: ::bevy::math::Vec2::new(#x, #y),
+                    max: ::bevy::math::Vec2::new(#max_x, #max_y),
+                };
+                pub const LEFT: f32 = #left;
+                pub const TOP: f32 = #top;
+                pub const RIGHT: f32 = #right;
+                pub const BOTTOM: f32 = #bottom;
+                /// Smallest size the slice can be stretched to, keeping its borders
+                pub const MIN_SIZE: ::bevy::math::Vec2 = ::bevy::math::Vec2::new(#left + #right, #top + #bottom);
+            }
+        })
+    });
+
+    // `include_bytes!` is relative to the invoking file, so use the full path
+    let embedded = match embed {
+        true => {
+            let full_path = match std::fs::canonicalize(&file_path) {
+                Ok(full_path) => full_path.display().to_string(),
+                Err(err) => {
+                    abort!(path, "Could not embed file."; note = err);
+                }
+            };
+            quote! {
+                pub const BYTES: &'static [u8] = include_bytes!(#full_path);
+
+                /// Add the embedded file to the assets, without going through the asset server
+                pub fn load_embedded(
+                    aseprites: &mut ::bevy::asset::Assets<::bevy_aseprite::Aseprite>,
+                ) -> ::bevy::asset::Handle<::bevy_aseprite::Aseprite> {
+                    let aseprite = ::bevy_aseprite::Aseprite::from_bytes(BYTES, Default::default())
+                        .expect("The embedded file was already parsed by the macro");
+                    aseprites.add(aseprite)
+                }
+            }
+        }
+        false => quote! {},
+    };
+
     let expanded = quote! {
         #[allow(non_snake_case)]
         #vis mod #name {
             pub const PATH: &'static str = #path;
 
+            #embedded
+
             pub mod tags {
                 #( pub const #tag_names: &'static str = #tag_values; )*
             }
 
+            pub mod tag_ranges {
+                #( pub const #tag_range_names: ::core::ops::Range<u16> = #tag_range_starts..#tag_range_ends; )*
+            }
+
             pub mod slices {
                 #( pub const #slice_names: &'static str = #slice_values; )*
             }
+
+            pub mod nine_patches {
+                #( #nine_patches )*
+            }
         }
     };
 
@@ -0,0 +1,57 @@
+use bevy::prelude::*;
+use bevy_aseprite::{
+    anim::AsepriteAnimation, asset_loader::AsepriteLoadingPlugin, Aseprite, AsepriteBundle,
+    AsepritePlugin,
+};
+use bevy_asset_loader::prelude::*;
+
+mod sprites {
+    use bevy_aseprite::aseprite;
+
+    // https://meitdev.itch.io/crow
+    aseprite!(pub Crow, "crow.aseprite");
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, States)]
+enum GameState {
+    #[default]
+    Loading,
+    BuildingAtlases,
+    Playing,
+}
+
+#[derive(AssetCollection, Resource)]
+struct Sprites {
+    #[asset(path = "crow.aseprite")]
+    crow: Handle<Aseprite>,
+}
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins.set(ImagePlugin::default_nearest()))
+        .add_plugins(AsepritePlugin::default())
+        .add_state::<GameState>()
+        .add_loading_state(
+            LoadingState::new(GameState::Loading)
+                .continue_to_state(GameState::BuildingAtlases)
+                .load_collection::<Sprites>(),
+        )
+        // The files are loaded, wait for their atlases before playing
+        .add_plugins(AsepriteLoadingPlugin {
+            state: GameState::BuildingAtlases,
+            next_state: GameState::Playing,
+        })
+        .add_systems(OnEnter(GameState::Playing), setup)
+        .run();
+}
+
+fn setup(mut commands: Commands, sprites: Res<Sprites>) {
+    commands.spawn(Camera2dBundle::default());
+
+    commands.spawn(AsepriteBundle {
+        aseprite: sprites.crow.clone(),
+        animation: AsepriteAnimation::from(sprites::Crow::tags::FLAP_WINGS),
+        transform: Transform::from_scale(Vec3::splat(4.)),
+        ..Default::default()
+    });
+}
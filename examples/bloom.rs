@@ -0,0 +1,75 @@
+use bevy::{
+    core_pipeline::bloom::BloomSettings, core_pipeline::tonemapping::Tonemapping, prelude::*,
+};
+use bevy_aseprite::{
+    anim::AsepriteAnimation, effects::AsepriteMaterialOverride, Aseprite, AsepriteBundle,
+    AsepritePlugin,
+};
+
+// The flame of the lantern is drawn again over it, from its emissive layer
+#[derive(Component, Clone, Copy, Debug)]
+struct Glow;
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins.set(ImagePlugin::default_nearest()))
+        .add_plugins(AsepritePlugin::default())
+        .add_systems(Startup, setup)
+        .add_systems(Update, add_glow)
+        .run();
+}
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        Camera2dBundle {
+            camera: Camera {
+                hdr: true,
+                ..Default::default()
+            },
+            tonemapping: Tonemapping::TonyMcMapface,
+            ..Default::default()
+        },
+        BloomSettings::default(),
+    ));
+
+    // `lantern.aseprite` has a hidden `emissive` layer with the flame
+    let lantern = asset_server.load("lantern.aseprite");
+    commands
+        .spawn(AsepriteBundle {
+            aseprite: lantern.clone(),
+            animation: AsepriteAnimation::from("flicker"),
+            transform: Transform::from_scale(Vec3::splat(8.)),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                AsepriteBundle {
+                    aseprite: lantern,
+                    animation: AsepriteAnimation::from("flicker"),
+                    transform: Transform::from_xyz(0., 0., 0.1),
+                    ..Default::default()
+                },
+                // Colors brighter than white are picked up by bloom
+                TextureAtlasSprite {
+                    color: Color::rgb(4., 3., 2.),
+                    ..Default::default()
+                },
+                Glow,
+            ));
+        });
+}
+
+// The emissive texture exists once the atlas has been built
+fn add_glow(
+    mut commands: Commands,
+    aseprites: Res<Assets<Aseprite>>,
+    glows: Query<(Entity, &Handle<Aseprite>), (With<Glow>, Without<AsepriteMaterialOverride>)>,
+) {
+    for (entity, handle) in &glows {
+        if let Some(texture) = aseprites.get(handle).and_then(Aseprite::emissive_texture) {
+            commands
+                .entity(entity)
+                .insert(AsepriteMaterialOverride::new(texture.clone()));
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use std::path::Path;
+
+use bevy::{
+    asset::io::{
+        memory::{Dir, MemoryAssetReader},
+        AssetSource, AssetSourceId,
+    },
+    prelude::*,
+};
+use bevy_aseprite::{anim::AsepriteAnimation, AsepriteBundle, AsepritePlugin};
+
+fn main() {
+    // Stands in for an archive or embedded VFS, filled from a pak file
+    let pak = Dir::default();
+    pak.insert_asset(
+        Path::new("crow.aseprite"),
+        include_bytes!("../assets/crow.aseprite").to_vec(),
+    );
+
+    App::new()
+        // Sources have to be registered before the `AssetPlugin`
+        .register_asset_source(
+            "pak",
+            AssetSource::build()
+                .with_reader(move || Box::new(MemoryAssetReader { root: pak.clone() })),
+        )
+        .add_plugins(DefaultPlugins.set(ImagePlugin::default_nearest()))
+        .add_plugins(AsepritePlugin::default().only_from_sources([AssetSourceId::from("pak")]))
+        .add_systems(Startup, setup)
+        .run();
+}
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn(Camera2dBundle::default());
+
+    commands.spawn(AsepriteBundle {
+        aseprite: asset_server.load("pak://crow.aseprite"),
+        animation: AsepriteAnimation::from("flap_wings"),
+        transform: Transform::from_scale(Vec3::splat(4.)),
+        ..Default::default()
+    });
+}
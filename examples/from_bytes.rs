@@ -0,0 +1,33 @@
+use bevy::prelude::*;
+use bevy_aseprite::{
+    anim::AsepriteAnimation, Aseprite, AsepriteBundle, AsepriteLoaderSettings, AsepritePlugin,
+};
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins.set(ImagePlugin::default_nearest()))
+        .add_plugins(AsepritePlugin::default())
+        .add_systems(Startup, setup)
+        .run();
+}
+
+fn setup(mut commands: Commands, mut aseprites: ResMut<Assets<Aseprite>>) {
+    commands.spawn(Camera2dBundle::default());
+
+    // Stands in for content downloaded or generated at runtime
+    let bytes = std::fs::read("assets/crow.aseprite").expect("the crow is in the assets folder");
+    let aseprite = match Aseprite::from_bytes(&bytes, AsepriteLoaderSettings::default()) {
+        Ok(aseprite) => aseprite,
+        Err(err) => {
+            error!("Invalid aseprite: {}", err);
+            return;
+        }
+    };
+
+    commands.spawn(AsepriteBundle {
+        aseprite: aseprites.add(aseprite),
+        animation: AsepriteAnimation::from("flap_wings"),
+        transform: Transform::from_scale(Vec3::splat(4.)),
+        ..Default::default()
+    });
+}
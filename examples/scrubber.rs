@@ -0,0 +1,125 @@
+use bevy::{prelude::*, ui::RelativeCursorPosition};
+use bevy_aseprite::{anim::AsepriteAnimation, Aseprite, AsepriteBundle, AsepritePlugin};
+
+mod sprites {
+    use bevy_aseprite::aseprite;
+
+    // https://meitdev.itch.io/crow
+    aseprite!(pub Crow, "crow.aseprite");
+}
+
+#[derive(Component)]
+struct Scrubber;
+
+#[derive(Component)]
+struct ScrubberHandle;
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins.set(ImagePlugin::default_nearest()))
+        .add_plugins(AsepritePlugin::default())
+        .add_systems(Startup, setup)
+        .add_systems(Update, (scrub, update_handle))
+        .run();
+}
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn(Camera2dBundle::default());
+
+    commands.spawn(AsepriteBundle {
+        aseprite: asset_server.load(sprites::Crow::PATH),
+        animation: AsepriteAnimation::from(sprites::Crow::tags::FLAP_WINGS),
+        transform: Transform::from_scale(Vec3::splat(4.)),
+        ..Default::default()
+    });
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                align_items: AlignItems::FlexEnd,
+                justify_content: JustifyContent::Center,
+                padding: UiRect::bottom(Val::Px(40.)),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    NodeBundle {
+                        style: Style {
+                            width: Val::Px(400.),
+                            height: Val::Px(20.),
+                            ..Default::default()
+                        },
+                        background_color: Color::DARK_GRAY.into(),
+                        ..Default::default()
+                    },
+                    Interaction::default(),
+                    RelativeCursorPosition::default(),
+                    Scrubber,
+                ))
+                .with_children(|parent| {
+                    parent.spawn((
+                        NodeBundle {
+                            style: Style {
+                                width: Val::Px(6.),
+                                height: Val::Percent(100.),
+                                position_type: PositionType::Absolute,
+                                ..Default::default()
+                            },
+                            background_color: Color::WHITE.into(),
+                            ..Default::default()
+                        },
+                        ScrubberHandle,
+                    ));
+                });
+        });
+}
+
+// Pause the animation and seek to the cursor while the bar is held
+fn scrub(
+    aseprites: Res<Assets<Aseprite>>,
+    scrubber: Query<(&Interaction, &RelativeCursorPosition), With<Scrubber>>,
+    mut animations: Query<(&mut AsepriteAnimation, &Handle<Aseprite>)>,
+) {
+    let (interaction, cursor) = scrubber.single();
+    let position = match (interaction, cursor.normalized) {
+        (Interaction::Pressed, Some(position)) => position.x.clamp(0., 1.),
+        _ => {
+            for (mut animation, _) in animations.iter_mut() {
+                if animation.is_paused() {
+                    animation.play();
+                }
+            }
+            return;
+        }
+    };
+
+    for (mut animation, handle) in animations.iter_mut() {
+        let info = match aseprites.get(handle).and_then(Aseprite::info) {
+            Some(info) => info,
+            None => continue,
+        };
+        let time = animation.tag_duration(info).mul_f32(position);
+        animation.pause();
+        animation.set_time_in_tag(info, time);
+    }
+}
+
+fn update_handle(
+    aseprites: Res<Assets<Aseprite>>,
+    animations: Query<(&AsepriteAnimation, &Handle<Aseprite>)>,
+    mut handle: Query<&mut Style, With<ScrubberHandle>>,
+) {
+    let (animation, aseprite) = animations.single();
+    let info = match aseprites.get(aseprite).and_then(Aseprite::info) {
+        Some(info) => info,
+        None => return,
+    };
+    let progress = animation.time_in_tag(info).as_secs_f32()
+        / animation.tag_duration(info).as_secs_f32().max(f32::EPSILON);
+    handle.single_mut().left = Val::Percent(progress * 100.);
+}
@@ -19,7 +19,7 @@ mod sprites {
 fn main() {
     App::new()
         .add_plugins(DefaultPlugins.set(ImagePlugin::default_nearest()))
-        .add_plugins(AsepritePlugin)
+        .add_plugins(AsepritePlugin::default())
         .add_systems(Startup, setup)
         .add_systems(Startup, setup_text)
         .add_systems(Update, change_animation)
@@ -65,18 +65,18 @@ fn change_animation(
 ) {
     if keys.just_pressed(KeyCode::Key1) {
         for mut crow_anim in aseprites.p0().iter_mut() {
-            *crow_anim = AsepriteAnimation::from(sprites::Crow::tags::FLAP_WINGS);
+            crow_anim.set_tag(sprites::Crow::tags::FLAP_WINGS);
         }
         for mut player_anim in aseprites.p1().iter_mut() {
-            *player_anim = AsepriteAnimation::from(sprites::Player::tags::LEFT_WALK);
+            player_anim.set_tag(sprites::Player::tags::LEFT_WALK);
         }
     }
     if keys.just_pressed(KeyCode::Key2) {
         for mut crow_anim in aseprites.p0().iter_mut() {
-            *crow_anim = AsepriteAnimation::from(sprites::Crow::tags::GROOVE);
+            crow_anim.set_tag(sprites::Crow::tags::GROOVE);
         }
         for mut player_anim in aseprites.p1().iter_mut() {
-            *player_anim = AsepriteAnimation::from(sprites::Player::tags::RIGHT_WALK);
+            player_anim.set_tag(sprites::Player::tags::RIGHT_WALK);
         }
     }
 
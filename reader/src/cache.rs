@@ -0,0 +1,110 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard, OnceLock, PoisonError},
+    time::SystemTime,
+};
+
+use crate::{error::AseResult, raw::AsepriteParseMode, Aseprite};
+
+// Parsed files by path and mode, with their modification time when parsed
+type CachedFiles = HashMap<(PathBuf, AsepriteParseMode), (Option<SystemTime>, Arc<Aseprite>)>;
+
+/// Memoizes [`Aseprite::from_path`] for tools reading the same files many
+/// times, e.g. build scripts or the `aseprite!` macro
+///
+/// A file is parsed again once its modification time changes. The cache can
+/// be shared between threads, [`AsepriteCache::global`] is one for the whole
+/// process.
+#[derive(Debug, Default)]
+pub struct AsepriteCache {
+    files: Mutex<CachedFiles>,
+}
+
+impl AsepriteCache {
+    /// Create an empty cache
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A cache shared by the whole process
+    pub fn global() -> &'static AsepriteCache {
+        static CACHE: OnceLock<AsepriteCache> = OnceLock::new();
+        CACHE.get_or_init(AsepriteCache::new)
+    }
+
+    /// Parse a file, unless it was parsed before and didn't change since
+    pub fn get<P: AsRef<Path>>(&self, path: P) -> AseResult<Arc<Aseprite>> {
+        self.get_with(path, AsepriteParseMode::Loose)
+    }
+
+    /// Parse a file in a mode, unless it was parsed in this mode before and
+    /// didn't change since
+    ///
+    /// [`AsepriteParseMode::Metadata`] skips the pixels of the cels, for
+    /// tools which only need the tags or slices of the files.
+    pub fn get_with<P: AsRef<Path>>(
+        &self,
+        path: P,
+        mode: AsepriteParseMode,
+    ) -> AseResult<Arc<Aseprite>> {
+        let key = (path.as_ref().to_path_buf(), mode);
+        let modified = std::fs::metadata(&key.0)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        if let Some((cached, aseprite)) = self.files().get(&key) {
+            if modified.is_some() && *cached == modified {
+                return Ok(aseprite.clone());
+            }
+        }
+
+        // Parsed without holding the lock, so other files can be read meanwhile
+        let aseprite = Arc::new(Aseprite::from_bytes_with(std::fs::read(&key.0)?, mode)?);
+        self.files().insert(key, (modified, aseprite.clone()));
+        Ok(aseprite)
+    }
+
+    /// Forget every file, they are parsed again on their next use
+    pub fn clear(&self) {
+        self.files().clear();
+    }
+
+    // A panic while the lock was held can't leave the map half updated
+    fn files(&self) -> MutexGuard<'_, CachedFiles> {
+        self.files.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs::File,
+        sync::Arc,
+        time::{Duration, SystemTime},
+    };
+
+    use super::AsepriteCache;
+
+    #[test]
+    fn reparse_modified_files() {
+        let path = std::env::temp_dir().join("bevy_aseprite_reader_cache.aseprite");
+        std::fs::copy("./tests/test_cases/layers.aseprite", &path).unwrap();
+
+        let cache = AsepriteCache::new();
+        let first = cache.get(&path).unwrap();
+        assert!(Arc::ptr_eq(&first, &cache.get(&path).unwrap()));
+
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(10))
+            .unwrap();
+        let modified = cache.get(&path).unwrap();
+        assert!(!Arc::ptr_eq(&first, &modified));
+
+        cache.clear();
+        assert!(!Arc::ptr_eq(&modified, &cache.get(&path).unwrap()));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
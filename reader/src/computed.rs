@@ -1,7 +1,8 @@
 use std::{
-    collections::{BTreeMap, HashMap},
-    ops::{Index, Range},
+    collections::{BTreeMap, BTreeSet, HashMap},
+    ops::{Index, Range, RangeInclusive},
     path::Path,
+    time::Duration,
 };
 
 use image::{Pixel, Rgba, RgbaImage};
@@ -12,8 +13,9 @@ use crate::{
     error::{AseResult, AsepriteError, AsepriteInvalidError},
     raw::{
         AsepriteAnimationDirection, AsepriteBlendMode, AsepriteColor, AsepriteColorDepth,
-        AsepriteLayerType, AsepriteNinePatchInfo, AsepritePixel, RawAseprite, RawAsepriteCel,
-        RawAsepriteChunk, RawAsepritePaletteEntry,
+        AsepriteLayerType, AsepriteNinePatchInfo, AsepriteParseMode, AsepritePivot, AsepritePixel,
+        RawAseprite, RawAsepriteCel, RawAsepriteChunk, RawAsepriteOldPalettePacket,
+        RawAsepritePaletteEntry,
     },
 };
 
@@ -22,18 +24,36 @@ use crate::{
 pub struct Aseprite {
     dimensions: (u16, u16),
     tags: HashMap<String, AsepriteTag>,
+    tag_list: Vec<AsepriteTag>,
     slices: HashMap<String, AsepriteSlice>,
+    slice_keys: HashMap<String, Vec<AsepriteSlice>>,
     layers: BTreeMap<usize, AsepriteLayer>,
     frame_count: usize,
     palette: Option<AsepritePalette>,
     transparent_palette: Option<u8>,
     frame_infos: Vec<AsepriteFrameInfo>,
+    user_data: Option<AsepriteUserData>,
+}
+
+// What a user data chunk applies to, from the chunk it follows
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum UserDataOwner {
+    Cel,
+    Palette,
+    Slice(String),
+    // Index of the tag in the tag list, tags chunks are followed by the user
+    // data of each of their tags in order
+    Tag(usize),
+    Other,
 }
 
 impl Aseprite {
     /// Get the [`AsepriteTag`]s defined in this Aseprite
     pub fn tags(&self) -> AsepriteTags {
-        AsepriteTags { tags: &self.tags }
+        AsepriteTags {
+            tags: &self.tags,
+            tag_list: &self.tag_list,
+        }
     }
 
     /// Get the associated [`AsepriteLayer`]s defined in this Aseprite
@@ -53,29 +73,265 @@ impl Aseprite {
         &self.frame_infos
     }
 
+    /// Get the user data of the sprite itself, set in its properties
+    ///
+    /// Only stored by Aseprite 1.3 and later.
+    pub fn user_data(&self) -> Option<&AsepriteUserData> {
+        self.user_data.as_ref()
+    }
+
     /// Get the slices inside this aseprite
     pub fn slices(&self) -> AsepriteSlices {
         AsepriteSlices { aseprite: self }
     }
+
+    /// Compare with another version of the file, e.g. after it was modified
+    ///
+    /// Layers are matched by their id and tags by their name.
+    pub fn diff(&self, other: &Aseprite) -> AsepriteDiff {
+        let frame_count = self.frame_count.max(other.frame_count);
+        let common_frames = self.frame_count.min(other.frame_count);
+        let mut diff = AsepriteDiff {
+            dimensions_changed: self.dimensions != other.dimensions,
+            ..Default::default()
+        };
+
+        let mut changed_frames = BTreeSet::new();
+        // Added or removed frames
+        changed_frames.extend(common_frames..frame_count);
+        if diff.dimensions_changed
+            || self.palette != other.palette
+            || self.transparent_palette != other.transparent_palette
+        {
+            changed_frames.extend(0..frame_count);
+        }
+        for frame in 0..common_frames {
+            if self.frame_infos[frame].duration != other.frame_infos[frame].duration {
+                diff.retimed_frames.push(frame as u16);
+            }
+        }
+
+        let layer_ids: BTreeSet<_> = self.layers.keys().chain(other.layers.keys()).collect();
+        for id in layer_ids {
+            let (layer, other_layer) = match (self.layers.get(id), other.layers.get(id)) {
+                (Some(layer), Some(other_layer)) => (layer, other_layer),
+                // Added or removed layers change every frame they have a cel in
+                (layer, other_layer) => {
+                    let layer = layer.or(other_layer).unwrap();
+                    changed_frames.extend(layer.cels().map(|cel| cel.frame as usize));
+                    diff.changed_layers.push(*id);
+                    continue;
+                }
+            };
+
+            let properties_changed = !layer.properties_eq(other_layer);
+            let mut layer_changed = properties_changed;
+            for frame in 0..frame_count {
+                let same = match (layer.get_cel(frame).ok(), other_layer.get_cel(frame).ok()) {
+                    (None, None) => true,
+                    (Some(cel), Some(other_cel)) => {
+                        !properties_changed
+                            && cel.x == other_cel.x
+                            && cel.y == other_cel.y
+                            && cel.opacity == other_cel.opacity
+                            && layer.cel_pixels(cel) == other_layer.cel_pixels(other_cel)
+                    }
+                    _ => false,
+                };
+                if !same {
+                    changed_frames.insert(frame);
+                    layer_changed = true;
+                }
+            }
+            if layer_changed {
+                diff.changed_layers.push(*id);
+            }
+        }
+        diff.changed_frames = changed_frames
+            .into_iter()
+            .map(|frame| frame as u16)
+            .collect();
+
+        let tag_names: BTreeSet<_> = self.tags.keys().chain(other.tags.keys()).collect();
+        diff.changed_tags = tag_names
+            .into_iter()
+            .filter(|name| {
+                !self
+                    .tags()
+                    .get_all_by_name(name)
+                    .eq(other.tags().get_all_by_name(name))
+            })
+            .cloned()
+            .collect();
+
+        diff
+    }
+
+    /// Get the rectangle covered by the canvas
+    pub fn canvas_rect(&self) -> AsepriteRect {
+        AsepriteRect {
+            x: 0,
+            y: 0,
+            width: self.dimensions.0 as u32,
+            height: self.dimensions.1 as u32,
+        }
+    }
+
+    /// Pack the frames of a tag into a sprite sheet, row by row
+    ///
+    /// The frames are laid out in file order, `columns` per row, e.g. to
+    /// export a single animation as a strip for other tools.
+    pub fn tag_sheet(&self, name: &str, columns: u32) -> AseResult<AsepriteTagSheet> {
+        let tag = self
+            .tags
+            .get(name)
+            .ok_or_else(|| AsepriteError::TagNotFound(name.to_owned()))?;
+        let count = tag.frames.frame_count() as u32;
+        let columns = columns.clamp(1, count.max(1));
+        let (width, height) = (self.dimensions.0 as u32, self.dimensions.1 as u32);
+
+        let mut sheet = AsepriteTagSheet {
+            image: RgbaImage::new(width * columns, height * count.div_ceil(columns)),
+            frames: Vec::with_capacity(count as usize),
+            animation_direction: tag.animation_direction,
+        };
+        let mut frame_image = RgbaImage::new(width, height);
+        for (idx, frame) in tag.frames.iter().enumerate() {
+            self.image_for_frame_into(frame, &mut frame_image)?;
+            let rect = AsepriteRect {
+                x: (idx as u32 % columns * width) as i32,
+                y: (idx as u32 / columns * height) as i32,
+                width,
+                height,
+            };
+            image::imageops::replace(&mut sheet.image, &frame_image, rect.x as i64, rect.y as i64);
+            sheet.frames.push(AsepriteTagSheetFrame {
+                frame,
+                rect,
+                duration: self.frame_infos[frame as usize].duration,
+            });
+        }
+        Ok(sheet)
+    }
+
+    /// Crop the cels reaching outside of the canvas, e.g. after the canvas was resized
+    ///
+    /// Content outside of the canvas is never drawn, cropping it only saves
+    /// memory and lets tools repair such files. Returns how many cels were
+    /// cropped, linked cels follow the cel they link to.
+    pub fn clamp_cels_to_canvas(&mut self) -> usize {
+        let canvas = self.canvas_rect();
+        let mut clamped = 0;
+        for layer in self.layers.values_mut() {
+            let cels = match layer {
+                AsepriteLayer::Normal { cels, .. } => cels,
+                AsepriteLayer::Group { .. } => continue,
+            };
+
+            // How far the content of each frame moved while cropping
+            let mut offsets = HashMap::new();
+            for cel in cels.iter_mut() {
+                let bounds = match cel.bounds() {
+                    Some(bounds) => bounds,
+                    None => continue,
+                };
+                if canvas.contains(&bounds) {
+                    continue;
+                }
+                let visible = canvas.intersect(&bounds).unwrap_or_default();
+                cel.crop(visible);
+                offsets.insert(cel.frame, (visible.x - bounds.x, visible.y - bounds.y));
+                clamped += 1;
+            }
+            for cel in cels.iter_mut() {
+                if let RawAsepriteCel::Linked { frame_position } = cel.raw_cel {
+                    if let Some((dx, dy)) = offsets.get(&frame_position) {
+                        cel.x += *dx as f64;
+                        cel.y += *dy as f64;
+                    }
+                }
+            }
+        }
+        clamped
+    }
+
+    /// Composite a frame into an existing image, reusing its buffer
+    ///
+    /// The image is cleared first, and only reallocated if its size doesn't
+    /// match the dimensions of the aseprite.
+    pub fn image_for_frame_into(&self, frame: u16, image: &mut RgbaImage) -> AseResult<()> {
+        self.image_for_frame_into_with(frame, &AsepriteCompositeOptions::default(), image)
+    }
+
+    /// Composite a frame into an existing image with the given options
+    ///
+    /// See [`image_for_frame_into`](Self::image_for_frame_into)
+    pub fn image_for_frame_into_with(
+        &self,
+        frame: u16,
+        options: &AsepriteCompositeOptions,
+        image: &mut RgbaImage,
+    ) -> AseResult<()> {
+        if frame as usize >= self.frame_count {
+            return Err(AsepriteInvalidError::InvalidFrame(frame as usize).into());
+        }
+        image_for_frame_into(self, frame, options, image)
+    }
+
+    /// Blend the cel of a single layer on top of an existing image
+    ///
+    /// The image is left untouched where the layer has no pixels, pixels
+    /// outside of the image are skipped.
+    pub fn composite_layer_into(
+        &self,
+        layer: &AsepriteLayer,
+        frame: u16,
+        image: &mut RgbaImage,
+    ) -> AseResult<()> {
+        composite_layer_into(self, layer, frame, None, image)
+    }
 }
 
 impl Aseprite {
     /// Construct a [`Aseprite`] from a [`RawAseprite`]
     pub fn from_raw(raw: RawAseprite) -> AseResult<Self> {
         let mut tags = HashMap::new();
+        let mut tag_list = vec![];
         let mut layers = BTreeMap::new();
         let mut palette = None;
         let mut frame_infos = vec![];
-        let mut slices = HashMap::new();
+        let mut slice_keys: HashMap<String, Vec<AsepriteSlice>> = HashMap::new();
+        let mut user_data = None;
 
         let frame_count = raw.frames.len();
-
-        for frame in raw.frames {
+        // Old palette chunks are only used by files without a new one
+        let has_palette = raw.frames.iter().any(|frame| {
+            frame
+                .chunks
+                .iter()
+                .any(|chunk| matches!(chunk, RawAsepriteChunk::Palette { .. }))
+        });
+
+        for (frame_index, frame) in raw.frames.into_iter().enumerate() {
             frame_infos.push(AsepriteFrameInfo {
-                delay_ms: frame.duration_ms as usize,
+                duration: Duration::from_millis(frame.duration_ms as u64),
+                markers: vec![],
             });
 
+            // User data applies to the chunk it follows, the sprite's follows
+            // the palette of the first frame
+            let mut previous = UserDataOwner::Other;
             for chunk in frame.chunks {
+                let owner = std::mem::replace(
+                    &mut previous,
+                    match &chunk {
+                        RawAsepriteChunk::Cel { .. } => UserDataOwner::Cel,
+                        RawAsepriteChunk::Palette { .. } => UserDataOwner::Palette,
+                        RawAsepriteChunk::Slice { name, .. } => UserDataOwner::Slice(name.clone()),
+                        RawAsepriteChunk::Tags { .. } => UserDataOwner::Tag(tag_list.len()),
+                        _ => UserDataOwner::Other,
+                    },
+                );
                 match chunk {
                     RawAsepriteChunk::Layer {
                         flags,
@@ -92,12 +348,13 @@ impl Aseprite {
                             id,
                             name,
                             layer_type,
-                            flags & 0x1 != 0,
+                            AsepriteLayerFlags::from_bits_retain(flags),
                             blend_mode,
+                            // Files from old versions don't store a valid layer opacity
                             if raw.header.flags & 0x1 != 0 {
-                                Some(opacity)
+                                opacity
                             } else {
-                                None
+                                255
                             },
                             layer_child,
                         );
@@ -114,7 +371,13 @@ impl Aseprite {
                             .get_mut(&(layer_index as usize))
                             .ok_or(AsepriteInvalidError::InvalidLayer(layer_index as usize))?;
 
-                        layer.add_cel(AsepriteCel::new(x as f64, y as f64, opacity, cel))?;
+                        layer.add_cel(AsepriteCel::new(
+                            frame_index as u16,
+                            x as f64,
+                            y as f64,
+                            opacity,
+                            cel,
+                        ))?;
                     }
                     crate::raw::RawAsepriteChunk::CelExtra {
                         flags: _,
@@ -124,16 +387,24 @@ impl Aseprite {
                         height: _,
                     } => warn!("Not yet implemented cel extra"),
                     crate::raw::RawAsepriteChunk::Tags { tags: raw_tags } => {
-                        tags.extend(raw_tags.into_iter().map(|raw_tag| {
-                            (
-                                raw_tag.name.clone(),
-                                AsepriteTag {
-                                    frames: raw_tag.from..raw_tag.to + 1,
-                                    animation_direction: raw_tag.anim_direction,
-                                    name: raw_tag.name,
-                                },
-                            )
-                        }))
+                        for raw_tag in raw_tags {
+                            if raw_tag.from > raw_tag.to || raw_tag.to as usize >= frame_count {
+                                return Err(AsepriteError::InvalidConfiguration(
+                                    AsepriteInvalidError::InvalidTag(raw_tag.name),
+                                ));
+                            }
+
+                            let tag = AsepriteTag {
+                                frames: FrameSpan::new(raw_tag.from, raw_tag.to),
+                                animation_direction: raw_tag.anim_direction,
+                                color: raw_tag.color,
+                                name: raw_tag.name,
+                                user_data: None,
+                            };
+                            // The last tag with a name wins
+                            tags.insert(tag.name.clone(), tag.clone());
+                            tag_list.push(tag);
+                        }
                     }
                     crate::raw::RawAsepriteChunk::Palette {
                         palette_size,
@@ -141,40 +412,98 @@ impl Aseprite {
                         to_color: _,
                         entries,
                     } => {
-                        palette =
-                            Some(AsepritePalette::from_raw(palette_size, from_color, entries));
+                        palette
+                            .get_or_insert_with(|| AsepritePalette {
+                                entries: Vec::new(),
+                            })
+                            .update(palette_size, from_color, entries);
                     }
-                    crate::raw::RawAsepriteChunk::UserData { data: _ } => {
-                        warn!("Not yet implemented user data")
+                    crate::raw::RawAsepriteChunk::OldPalette { packets } => {
+                        if !has_palette {
+                            palette
+                                .get_or_insert_with(|| AsepritePalette {
+                                    entries: Vec::new(),
+                                })
+                                .update_old(packets);
+                        }
                     }
+                    crate::raw::RawAsepriteChunk::UserData { data } => match owner {
+                        UserDataOwner::Cel => {
+                            if let Some(text) = data.text {
+                                frame_infos[frame_index].markers.push(text);
+                            }
+                        }
+                        UserDataOwner::Palette if frame_index == 0 => {
+                            user_data = Some(AsepriteUserData {
+                                text: data.text,
+                                color: data.color,
+                            });
+                        }
+                        // Aseprite writes one for every tag, even without any data
+                        UserDataOwner::Tag(index) => {
+                            if index + 1 < tag_list.len() {
+                                previous = UserDataOwner::Tag(index + 1);
+                            }
+                            let user_data = match (&data.text, &data.color) {
+                                (None, None) => None,
+                                _ => Some(AsepriteUserData {
+                                    text: data.text,
+                                    color: data.color,
+                                }),
+                            };
+                            match tag_list.get_mut(index) {
+                                Some(tag) => tag.user_data = user_data,
+                                None => continue,
+                            }
+                            // The tag looked up by name is the last one with it
+                            let tag = &tag_list[index];
+                            if !tag_list[index + 1..]
+                                .iter()
+                                .any(|other| other.name == tag.name)
+                            {
+                                if let Some(last) = tags.get_mut(&tag.name) {
+                                    last.user_data = tag.user_data.clone();
+                                }
+                            }
+                        }
+                        // Applies to every key of the slice
+                        UserDataOwner::Slice(name) => {
+                            for key in slice_keys.entry(name).or_default() {
+                                key.user_data = Some(AsepriteUserData {
+                                    text: data.text.clone(),
+                                    color: data.color,
+                                });
+                            }
+                        }
+                        _ => {}
+                    },
                     crate::raw::RawAsepriteChunk::Slice {
                         flags: _,
                         name,
                         slices: raw_slices,
-                    } => slices.extend(raw_slices.into_iter().map(
-                        |crate::raw::RawAsepriteSlice {
-                             frame,
-                             x_origin,
-                             y_origin,
-                             width,
-                             height,
-                             nine_patch_info,
-                             pivot: _,
-                         }| {
-                            (
-                                name.clone(),
-                                AsepriteSlice {
-                                    name: name.clone(),
-                                    valid_frame: frame as u16,
-                                    position_x: x_origin,
-                                    position_y: y_origin,
-                                    width,
-                                    height,
-                                    nine_patch_info,
-                                },
-                            )
-                        },
-                    )),
+                    } => slice_keys.entry(name.clone()).or_default().extend(
+                        raw_slices.into_iter().map(
+                            |crate::raw::RawAsepriteSlice {
+                                 frame,
+                                 x_origin,
+                                 y_origin,
+                                 width,
+                                 height,
+                                 nine_patch_info,
+                                 pivot,
+                             }| AsepriteSlice {
+                                name: name.clone(),
+                                valid_frame: frame as u16,
+                                position_x: x_origin,
+                                position_y: y_origin,
+                                width,
+                                height,
+                                nine_patch_info,
+                                pivot,
+                                user_data: None,
+                            },
+                        ),
+                    ),
                     crate::raw::RawAsepriteChunk::ColorProfile {
                         profile_type: _,
                         flags: _,
@@ -185,6 +514,16 @@ impl Aseprite {
             }
         }
 
+        // The keys of a slice are ordered by frame, the last one is the
+        // slice as it was before keys were supported
+        for keys in slice_keys.values_mut() {
+            keys.sort_by_key(|key| key.valid_frame);
+        }
+        let slices = slice_keys
+            .iter()
+            .filter_map(|(name, keys)| Some((name.clone(), keys.last()?.clone())))
+            .collect();
+
         Ok(Aseprite {
             dimensions: (raw.header.width, raw.header.height),
             transparent_palette: if raw.header.color_depth == AsepriteColorDepth::Indexed {
@@ -193,11 +532,14 @@ impl Aseprite {
                 None
             },
             tags,
+            tag_list,
             layers,
             frame_count,
             palette,
             frame_infos,
             slices,
+            slice_keys,
+            user_data,
         })
     }
 
@@ -216,18 +558,232 @@ impl Aseprite {
 
         Self::from_raw(raw_aseprite)
     }
+
+    /// Construct a [`Aseprite`] from a `&[u8]`, checking the sizes stored in
+    /// the file in [strict](AsepriteParseMode::Strict) mode
+    ///
+    /// Helps finding where files from other exporters go wrong.
+    pub fn from_bytes_with<S: AsRef<[u8]>>(buffer: S, mode: AsepriteParseMode) -> AseResult<Self> {
+        let raw_aseprite = crate::raw::read_aseprite_with(buffer.as_ref(), mode)?;
+
+        Self::from_raw(raw_aseprite)
+    }
+
+    /// Combine files into one with their frames one after the other, e.g.
+    /// animations split across `Idle.aseprite` and `Run.aseprite`
+    ///
+    /// Each file comes with a name: its tags are renamed `<name>/<tag>` and a
+    /// `<name>` tag covers all of its frames. Layers are matched by name and
+    /// nesting, those missing from the earlier files are drawn on top of
+    /// theirs. The files need the same dimensions, and indexed files the same
+    /// palette. The user data of the sprite is the first file's.
+    pub fn merge<'a>(files: impl IntoIterator<Item = (&'a str, &'a Aseprite)>) -> AseResult<Self> {
+        let mut files = files.into_iter();
+        let (name, first) = files
+            .next()
+            .ok_or_else(|| AsepriteInvalidError::InvalidMerge("no files were given".to_string()))?;
+        let mut merged = Aseprite {
+            dimensions: first.dimensions,
+            tags: HashMap::new(),
+            tag_list: vec![],
+            slices: HashMap::new(),
+            slice_keys: HashMap::new(),
+            layers: BTreeMap::new(),
+            frame_count: 0,
+            palette: first.palette.clone(),
+            transparent_palette: first.transparent_palette,
+            frame_infos: vec![],
+            user_data: first.user_data.clone(),
+        };
+        for (name, file) in std::iter::once((name, first)).chain(files) {
+            merged.append(name, file)?;
+        }
+        merged.slices = merged
+            .slice_keys
+            .iter()
+            .filter_map(|(name, keys)| Some((name.clone(), keys.last()?.clone())))
+            .collect();
+        Ok(merged)
+    }
+
+    // Adds the frames of a file after the current ones
+    fn append(&mut self, name: &str, file: &Aseprite) -> AseResult<()> {
+        if file.dimensions != self.dimensions {
+            return Err(AsepriteInvalidError::InvalidMerge(format!(
+                "{} is {}x{} instead of {}x{}",
+                name, file.dimensions.0, file.dimensions.1, self.dimensions.0, self.dimensions.1
+            ))
+            .into());
+        }
+        if file.transparent_palette != self.transparent_palette
+            || (file.transparent_palette.is_some() && file.palette != self.palette)
+        {
+            return Err(AsepriteInvalidError::InvalidMerge(format!(
+                "{} has another palette",
+                name
+            ))
+            .into());
+        }
+
+        if file.frame_count == 0 {
+            return Err(
+                AsepriteInvalidError::InvalidMerge(format!("{} has no frames", name)).into(),
+            );
+        }
+
+        let offset = self.frame_count as u16;
+        for layer in file.layers.values() {
+            let id = match self.layers.values().find(|merged| {
+                merged.name() == layer.name()
+                    && merged.is_group() == layer.is_group()
+                    && merged.child_level() == layer.child_level()
+            }) {
+                Some(merged) => merged.id(),
+                None => {
+                    let id = self.layers.len();
+                    self.layers.insert(id, layer.without_cels(id));
+                    id
+                }
+            };
+            let merged = self
+                .layers
+                .get_mut(&id)
+                .expect("the layer was found or added");
+            for cel in layer.cels() {
+                let mut cel = cel.clone();
+                cel.frame += offset;
+                if let RawAsepriteCel::Linked { frame_position } = &mut cel.raw_cel {
+                    *frame_position += offset;
+                }
+                merged.add_cel(cel)?;
+            }
+        }
+
+        let frames = |span: FrameSpan| FrameSpan::new(span.from + offset, span.to + offset);
+        let mut tags = vec![AsepriteTag {
+            frames: FrameSpan::new(offset, offset + file.frame_count as u16 - 1),
+            animation_direction: AsepriteAnimationDirection::Forward,
+            color: AsepriteColor {
+                red: 0,
+                green: 0,
+                blue: 0,
+                alpha: 255,
+            },
+            name: name.to_string(),
+            user_data: None,
+        }];
+        tags.extend(file.tag_list.iter().map(|tag| AsepriteTag {
+            frames: frames(tag.frames),
+            name: format!("{}/{}", name, tag.name),
+            ..tag.clone()
+        }));
+        for tag in tags {
+            self.tags.insert(tag.name.clone(), tag.clone());
+            self.tag_list.push(tag);
+        }
+
+        for (name, keys) in &file.slice_keys {
+            self.slice_keys
+                .entry(name.clone())
+                .or_default()
+                .extend(keys.iter().map(|key| AsepriteSlice {
+                    valid_frame: key.valid_frame + offset,
+                    ..key.clone()
+                }));
+        }
+        self.frame_infos.extend(file.frame_infos.iter().cloned());
+        self.frame_count += file.frame_count;
+        Ok(())
+    }
 }
 
 /// The loaded aseprite file without image data
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AsepriteInfo {
     pub dimensions: (u16, u16),
+    /// Tags by their name, the last one of tags sharing a name
     pub tags: HashMap<String, AsepriteTag>,
+    /// Every tag in the order of the timeline, including tags sharing a name
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub tag_list: Vec<AsepriteTag>,
     pub slices: HashMap<String, AsepriteSlice>,
+    /// Every key of each slice, ordered by the frame they are valid from
+    pub slice_keys: HashMap<String, Vec<AsepriteSlice>>,
     pub frame_count: usize,
     pub palette: Option<AsepritePalette>,
     pub transparent_palette: Option<u8>,
     pub frame_infos: Vec<AsepriteFrameInfo>,
+    /// User data of the sprite itself, e.g. project-wide metadata such as
+    /// export scale hints
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub user_data: Option<AsepriteUserData>,
+}
+
+impl AsepriteInfo {
+    /// Get the tags which contain a frame, in the order of the timeline
+    ///
+    /// Tags can overlap, so a frame can be in several of them. Tags sharing a
+    /// name are all included.
+    pub fn tags_for_frame(&self, frame: u16) -> Vec<&AsepriteTag> {
+        self.tag_list
+            .iter()
+            .filter(|tag| tag.frames.contains(frame))
+            .collect()
+    }
+
+    /// Get the names used by more than one tag, sorted
+    pub fn duplicate_tags(&self) -> Vec<&str> {
+        let mut names: Vec<_> = self.tag_list.iter().map(|tag| tag.name.as_str()).collect();
+        names.sort_unstable();
+        let mut duplicates: Vec<_> = names
+            .windows(2)
+            .filter(|names| names[0] == names[1])
+            .map(|names| names[0])
+            .collect();
+        duplicates.dedup();
+        duplicates
+    }
+
+    /// Get the first tag in the timeline with a name
+    ///
+    /// Unlike [`tags`](Self::tags), this ignores how the tags were keyed.
+    pub fn first_tag_named(&self, name: &str) -> Option<&AsepriteTag> {
+        self.tag_list.iter().find(|tag| tag.name == name)
+    }
+
+    /// Key the tags with `key` instead of their name
+    ///
+    /// `key` is called with the index of each tag in the timeline, so tags
+    /// sharing a name can be kept apart.
+    pub fn rekey_tags(&mut self, key: impl Fn(usize, &AsepriteTag) -> String) {
+        self.tags = self
+            .tag_list
+            .iter()
+            .enumerate()
+            .map(|(index, tag)| (key(index, tag), tag.clone()))
+            .collect();
+    }
+
+    /// Get the key of a slice which is valid for a frame
+    ///
+    /// Slices can be moved and resized over the animation, each change
+    /// being a key valid from its frame until the next one.
+    pub fn slice_at(&self, name: &str, frame: u16) -> Option<&AsepriteSlice> {
+        self.slice_keys
+            .get(name)?
+            .iter()
+            .take_while(|key| key.valid_frame <= frame)
+            .last()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl AsepriteInfo {
+    /// Serialize the info to JSON, for tools which can't read aseprite files
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
 }
 
 impl Into<AsepriteInfo> for Aseprite {
@@ -235,49 +791,81 @@ impl Into<AsepriteInfo> for Aseprite {
         AsepriteInfo {
             dimensions: self.dimensions,
             tags: self.tags,
+            tag_list: self.tag_list,
             slices: self.slices,
+            slice_keys: self.slice_keys,
             frame_count: self.frame_count,
             palette: self.palette,
             transparent_palette: self.transparent_palette,
             frame_infos: self.frame_infos,
+            user_data: self.user_data,
         }
     }
 }
 
 /// The palette entries in the aseprite file
 #[allow(missing_docs)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AsepritePalette {
     pub entries: Vec<AsepriteColor>,
 }
 
 impl AsepritePalette {
-    fn from_raw(
+    // Palette chunks after the first only contain the entries which changed
+    fn update(
+        &mut self,
         palette_size: u32,
         from_color: u32,
         raw_entries: Vec<RawAsepritePaletteEntry>,
-    ) -> Self {
-        let mut entries = vec![
+    ) {
+        self.entries.resize(
+            palette_size as usize,
             AsepriteColor {
                 red: 0,
                 green: 0,
                 blue: 0,
-                alpha: 0
-            };
-            palette_size as usize
-        ];
-
-        for (raw_idx, idx) in ((from_color as usize)..entries.len()).enumerate() {
-            entries[idx] = raw_entries[raw_idx].color;
+                alpha: 0,
+            },
+        );
+
+        for (entry, raw_entry) in self
+            .entries
+            .iter_mut()
+            .skip(from_color as usize)
+            .zip(raw_entries)
+        {
+            *entry = raw_entry.color;
         }
+    }
 
-        AsepritePalette { entries }
+    // Old palette chunks list packets of entries, skipping the others
+    fn update_old(&mut self, packets: Vec<RawAsepriteOldPalettePacket>) {
+        let mut index = 0;
+        for packet in packets {
+            index += packet.skip as usize;
+            let end = index + packet.colors.len();
+            if self.entries.len() < end {
+                self.entries.resize(
+                    end,
+                    AsepriteColor {
+                        red: 0,
+                        green: 0,
+                        blue: 0,
+                        alpha: 0,
+                    },
+                );
+            }
+            self.entries[index..end].copy_from_slice(&packet.colors);
+            index = end;
+        }
     }
 }
 
 /// All the tags defined in the corresponding aseprite
 pub struct AsepriteTags<'a> {
     tags: &'a HashMap<String, AsepriteTag>,
+    tag_list: &'a [AsepriteTag],
 }
 
 impl<'a> AsepriteTags<'a> {
@@ -286,10 +874,26 @@ impl<'a> AsepriteTags<'a> {
         self.tags.get(name.as_ref())
     }
 
-    /// Get all available tags
+    /// Get every tag with a name, in the order of the timeline
+    ///
+    /// [`get_by_name`](Self::get_by_name) only returns the last of them.
+    pub fn get_all_by_name<'n>(&self, name: &'n str) -> impl Iterator<Item = &'a AsepriteTag> + 'n
+    where
+        'a: 'n,
+    {
+        self.tag_list.iter().filter(move |tag| tag.name == name)
+    }
+
+    /// Get all available tags, one per name
     pub fn all(&self) -> impl Iterator<Item = &AsepriteTag> {
         self.tags.values()
     }
+
+    /// Get every tag in the order of the timeline, including tags sharing a
+    /// name
+    pub fn in_order(&self) -> &'a [AsepriteTag] {
+        self.tag_list
+    }
 }
 
 impl<'a, 'r> Index<&'r str> for AsepriteTags<'a> {
@@ -300,19 +904,78 @@ impl<'a, 'r> Index<&'r str> for AsepriteTags<'a> {
     }
 }
 
-#[derive(Debug, Clone)]
+/// A range of frames including both ends, like tags in the timeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FrameSpan {
+    /// The first frame
+    pub from: u16,
+    /// The last frame, included in the span
+    pub to: u16,
+}
+
+impl FrameSpan {
+    /// Create a span from its first and last frames
+    pub fn new(from: u16, to: u16) -> Self {
+        Self { from, to }
+    }
+
+    /// Get the number of frames in the span
+    pub fn frame_count(&self) -> usize {
+        (self.to as usize + 1).saturating_sub(self.from as usize)
+    }
+
+    /// Returns `true` if the frame is part of the span
+    pub fn contains(&self, frame: u16) -> bool {
+        (self.from..=self.to).contains(&frame)
+    }
+
+    /// Iterate over the frames from first to last
+    pub fn iter(&self) -> RangeInclusive<u16> {
+        self.from..=self.to
+    }
+
+    /// Get the frames as a half-open range, e.g. to index frame lists
+    ///
+    /// The range is widened to `usize` so a span ending on the last `u16`
+    /// frame doesn't overflow.
+    pub fn as_range(&self) -> Range<usize> {
+        self.from as usize..self.to as usize + 1
+    }
+}
+
+impl IntoIterator for FrameSpan {
+    type Item = u16;
+    type IntoIter = RangeInclusive<u16>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 /// A single Aseprite tag
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AsepriteTag {
     /// The frames which this tag represents
-    pub frames: Range<u16>,
+    pub frames: FrameSpan,
     /// The direction of its animation
     pub animation_direction: AsepriteAnimationDirection,
+    /// The color of the tag in the timeline
+    ///
+    /// Often used to categorize tags, e.g. red for attacks
+    pub color: AsepriteColor,
     /// The tag name
     pub name: String,
+    /// Text and color set in the tag properties
+    ///
+    /// Only stored by Aseprite 1.3 and later.
+    pub user_data: Option<AsepriteUserData>,
 }
 
 #[derive(Debug, Clone)]
 /// A single Aseprite slice
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AsepriteSlice {
     /// The slice name
     pub name: String,
@@ -328,6 +991,18 @@ pub struct AsepriteSlice {
     pub height: u32,
     /// Nine-Patch Info if it exists
     pub nine_patch_info: Option<AsepriteNinePatchInfo>,
+    /// Pivot if it exists, relative to the slice position
+    pub pivot: Option<AsepritePivot>,
+    /// Text and color set in the slice properties, e.g. a localization key
+    /// for a text placeholder
+    pub user_data: Option<AsepriteUserData>,
+}
+
+impl AsepriteSlice {
+    /// Get the text of the slice's user data, if any
+    pub fn text(&self) -> Option<&str> {
+        self.user_data.as_ref()?.text.as_deref()
+    }
 }
 
 /// The layers inside an aseprite file
@@ -351,6 +1026,52 @@ impl<'a> AsepriteLayers<'a> {
     pub fn get_by_id(&self, id: usize) -> Option<&AsepriteLayer> {
         self.layers.get(&id)
     }
+
+    /// Get the layer groups, from the bottom to the top of the layer stack
+    pub fn groups(&self) -> impl Iterator<Item = &AsepriteLayer> {
+        self.layers.values().filter(|layer| layer.is_group())
+    }
+
+    /// Get the layers nested in a group, at any depth, from the bottom to the
+    /// top of the layer stack
+    ///
+    /// Returns `None` if no group has this name.
+    pub fn in_group(&self, name: &str) -> Option<Vec<&AsepriteLayer>> {
+        let (&group_id, group) = self
+            .layers
+            .iter()
+            .find(|(_, layer)| layer.is_group() && layer.name() == name)?;
+        // Children come right after their group, at a deeper level
+        let level = group.child_level();
+        Some(
+            self.layers
+                .range(group_id + 1..)
+                .map(|(_, layer)| layer)
+                .take_while(|layer| layer.child_level() > level)
+                .collect(),
+        )
+    }
+}
+
+bitflags::bitflags! {
+    /// The flags of a layer, as set in its properties in aseprite
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct AsepriteLayerFlags: u16 {
+        /// The layer is visible
+        const VISIBLE = 0x1;
+        /// The layer can be edited, i.e. it isn't locked
+        const EDITABLE = 0x2;
+        /// The cels of the layer can't be moved
+        const LOCK_MOVEMENT = 0x4;
+        /// The layer is the background layer
+        const BACKGROUND = 0x8;
+        /// New cels are linked to the previous one when possible
+        const PREFER_LINKED_CELS = 0x10;
+        /// The group is collapsed in the timeline
+        const COLLAPSED = 0x20;
+        /// The layer is a reference layer, not part of the sprite
+        const REFERENCE = 0x40;
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -362,6 +1083,8 @@ pub enum AsepriteLayer {
         name: String,
         /// Id of the layer
         id: usize,
+        /// Flags of the layer, as set in aseprite
+        flags: AsepriteLayerFlags,
         /// Visibility of the layer
         visible: bool,
         /// How deep it is nested in the layer hierarchy
@@ -373,12 +1096,16 @@ pub enum AsepriteLayer {
         name: String,
         /// Id of the layer
         id: usize,
+        /// Flags of the layer, as set in aseprite
+        flags: AsepriteLayerFlags,
         /// Blend mode of this layer
         blend_mode: AsepriteBlendMode,
-        /// Opacity of this layer (if enabled)
-        opacity: Option<u8>,
+        /// Opacity of this layer, 255 for files without valid layer opacities
+        opacity: u8,
         /// Visibility of this layer
         visible: bool,
+        /// Whether this is the background layer
+        background: bool,
         /// How deep it is nested in the layer hierarchy
         child_level: u16,
         /// Cels
@@ -391,24 +1118,29 @@ impl AsepriteLayer {
         id: usize,
         name: String,
         layer_type: AsepriteLayerType,
-        visible: bool,
+        flags: AsepriteLayerFlags,
         blend_mode: AsepriteBlendMode,
-        opacity: Option<u8>,
+        opacity: u8,
         child_level: u16,
     ) -> Self {
+        let visible = flags.contains(AsepriteLayerFlags::VISIBLE);
+        let background = flags.contains(AsepriteLayerFlags::BACKGROUND);
         match layer_type {
             AsepriteLayerType::Normal => AsepriteLayer::Normal {
                 name,
                 id,
+                flags,
                 blend_mode,
                 opacity,
                 visible,
+                background,
                 child_level,
                 cels: vec![],
             },
             AsepriteLayerType::Group => AsepriteLayer::Group {
                 name,
                 id,
+                flags,
                 visible,
                 child_level,
             },
@@ -429,6 +1161,24 @@ impl AsepriteLayer {
         }
     }
 
+    /// Get how deep the layer is nested in groups, `0` at the top level
+    pub fn child_level(&self) -> u16 {
+        match self {
+            AsepriteLayer::Group { child_level, .. }
+            | AsepriteLayer::Normal { child_level, .. } => *child_level,
+        }
+    }
+
+    /// Get the flags of the layer
+    ///
+    /// Besides visibility, these carry the authoring intent of the layer
+    /// (locked, preferring linked cels, ...) for tools editing the file.
+    pub fn flags(&self) -> AsepriteLayerFlags {
+        match self {
+            AsepriteLayer::Group { flags, .. } | AsepriteLayer::Normal { flags, .. } => *flags,
+        }
+    }
+
     /// Get the visibility of the layer
     pub fn is_visible(&self) -> bool {
         match self {
@@ -438,6 +1188,24 @@ impl AsepriteLayer {
         }
     }
 
+    /// Get the opacity of the layer
+    ///
+    /// Groups and files saved without valid layer opacities are fully opaque.
+    pub fn opacity(&self) -> u8 {
+        match self {
+            AsepriteLayer::Group { .. } => 255,
+            AsepriteLayer::Normal { opacity, .. } => *opacity,
+        }
+    }
+
+    /// Returns `true` if this is the background layer
+    pub fn is_background(&self) -> bool {
+        match self {
+            AsepriteLayer::Group { .. } => false,
+            AsepriteLayer::Normal { background, .. } => *background,
+        }
+    }
+
     /// Returns `true` if the aseprite layer is [`Group`].
     ///
     /// [`Group`]: AsepriteLayer::Group
@@ -446,6 +1214,127 @@ impl AsepriteLayer {
         matches!(self, Self::Group { .. })
     }
 
+    /// Get the cels of this layer, in frame order
+    ///
+    /// Frames without a cel in this layer are skipped, groups have no cels.
+    pub fn cels(&self) -> impl Iterator<Item = &AsepriteCel> {
+        let cels = match self {
+            AsepriteLayer::Group { .. } => &[][..],
+            AsepriteLayer::Normal { cels, .. } => &cels[..],
+        };
+        cels.iter()
+    }
+
+    // A copy of the layer with another id and no cels
+    fn without_cels(&self, new_id: usize) -> AsepriteLayer {
+        match self {
+            AsepriteLayer::Group {
+                name,
+                flags,
+                visible,
+                child_level,
+                ..
+            } => AsepriteLayer::Group {
+                name: name.clone(),
+                id: new_id,
+                flags: *flags,
+                visible: *visible,
+                child_level: *child_level,
+            },
+            AsepriteLayer::Normal {
+                name,
+                flags,
+                blend_mode,
+                opacity,
+                visible,
+                background,
+                child_level,
+                ..
+            } => AsepriteLayer::Normal {
+                name: name.clone(),
+                id: new_id,
+                flags: *flags,
+                blend_mode: *blend_mode,
+                opacity: *opacity,
+                visible: *visible,
+                background: *background,
+                child_level: *child_level,
+                cels: vec![],
+            },
+        }
+    }
+
+    // Compares everything but the cels
+    fn properties_eq(&self, other: &AsepriteLayer) -> bool {
+        match (self, other) {
+            (
+                AsepriteLayer::Group {
+                    name,
+                    visible,
+                    child_level,
+                    ..
+                },
+                AsepriteLayer::Group {
+                    name: other_name,
+                    visible: other_visible,
+                    child_level: other_child_level,
+                    ..
+                },
+            ) => name == other_name && visible == other_visible && child_level == other_child_level,
+            (
+                AsepriteLayer::Normal {
+                    name,
+                    blend_mode,
+                    opacity,
+                    visible,
+                    background,
+                    child_level,
+                    ..
+                },
+                AsepriteLayer::Normal {
+                    name: other_name,
+                    blend_mode: other_blend_mode,
+                    opacity: other_opacity,
+                    visible: other_visible,
+                    background: other_background,
+                    child_level: other_child_level,
+                    ..
+                },
+            ) => {
+                name == other_name
+                    && blend_mode == other_blend_mode
+                    && opacity == other_opacity
+                    && visible == other_visible
+                    && background == other_background
+                    && child_level == other_child_level
+            }
+            _ => false,
+        }
+    }
+
+    // The size and pixels drawn by a cel, following linked cels
+    fn cel_pixels<'a>(&'a self, cel: &'a AsepriteCel) -> Option<(u16, u16, &'a [AsepritePixel])> {
+        let cel = match cel.raw_cel {
+            RawAsepriteCel::Linked { frame_position } => {
+                self.get_cel(frame_position as usize).ok()?
+            }
+            _ => cel,
+        };
+        match &cel.raw_cel {
+            RawAsepriteCel::Raw {
+                width,
+                height,
+                pixels,
+            }
+            | RawAsepriteCel::Compressed {
+                width,
+                height,
+                pixels,
+            } => Some((*width, *height, pixels)),
+            RawAsepriteCel::Linked { .. } => None,
+        }
+    }
+
     fn cel_count(&self) -> usize {
         match self {
             AsepriteLayer::Group { .. } => 0,
@@ -471,9 +1360,13 @@ impl AsepriteLayer {
             AsepriteLayer::Group { id, .. } => Err(AsepriteError::InvalidConfiguration(
                 AsepriteInvalidError::InvalidLayer(*id),
             )),
-            AsepriteLayer::Normal { cels, .. } => cels.get(frame).ok_or(
-                AsepriteError::InvalidConfiguration(AsepriteInvalidError::InvalidFrame(frame)),
-            ),
+            // Cels are added in frame order, but frames without a cel are skipped
+            AsepriteLayer::Normal { cels, .. } => cels
+                .binary_search_by_key(&frame, |cel| cel.frame as usize)
+                .map(|idx| &cels[idx])
+                .map_err(|_| {
+                    AsepriteError::InvalidConfiguration(AsepriteInvalidError::InvalidFrame(frame))
+                }),
         }
     }
 }
@@ -481,6 +1374,7 @@ impl AsepriteLayer {
 #[derive(Debug, Clone)]
 /// A single cel in a frame in a layer
 pub struct AsepriteCel {
+    frame: u16,
     x: f64,
     y: f64,
     opacity: u8,
@@ -488,39 +1382,321 @@ pub struct AsepriteCel {
 }
 
 impl AsepriteCel {
-    fn new(x: f64, y: f64, opacity: u8, raw_cel: RawAsepriteCel) -> Self {
+    fn new(frame: u16, x: f64, y: f64, opacity: u8, raw_cel: RawAsepriteCel) -> Self {
         AsepriteCel {
+            frame,
             x,
             y,
             opacity,
             raw_cel,
         }
     }
-}
 
-/// The frames contained in an aseprite
-pub struct AsepriteFrames<'a> {
-    aseprite: &'a Aseprite,
-}
+    /// Get the frame this cel is in
+    pub fn frame(&self) -> u16 {
+        self.frame
+    }
 
-impl<'a> AsepriteFrames<'a> {
-    /// Get a range of frames
-    pub fn get_for(&self, range: &Range<u16>) -> AsepriteFrameRange {
-        AsepriteFrameRange {
-            aseprite: self.aseprite,
-            range: range.clone(),
+    /// Get the position of the cel, relative to the canvas
+    pub fn position(&self) -> (f64, f64) {
+        (self.x, self.y)
+    }
+
+    /// Get the opacity of the cel
+    pub fn opacity(&self) -> u8 {
+        self.opacity
+    }
+
+    /// Get the raw content of the cel
+    pub fn raw_cel(&self) -> &RawAsepriteCel {
+        &self.raw_cel
+    }
+
+    /// Get the rectangle covered by the cel, relative to the canvas
+    ///
+    /// Cels can reach outside of the canvas, see
+    /// [`Aseprite::clamp_cels_to_canvas`]. Linked cels return `None`, their
+    /// size is the one of the cel they link to.
+    pub fn bounds(&self) -> Option<AsepriteRect> {
+        match &self.raw_cel {
+            RawAsepriteCel::Raw { width, height, .. }
+            | RawAsepriteCel::Compressed { width, height, .. } => Some(AsepriteRect {
+                x: self.x as i32,
+                y: self.y as i32,
+                width: *width as u32,
+                height: *height as u32,
+            }),
+            RawAsepriteCel::Linked { .. } => None,
         }
     }
 
-    /// Get the amount of frames in this aseprite
-    pub fn count(&self) -> usize {
-        self.aseprite.frame_count
+    // Keeps only the pixels inside of `rect`, which has to be within the bounds
+    fn crop(&mut self, rect: AsepriteRect) {
+        let bounds = match self.bounds() {
+            Some(bounds) => bounds,
+            None => return,
+        };
+        if let RawAsepriteCel::Raw {
+            width,
+            height,
+            pixels,
+        }
+        | RawAsepriteCel::Compressed {
+            width,
+            height,
+            pixels,
+        } = &mut self.raw_cel
+        {
+            let left = (rect.x - bounds.x) as usize;
+            let top = (rect.y - bounds.y) as usize;
+            let mut cropped = Vec::with_capacity((rect.width * rect.height) as usize);
+            for y in 0..rect.height as usize {
+                for x in 0..rect.width as usize {
+                    cropped.push(pixels[left + x + (top + y) * *width as usize].clone());
+                }
+            }
+            *pixels = cropped;
+            *width = rect.width as u16;
+            *height = rect.height as u16;
+        }
+        self.x = rect.x as f64;
+        self.y = rect.y as f64;
     }
 }
 
-#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
-/// The nine slices in a nine-patch image
-#[allow(missing_docs)]
+/// Differences between two versions of an aseprite file, see [`Aseprite::diff`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AsepriteDiff {
+    /// The canvas was resized, every frame changed
+    pub dimensions_changed: bool,
+    /// Frames whose image changed, including added and removed frames
+    pub changed_frames: Vec<u16>,
+    /// Frames whose duration changed
+    pub retimed_frames: Vec<u16>,
+    /// Ids of the layers which were added, removed or changed
+    pub changed_layers: Vec<usize>,
+    /// Names of the tags which were added, removed or changed
+    pub changed_tags: Vec<String>,
+}
+
+impl AsepriteDiff {
+    /// Returns `true` if both versions are the same
+    pub fn is_empty(&self) -> bool {
+        self == &AsepriteDiff::default()
+    }
+}
+
+/// A rectangle in canvas coordinates
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AsepriteRect {
+    /// Left edge
+    pub x: i32,
+    /// Top edge
+    pub y: i32,
+    /// Width in pixels
+    pub width: u32,
+    /// Height in pixels
+    pub height: u32,
+}
+
+impl AsepriteRect {
+    /// Returns `true` if `other` lies entirely within this rectangle
+    pub fn contains(&self, other: &AsepriteRect) -> bool {
+        other.x >= self.x
+            && other.y >= self.y
+            && other.x as i64 + other.width as i64 <= self.x as i64 + self.width as i64
+            && other.y as i64 + other.height as i64 <= self.y as i64 + self.height as i64
+    }
+
+    /// Get the part covered by both rectangles, if they overlap
+    pub fn intersect(&self, other: &AsepriteRect) -> Option<AsepriteRect> {
+        let min_x = self.x.max(other.x);
+        let min_y = self.y.max(other.y);
+        let max_x = (self.x as i64 + self.width as i64).min(other.x as i64 + other.width as i64);
+        let max_y = (self.y as i64 + self.height as i64).min(other.y as i64 + other.height as i64);
+        if max_x <= min_x as i64 || max_y <= min_y as i64 {
+            return None;
+        }
+        Some(AsepriteRect {
+            x: min_x,
+            y: min_y,
+            width: (max_x - min_x as i64) as u32,
+            height: (max_y - min_y as i64) as u32,
+        })
+    }
+}
+
+/// The frames of a tag packed into a single image, see [`Aseprite::tag_sheet`]
+#[derive(Debug, Clone)]
+pub struct AsepriteTagSheet {
+    /// The packed frames
+    pub image: RgbaImage,
+    /// Where each frame is in the image, in file order
+    pub frames: Vec<AsepriteTagSheetFrame>,
+    /// The direction the tag is played in
+    pub animation_direction: AsepriteAnimationDirection,
+}
+
+/// A frame inside an [`AsepriteTagSheet`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsepriteTagSheetFrame {
+    /// The index of the frame in the file
+    pub frame: u16,
+    /// Where the frame is in the sheet
+    pub rect: AsepriteRect,
+    /// How long the frame is shown
+    pub duration: Duration,
+}
+
+/// The frames contained in an aseprite
+pub struct AsepriteFrames<'a> {
+    aseprite: &'a Aseprite,
+}
+
+impl<'a> AsepriteFrames<'a> {
+    /// Get a range of frames
+    ///
+    /// The range is clamped to the frames of the file, with a warning if it
+    /// reaches past them.
+    pub fn get_for(&self, range: &Range<u16>) -> AsepriteFrameRange {
+        self.get_for_usize(range.start as usize..range.end as usize)
+    }
+
+    /// Get the frames of a span, e.g. of a tag
+    ///
+    /// Like [`get_for`](Self::get_for), the span is clamped to the frames of
+    /// the file.
+    pub fn get_for_span(&self, span: FrameSpan) -> AsepriteFrameRange {
+        self.get_for_usize(span.as_range())
+    }
+
+    fn get_for_usize(&self, range: Range<usize>) -> AsepriteFrameRange {
+        let end = range.end.min(self.aseprite.frame_count);
+        let start = range.start.min(end);
+        if (start..end) != range {
+            warn!(
+                "Frames {:?} are out of the {} frames of the file, using {:?}",
+                range,
+                self.aseprite.frame_count,
+                start..end
+            );
+        }
+        AsepriteFrameRange {
+            aseprite: self.aseprite,
+            // Files have at most `u16::MAX` frames
+            range: start as u16..end as u16,
+        }
+    }
+
+    /// Get every frame of the file
+    pub fn all(&self) -> AsepriteFrameRange {
+        self.get_for(&(0..self.aseprite.frame_count as u16))
+    }
+
+    /// Get the amount of frames in this aseprite
+    pub fn count(&self) -> usize {
+        self.aseprite.frame_count
+    }
+
+    /// Get a single frame
+    pub fn get(&self, index: u16) -> Option<AsepriteFrame<'a>> {
+        ((index as usize) < self.aseprite.frame_count).then_some(AsepriteFrame {
+            aseprite: self.aseprite,
+            index,
+        })
+    }
+
+    /// Iterate over all frames
+    pub fn iter(&self) -> impl Iterator<Item = AsepriteFrame<'a>> + 'a {
+        let aseprite = self.aseprite;
+        (0..aseprite.frame_count as u16).map(move |index| AsepriteFrame { aseprite, index })
+    }
+
+    /// Get the identifier of every frame, see [`AsepriteFrameId`]
+    pub fn ids(&self) -> AseResult<Vec<AsepriteFrameId>> {
+        self.iter().map(|frame| frame.id()).collect()
+    }
+}
+
+/// Identifies the composited image of a frame
+///
+/// Frames showing the same pixels get the same id, whatever their index,
+/// layers or file. The hash (64 bit FNV-1a of the size and RGBA pixels) is
+/// stable across platforms and versions, so peers can check they render the
+/// same frame and caches are only invalidated when the visuals change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AsepriteFrameId(pub u64);
+
+impl AsepriteFrameId {
+    /// Get the id of an image
+    pub fn of(image: &RgbaImage) -> Self {
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let size = [image.width().to_le_bytes(), image.height().to_le_bytes()];
+        for byte in size.iter().flatten().chain(image.as_raw()) {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+        AsepriteFrameId(hash)
+    }
+}
+
+/// A single frame in an aseprite
+#[derive(Clone, Copy)]
+pub struct AsepriteFrame<'a> {
+    aseprite: &'a Aseprite,
+    index: u16,
+}
+
+impl<'a> AsepriteFrame<'a> {
+    /// Get the index of this frame
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+
+    /// Get how long this frame is shown
+    pub fn duration(&self) -> Duration {
+        self.aseprite.frame_infos[self.index as usize].duration
+    }
+
+    /// Get the image of this frame, with all visible layers composited
+    pub fn image(&self) -> AseResult<RgbaImage> {
+        image_for_frame(
+            self.aseprite,
+            self.index,
+            &AsepriteCompositeOptions::default(),
+        )
+    }
+
+    /// Get an identifier of what this frame shows, see [`AsepriteFrameId`]
+    pub fn id(&self) -> AseResult<AsepriteFrameId> {
+        Ok(AsepriteFrameId::of(&self.image()?))
+    }
+
+    /// Get the cels of this frame, along with the layer they are in
+    pub fn cels(&self) -> impl Iterator<Item = (&'a AsepriteLayer, &'a AsepriteCel)> + 'a {
+        let index = self.index as usize;
+        self.aseprite
+            .layers
+            .values()
+            .filter_map(move |layer| layer.get_cel(index).ok().map(|cel| (layer, cel)))
+    }
+
+    /// Get the tags which contain this frame, in the order of the timeline
+    ///
+    /// Tags sharing a name are all included.
+    pub fn tags_containing(&self) -> impl Iterator<Item = &'a AsepriteTag> + 'a {
+        let index = self.index;
+        self.aseprite
+            .tag_list
+            .iter()
+            .filter(move |tag| tag.frames.contains(index))
+    }
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
+/// The nine slices in a nine-patch image
+#[allow(missing_docs)]
 pub enum NineSlice {
     TopLeft,
     TopCenter,
@@ -533,6 +1709,27 @@ pub enum NineSlice {
     Center,
 }
 
+// Column and row of each of the nine slices
+const NINE_SLICES: [(NineSlice, usize, usize); 9] = [
+    (NineSlice::TopLeft, 0, 0),
+    (NineSlice::TopCenter, 1, 0),
+    (NineSlice::TopRight, 2, 0),
+    (NineSlice::LeftCenter, 0, 1),
+    (NineSlice::Center, 1, 1),
+    (NineSlice::RightCenter, 2, 1),
+    (NineSlice::BottomLeft, 0, 2),
+    (NineSlice::BottomCenter, 1, 2),
+    (NineSlice::BottomRight, 2, 2),
+];
+
+// Edges of the three columns (or rows) of a nine-patch. The center is
+// relative to the slice bounds, and clamped to them.
+fn nine_patch_edges(center: i32, center_size: u32, size: u32) -> [u32; 4] {
+    let start = (center as i64).clamp(0, size as i64);
+    let end = (center as i64 + center_size as i64).clamp(start, size as i64);
+    [0, start as u32, end as u32, size]
+}
+
 /// A single slice image
 ///
 /// Only contains nine-patch info if the aseprite also contained one
@@ -542,6 +1739,16 @@ pub struct AsepriteSliceImage {
     pub nine_slices: Option<HashMap<NineSlice, RgbaImage>>,
 }
 
+/// What happens to the parts of a slice outside of the canvas
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AsepriteSliceOverflow {
+    /// Leave them transparent, keeping the size of the slice
+    #[default]
+    Pad,
+    /// Fail with [`AsepriteInvalidError::SliceOutOfBounds`]
+    Error,
+}
+
 /// The slices contained in an aseprite
 pub struct AsepriteSlices<'a> {
     aseprite: &'a Aseprite,
@@ -558,114 +1765,96 @@ impl<'a> AsepriteSlices<'a> {
         self.aseprite.slices.values()
     }
 
+    /// Get every slice with the bounds it has at a frame
+    ///
+    /// Slices can be moved and resized over the animation, each change being
+    /// a key valid from its frame until the next one. Slices only starting
+    /// after the frame are left out.
+    pub fn at_frame(&self, frame: u16) -> impl Iterator<Item = &'a AsepriteSlice> + 'a {
+        self.aseprite.slice_keys.values().filter_map(move |keys| {
+            keys.iter()
+                .take_while(|key| key.valid_frame <= frame)
+                .last()
+        })
+    }
+
     /// Get the images represented by the slices
+    ///
+    /// Parts of the slices outside of the canvas are left transparent, see
+    /// [`get_images_with`](Self::get_images_with).
     pub fn get_images<I: Iterator<Item = &'a AsepriteSlice>>(
         &self,
         wanted_slices: I,
+    ) -> AseResult<Vec<AsepriteSliceImage>> {
+        self.get_images_with(wanted_slices, AsepriteSliceOverflow::default())
+    }
+
+    /// Get the images represented by the slices, choosing what happens to
+    /// slices reaching outside of the canvas
+    pub fn get_images_with<I: Iterator<Item = &'a AsepriteSlice>>(
+        &self,
+        wanted_slices: I,
+        overflow: AsepriteSliceOverflow,
     ) -> AseResult<Vec<AsepriteSliceImage>> {
         let mut slices = vec![];
+        let canvas = self.aseprite.canvas_rect();
 
         for slice in wanted_slices {
-            let frame = image_for_frame(self.aseprite, slice.valid_frame)?;
+            let bounds = AsepriteRect {
+                x: slice.position_x,
+                y: slice.position_y,
+                width: slice.width,
+                height: slice.height,
+            };
+            if overflow == AsepriteSliceOverflow::Error && !canvas.contains(&bounds) {
+                return Err(AsepriteError::InvalidConfiguration(
+                    AsepriteInvalidError::SliceOutOfBounds(slice.name.clone()),
+                ));
+            }
 
-            let image = image::imageops::crop_imm(
-                &frame,
-                slice.position_x.max(0) as u32,
-                slice.position_y.max(0) as u32,
-                slice.width,
-                slice.height,
-            )
-            .to_image();
+            let frame = image_for_frame(
+                self.aseprite,
+                slice.valid_frame,
+                &AsepriteCompositeOptions::default(),
+            )?;
+
+            // Only the part on the canvas is copied, the image keeps the size
+            // of the slice
+            let mut image = RgbaImage::new(slice.width, slice.height);
+            if let Some(visible) = canvas.intersect(&bounds) {
+                let part = image::imageops::crop_imm(
+                    &frame,
+                    visible.x as u32,
+                    visible.y as u32,
+                    visible.width,
+                    visible.height,
+                )
+                .to_image();
+                image::imageops::replace(
+                    &mut image,
+                    &part,
+                    visible.x as i64 - bounds.x as i64,
+                    visible.y as i64 - bounds.y as i64,
+                );
+            }
 
             let slice_image = AsepriteSliceImage {
                 nine_slices: slice.nine_patch_info.as_ref().map(|info| {
-                    let mut map: HashMap<_, RgbaImage> = HashMap::new();
-
-                    let patch_x = info.x_center as u32;
-                    let patch_y = info.y_center as u32;
-
-                    let x = 0;
-                    let y = 0;
-                    let width = patch_x;
-                    let height = patch_y;
-                    map.insert(
-                        NineSlice::TopLeft,
-                        image::imageops::crop_imm(&image, x, y, width, height).to_image(),
-                    );
-
-                    let x = patch_x;
-                    let y = 0;
-                    let width = info.width;
-                    let height = patch_y;
-                    map.insert(
-                        NineSlice::TopCenter,
-                        image::imageops::crop_imm(&image, x, y, width, height).to_image(),
-                    );
-
-                    let x = patch_x + info.width;
-                    let y = 0;
-                    let width = slice.width - info.width - patch_x;
-                    let height = patch_y;
-                    map.insert(
-                        NineSlice::TopRight,
-                        image::imageops::crop_imm(&image, x, y, width, height).to_image(),
-                    );
-
-                    let x = patch_x + info.width;
-                    let y = patch_y;
-                    let width = slice.width - info.width - patch_x;
-                    let height = info.height;
-                    map.insert(
-                        NineSlice::RightCenter,
-                        image::imageops::crop_imm(&image, x, y, width, height).to_image(),
-                    );
-
-                    let x = patch_x + info.width;
-                    let y = info.height + patch_y;
-                    let width = slice.width - info.width - patch_x;
-                    let height = slice.height - info.height - patch_y;
-                    map.insert(
-                        NineSlice::BottomRight,
-                        image::imageops::crop_imm(&image, x, y, width, height).to_image(),
-                    );
-
-                    let x = patch_x;
-                    let y = patch_y + info.height;
-                    let width = info.width;
-                    let height = slice.height - info.height - patch_y;
-                    map.insert(
-                        NineSlice::BottomCenter,
-                        image::imageops::crop_imm(&image, x, y, width, height).to_image(),
-                    );
-
-                    let x = 0;
-                    let y = patch_y + info.height;
-                    let width = patch_x;
-                    let height = slice.height - info.height - patch_y;
-                    map.insert(
-                        NineSlice::BottomLeft,
-                        image::imageops::crop_imm(&image, x, y, width, height).to_image(),
-                    );
-
-                    let x = 0;
-                    let y = patch_y;
-                    let width = patch_x;
-                    let height = info.height;
-                    map.insert(
-                        NineSlice::LeftCenter,
-                        image::imageops::crop_imm(&image, x, y, width, height).to_image(),
-                    );
-
-                    let x = patch_x;
-                    let y = patch_y;
-                    let width = info.width;
-                    let height = info.height;
-                    map.insert(
-                        NineSlice::Center,
-                        image::imageops::crop_imm(&image, x, y, width, height).to_image(),
-                    );
-
-                    map
+                    let columns = nine_patch_edges(info.x_center, info.width, slice.width);
+                    let rows = nine_patch_edges(info.y_center, info.height, slice.height);
+                    NINE_SLICES
+                        .iter()
+                        .map(|&(nine_slice, column, row)| {
+                            let patch = image::imageops::crop_imm(
+                                &image,
+                                columns[column],
+                                rows[row],
+                                columns[column + 1] - columns[column],
+                                rows[row + 1] - rows[row],
+                            );
+                            (nine_slice, patch.to_image())
+                        })
+                        .collect()
                 }),
                 image,
             };
@@ -679,9 +1868,31 @@ impl<'a> AsepriteSlices<'a> {
 
 /// Information about a single animation frame
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AsepriteFrameInfo {
+    /// How long this frame is shown
+    pub duration: Duration,
+    /// Texts of the user data of the cels in this frame, in layer order
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub markers: Vec<String>,
+}
+
+impl AsepriteFrameInfo {
     /// The delay of this frame in milliseconds
-    pub delay_ms: usize,
+    #[deprecated = "Use `duration` instead"]
+    pub fn delay_ms(&self) -> usize {
+        self.duration.as_millis() as usize
+    }
+}
+
+/// Text and color attached to an element of the file in aseprite
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AsepriteUserData {
+    /// Text, if any
+    pub text: Option<String>,
+    /// Color, if any
+    pub color: Option<AsepriteColor>,
 }
 
 /// A range of frames in an aseprite
@@ -698,55 +1909,313 @@ impl<'a> AsepriteFrameRange<'a> {
 
     /// Get the images represented by this range
     pub fn get_images(&self) -> AseResult<Vec<RgbaImage>> {
+        self.get_images_with(&AsepriteCompositeOptions::default())
+    }
+
+    /// Get the images represented by this range, composited with the given options
+    pub fn get_images_with(&self, options: &AsepriteCompositeOptions) -> AseResult<Vec<RgbaImage>> {
         let mut frames = vec![];
         for frame in self.range.clone() {
-            let image = image_for_frame(self.aseprite, frame)?;
+            let image = image_for_frame(self.aseprite, frame, options)?;
             frames.push(image);
         }
         Ok(frames)
     }
+
+    /// Get the RGBA pixels of each frame in this range, row by row, along
+    /// with their width and height
+    ///
+    /// Meant for engines uploading the frames as textures, without going
+    /// through the types of the `image` crate.
+    pub fn get_rgba_buffers(&self) -> AseResult<Vec<(Vec<u8>, u32, u32)>> {
+        self.get_rgba_buffers_with(&AsepriteCompositeOptions::default())
+    }
+
+    /// Get the RGBA pixels of each frame in this range, composited with the
+    /// given options
+    ///
+    /// See [`get_rgba_buffers`](Self::get_rgba_buffers)
+    pub fn get_rgba_buffers_with(
+        &self,
+        options: &AsepriteCompositeOptions,
+    ) -> AseResult<Vec<(Vec<u8>, u32, u32)>> {
+        let mut frames = vec![];
+        for frame in self.range.clone() {
+            let image = image_for_frame(self.aseprite, frame, options)?;
+            let (width, height) = image.dimensions();
+            frames.push((image.into_raw(), width, height));
+        }
+        Ok(frames)
+    }
+
+    /// Get the palette indices of each frame in this range, for indexed files
+    ///
+    /// Every image holds one palette index per pixel, row by row, with empty
+    /// pixels set to the transparent index. Returns
+    /// [`AsepriteError::NotIndexed`] for files using another color mode.
+    pub fn get_indexed_images(&self) -> AseResult<Vec<(Vec<u8>, &'a AsepritePalette)>> {
+        let transparent = match self.aseprite.transparent_palette {
+            Some(transparent) => transparent,
+            None => return Err(AsepriteError::NotIndexed),
+        };
+        let palette = match &self.aseprite.palette {
+            Some(palette) => palette,
+            None => {
+                return Err(AsepriteError::InvalidConfiguration(
+                    AsepriteInvalidError::InvalidPaletteIndex(transparent as usize),
+                ))
+            }
+        };
+
+        let mut frames = vec![];
+        for frame in self.range.clone() {
+            let indices = indices_for_frame(self.aseprite, frame, transparent)?;
+            frames.push((indices, palette));
+        }
+        Ok(frames)
+    }
 }
 
-fn image_for_frame(aseprite: &Aseprite, frame: u16) -> AseResult<RgbaImage> {
-    let dim = aseprite.dimensions;
-    let mut image = RgbaImage::new(dim.0 as u32, dim.1 as u32);
+/// Options used when compositing the layers of a frame into an image
+#[derive(Debug, Clone, Default)]
+pub struct AsepriteCompositeOptions {
+    /// Leave out the background layer
+    pub skip_background_layer: bool,
+    /// Color drawn below all layers, transparent if `None`
+    pub background_color: Option<AsepriteColor>,
+    /// Only composite the layers nested in this layer group
+    ///
+    /// Compositing fails with [`AsepriteInvalidError::InvalidGroup`] if no
+    /// group has this name.
+    pub layer_group: Option<String>,
+    /// Only composite the layer with this name, even if it is hidden
+    ///
+    /// Compositing fails with [`AsepriteInvalidError::InvalidLayerName`] if no
+    /// layer has this name.
+    pub layer: Option<String>,
+    /// Leave out the layers with these names
+    pub skip_layers: Vec<String>,
+    /// What is drawn for layers without a cel in the frame
+    pub missing_cel: AsepriteMissingCel,
+    /// Output premultiplied alpha instead of straight alpha
+    ///
+    /// The color channels of each pixel are multiplied by its alpha once all
+    /// layers are composited, as expected by additive or premultiplied blend
+    /// states.
+    pub premultiply_alpha: bool,
+    /// Pixels of this color are left out, as if they were transparent
+    ///
+    /// For files converted from formats using a color key (e.g. magenta)
+    /// instead of alpha. Only the red, green and blue channels are compared.
+    pub transparent_color: Option<AsepriteColor>,
+}
+
+/// What is drawn for a layer without a cel in a frame
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AsepriteMissingCel {
+    /// Nothing, like in aseprite
+    #[default]
+    Transparent,
+    /// The last cel of the layer in an earlier frame, if any
+    ///
+    /// Matches exporters holding the last drawn cel until the next one.
+    PreviousFrameHold,
+    /// Fail with [`AsepriteInvalidError::MissingCel`]
+    ///
+    /// Every visible layer needs a cel in every frame, even empty layers.
+    Error,
+}
+
+// Frame of the cel drawn for a layer, `None` if nothing is drawn
+fn cel_frame(
+    layer: &AsepriteLayer,
+    frame: u16,
+    missing_cel: AsepriteMissingCel,
+) -> AseResult<Option<u16>> {
+    if layer.get_cel(frame as usize).is_ok() {
+        return Ok(Some(frame));
+    }
+    match missing_cel {
+        AsepriteMissingCel::Transparent => Ok(None),
+        AsepriteMissingCel::PreviousFrameHold => Ok(layer
+            .cels()
+            .take_while(|cel| cel.frame < frame)
+            .last()
+            .map(|cel| cel.frame)),
+        AsepriteMissingCel::Error => Err(AsepriteInvalidError::MissingCel {
+            layer: layer.name().to_string(),
+            frame,
+        }
+        .into()),
+    }
+}
+
+// Palette indices of the visible layers of a frame, row by row
+fn indices_for_frame(aseprite: &Aseprite, frame: u16, transparent: u8) -> AseResult<Vec<u8>> {
+    let (width, height) = (
+        aseprite.dimensions.0 as usize,
+        aseprite.dimensions.1 as usize,
+    );
+    let mut indices = vec![transparent; width * height];
+
     for (_layer_id, layer) in &aseprite.layers {
         if !layer.is_visible() || layer.is_group() {
             continue;
         }
-
-        let mut blank_cel: AsepriteCel;
-
         let cel = match layer.get_cel(frame as usize) {
             Ok(aseprite_cel) => aseprite_cel,
-            Err(_) => {
-                blank_cel = AsepriteCel {
-                    x: 0.0,
-                    y: 0.0,
-                    opacity: 0,
-                    raw_cel: RawAsepriteCel::Raw {
-                        width: dim.0,
-                        height: dim.1,
-                        pixels: vec![
-                            AsepritePixel::RGBA(AsepriteColor {
-                                red: 0,
-                                green: 0,
-                                blue: 0,
-                                alpha: 0,
-                            });
-                            (dim.0 * dim.1) as usize
-                        ],
-                    },
-                };
-                &blank_cel
+            Err(_) => continue,
+        };
+        let source = match &cel.raw_cel {
+            RawAsepriteCel::Linked { frame_position } => layer.get_cel(*frame_position as usize)?,
+            _ => cel,
+        };
+        let (cel_width, cel_height, pixels) = match &source.raw_cel {
+            RawAsepriteCel::Raw {
+                width,
+                height,
+                pixels,
+            }
+            | RawAsepriteCel::Compressed {
+                width,
+                height,
+                pixels,
+            } => (*width, *height, pixels),
+            RawAsepriteCel::Linked { frame_position } => {
+                error!("Tried to draw a linked cel twice!");
+                return Err(AsepriteError::InvalidConfiguration(
+                    AsepriteInvalidError::InvalidFrame(*frame_position as usize),
+                ));
             }
         };
 
-        let mut write_to_image = |cel: &AsepriteCel,
-                                  width: u16,
-                                  height: u16,
-                                  pixels: &[AsepritePixel]|
-         -> AseResult<()> {
+        for x in 0..cel_width {
+            for y in 0..cel_height {
+                let pix_x = cel.x as i16 + x as i16;
+                let pix_y = cel.y as i16 + y as i16;
+
+                if pix_x < 0 || pix_y < 0 || pix_x as usize >= width || pix_y as usize >= height {
+                    continue;
+                }
+                // Upper layers cover lower ones, except where they are transparent
+                match pixels[(x + y * cel_width) as usize] {
+                    AsepritePixel::Indexed(index) if index != transparent => {
+                        indices[pix_x as usize + pix_y as usize * width] = index;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    Ok(indices)
+}
+
+fn image_for_frame(
+    aseprite: &Aseprite,
+    frame: u16,
+    options: &AsepriteCompositeOptions,
+) -> AseResult<RgbaImage> {
+    let dim = aseprite.dimensions;
+    let mut image = RgbaImage::new(dim.0 as u32, dim.1 as u32);
+    image_for_frame_into(aseprite, frame, options, &mut image)?;
+    Ok(image)
+}
+
+fn image_for_frame_into(
+    aseprite: &Aseprite,
+    frame: u16,
+    options: &AsepriteCompositeOptions,
+    image: &mut RgbaImage,
+) -> AseResult<()> {
+    let dim = aseprite.dimensions;
+    if image.dimensions() != (dim.0 as u32, dim.1 as u32) {
+        *image = RgbaImage::new(dim.0 as u32, dim.1 as u32);
+    }
+    let background = match options.background_color {
+        Some(color) => Rgba([color.red, color.green, color.blue, color.alpha]),
+        None => Rgba([0, 0, 0, 0]),
+    };
+    for pixel in image.pixels_mut() {
+        *pixel = background;
+    }
+
+    let group: Option<BTreeSet<usize>> = match &options.layer_group {
+        Some(name) => match aseprite.layers().in_group(name) {
+            Some(layers) => Some(layers.iter().map(|layer| layer.id()).collect()),
+            None => return Err(AsepriteInvalidError::InvalidGroup(name.clone()).into()),
+        },
+        None => None,
+    };
+    let only_layer = match &options.layer {
+        Some(name) => match aseprite.layers().get_by_name(name) {
+            Some(layer) => Some(layer.id()),
+            None => return Err(AsepriteInvalidError::InvalidLayerName(name.clone()).into()),
+        },
+        None => None,
+    };
+
+    for (layer_id, layer) in &aseprite.layers {
+        match only_layer {
+            Some(id) if id != *layer_id => continue,
+            Some(_) => {}
+            None if !layer.is_visible() => continue,
+            None => {}
+        }
+        if layer.is_group() {
+            continue;
+        }
+        if options.skip_layers.iter().any(|name| name == layer.name()) {
+            continue;
+        }
+        if group
+            .as_ref()
+            .is_some_and(|group| !group.contains(layer_id))
+        {
+            continue;
+        }
+        if options.skip_background_layer && layer.is_background() {
+            continue;
+        }
+        if let Some(frame) = cel_frame(layer, frame, options.missing_cel)? {
+            composite_layer_into(aseprite, layer, frame, options.transparent_color, image)?;
+        }
+    }
+
+    if options.premultiply_alpha {
+        premultiply_alpha(image);
+    }
+
+    Ok(())
+}
+
+fn premultiply_alpha(image: &mut RgbaImage) {
+    for pixel in image.pixels_mut() {
+        let alpha = pixel[3] as u16;
+        for channel in &mut pixel.0[..3] {
+            *channel = ((*channel as u16 * alpha + 127) / 255) as u8;
+        }
+    }
+}
+
+fn composite_layer_into(
+    aseprite: &Aseprite,
+    layer: &AsepriteLayer,
+    frame: u16,
+    transparent_color: Option<AsepriteColor>,
+    image: &mut RgbaImage,
+) -> AseResult<()> {
+    let color_key = transparent_color.map(|color| [color.red, color.green, color.blue]);
+    // Layers without a cel in this frame have nothing to draw
+    let cel = match layer.get_cel(frame as usize) {
+        Ok(aseprite_cel) => aseprite_cel,
+        Err(_) => return Ok(()),
+    };
+
+    let mut write_to_image =
+        |cel: &AsepriteCel, width: u16, height: u16, pixels: &[AsepritePixel]| -> AseResult<()> {
+            // Layer and cel opacity both scale the alpha of every pixel
+            let opacity = layer.opacity() as u32 * cel.opacity as u32;
             for x in 0..width {
                 for y in 0..height {
                     let pix_x = cel.x as i16 + x as i16;
@@ -755,11 +2224,20 @@ fn image_for_frame(aseprite: &Aseprite, frame: u16) -> AseResult<RgbaImage> {
                     if pix_x < 0 || pix_y < 0 {
                         continue;
                     }
+                    if pix_x as u32 >= image.width() || pix_y as u32 >= image.height() {
+                        continue;
+                    }
                     let raw_pixel = &pixels[(x + y * width) as usize];
-                    let pixel = Rgba(
+                    let mut pixel = Rgba(
                         raw_pixel
                             .get_rgba(aseprite.palette.as_ref(), aseprite.transparent_palette)?,
                     );
+                    if color_key.is_some_and(|key| pixel.0[..3] == key) {
+                        continue;
+                    }
+                    if opacity != 255 * 255 {
+                        pixel[3] = (pixel[3] as u32 * opacity / (255 * 255)) as u8;
+                    }
 
                     image
                         .get_pixel_mut(pix_x as u32, pix_y as u32)
@@ -769,43 +2247,854 @@ fn image_for_frame(aseprite: &Aseprite, frame: u16) -> AseResult<RgbaImage> {
             Ok(())
         };
 
-        match &cel.raw_cel {
-            RawAsepriteCel::Raw {
-                width,
-                height,
-                pixels,
-            }
-            | RawAsepriteCel::Compressed {
-                width,
-                height,
-                pixels,
-            } => {
-                write_to_image(cel, *width, *height, pixels)?;
-            }
-            RawAsepriteCel::Linked { frame_position } => {
-                match &layer.get_cel(*frame_position as usize)?.raw_cel {
-                    RawAsepriteCel::Raw {
-                        width,
-                        height,
-                        pixels,
-                    }
-                    | RawAsepriteCel::Compressed {
-                        width,
-                        height,
-                        pixels,
-                    } => {
-                        write_to_image(cel, *width, *height, pixels)?;
-                    }
-                    RawAsepriteCel::Linked { frame_position } => {
-                        error!("Tried to draw a linked cel twice!");
-                        return Err(AsepriteError::InvalidConfiguration(
-                            AsepriteInvalidError::InvalidFrame(*frame_position as usize),
-                        ));
-                    }
+    match &cel.raw_cel {
+        RawAsepriteCel::Raw {
+            width,
+            height,
+            pixels,
+        }
+        | RawAsepriteCel::Compressed {
+            width,
+            height,
+            pixels,
+        } => {
+            write_to_image(cel, *width, *height, pixels)?;
+        }
+        RawAsepriteCel::Linked { frame_position } => {
+            match &layer.get_cel(*frame_position as usize)?.raw_cel {
+                RawAsepriteCel::Raw {
+                    width,
+                    height,
+                    pixels,
+                }
+                | RawAsepriteCel::Compressed {
+                    width,
+                    height,
+                    pixels,
+                } => {
+                    write_to_image(cel, *width, *height, pixels)?;
+                }
+                RawAsepriteCel::Linked { frame_position } => {
+                    error!("Tried to draw a linked cel twice!");
+                    return Err(AsepriteError::InvalidConfiguration(
+                        AsepriteInvalidError::InvalidFrame(*frame_position as usize),
+                    ));
                 }
             }
         }
     }
 
-    Ok(image)
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use image::{Rgba, RgbaImage};
+
+    use super::{
+        Aseprite, AsepriteCompositeOptions, AsepriteFrameId, AsepriteInfo, AsepriteLayerFlags,
+        AsepriteMissingCel, AsepriteRect, AsepriteSliceOverflow, FrameSpan, NineSlice,
+    };
+    use crate::error::{AsepriteError, AsepriteInvalidError};
+    use crate::raw::{AsepriteAnimationDirection, AsepriteColor, AsepriteParseMode};
+
+    #[test]
+    fn iterate_frames() {
+        let aseprite =
+            Aseprite::from_path("./tests/test_cases/multiple_frames_layers.aseprite").unwrap();
+
+        let frames = aseprite.frames();
+        assert_eq!(frames.iter().count(), 4);
+        assert!(frames.get(4).is_none());
+
+        for (index, frame) in frames.iter().enumerate() {
+            assert_eq!(frame.index() as usize, index);
+            assert_eq!(frame.duration(), Duration::from_millis(100));
+            let tags: Vec<_> = frame
+                .tags_containing()
+                .map(|tag| tag.name.as_str())
+                .collect();
+            assert_eq!(tags, vec!["smile"]);
+        }
+    }
+
+    #[test]
+    fn missing_cels_are_skipped() {
+        let aseprite =
+            Aseprite::from_path("./tests/test_cases/multiple_frames_layers.aseprite").unwrap();
+
+        let images = aseprite.frames().get_for(&(0..4)).get_images().unwrap();
+        let color = Rgba([0x21, 0x18, 0x1b, 0xff]);
+
+        // The eyes are linked in the first three frames, the mouth is only
+        // drawn from frame 2 on
+        for image in &images[..3] {
+            assert_eq!(image.get_pixel(15, 15), &color);
+        }
+        assert_eq!(images[3].get_pixel(15, 15)[3], 0);
+        assert_eq!(images[0].get_pixel(9, 27)[3], 0);
+        assert_eq!(images[1].get_pixel(9, 27)[3], 0);
+        assert_eq!(images[2].get_pixel(9, 27), &color);
+        assert_eq!(images[3].get_pixel(23, 27), &color);
+    }
+
+    #[test]
+    fn rgba_buffers() {
+        let aseprite =
+            Aseprite::from_path("./tests/test_cases/multiple_frames_layers.aseprite").unwrap();
+        let frames = aseprite.frames();
+        let frames = frames.get_for(&(0..4));
+
+        let buffers = frames.get_rgba_buffers().unwrap();
+        let images = frames.get_images().unwrap();
+        assert_eq!(buffers.len(), 4);
+        for ((data, width, height), image) in buffers.iter().zip(&images) {
+            assert_eq!((*width, *height), image.dimensions());
+            assert_eq!(data, image.as_raw());
+        }
+    }
+
+    #[test]
+    fn cel_user_data_markers() {
+        let aseprite = Aseprite::from_path("./tests/test_cases/user_data.aseprite").unwrap();
+
+        let markers: Vec<_> = aseprite
+            .frame_infos()
+            .iter()
+            .map(|info| info.markers.clone())
+            .collect();
+        // The user data of the sprite and colors without text aren't markers
+        assert_eq!(
+            markers,
+            vec![
+                vec!["step".to_string()],
+                vec![],
+                vec!["step".into(), "hit".into()]
+            ]
+        );
+    }
+
+    #[test]
+    fn slice_user_data() {
+        let aseprite = Aseprite::from_path("./tests/test_cases/slice_user_data.aseprite").unwrap();
+        let slices = aseprite.slices();
+
+        let panel = slices.get_by_name("panel").unwrap();
+        assert_eq!(panel.text(), Some("menu.title"));
+        assert_eq!(
+            panel.user_data.as_ref().unwrap().color,
+            Some(AsepriteColor {
+                red: 255,
+                green: 0,
+                blue: 0,
+                alpha: 255
+            })
+        );
+        // A color without text
+        let icon = slices.get_by_name("icon").unwrap();
+        assert!(icon.user_data.is_some());
+        assert_eq!(icon.text(), None);
+        assert!(slices.get_by_name("plain").unwrap().user_data.is_none());
+    }
+
+    #[test]
+    fn tag_user_data() {
+        let aseprite = Aseprite::from_path("./tests/test_cases/tag_user_data.aseprite").unwrap();
+        let tags = aseprite.tags();
+        let text = |name| {
+            tags.get_by_name(name)
+                .unwrap()
+                .user_data
+                .as_ref()
+                .and_then(|user_data| user_data.text.clone())
+        };
+
+        assert_eq!(text("walk").as_deref(), Some("speed=2"));
+        assert_eq!(text("attack").as_deref(), Some("speed=0.5; once=true"));
+        // Empty user data is written for every tag
+        assert!(tags.get_by_name("idle").unwrap().user_data.is_none());
+        // The cel following the user data of the tags isn't a marker
+        assert!(aseprite.frame_infos()[0].markers.is_empty());
+    }
+
+    #[test]
+    fn sprite_user_data() {
+        let aseprite = Aseprite::from_path("./tests/test_cases/user_data.aseprite").unwrap();
+        let user_data = aseprite.user_data().unwrap();
+        assert_eq!(user_data.text.as_deref(), Some("scale=2"));
+        assert_eq!(user_data.color, None);
+
+        let info: AsepriteInfo = aseprite.into();
+        assert_eq!(info.user_data.unwrap().text.as_deref(), Some("scale=2"));
+
+        let aseprite = Aseprite::from_path("./tests/test_cases/simple.aseprite").unwrap();
+        assert!(aseprite.user_data().is_none());
+    }
+
+    #[test]
+    fn frame_image() {
+        let aseprite = Aseprite::from_path("./tests/test_cases/simple.aseprite").unwrap();
+
+        let frame = aseprite.frames().get(0).unwrap();
+        assert_eq!(frame.duration(), Duration::from_millis(125));
+        assert_eq!(frame.cels().count(), 1);
+        assert_eq!(frame.image().unwrap().dimensions(), (123, 456));
+    }
+
+    #[test]
+    fn image_into_existing_buffer() {
+        let aseprite =
+            Aseprite::from_path("./tests/test_cases/multiple_frames_layers.aseprite").unwrap();
+        let color = Rgba([0x21, 0x18, 0x1b, 0xff]);
+
+        let mut image = RgbaImage::new(0, 0);
+        aseprite.image_for_frame_into(2, &mut image).unwrap();
+        assert_eq!(image, aseprite.frames().get(2).unwrap().image().unwrap());
+
+        // Reusing the buffer clears what the previous frame drew
+        aseprite.image_for_frame_into(0, &mut image).unwrap();
+        assert_eq!(image.get_pixel(9, 27)[3], 0);
+
+        let layers = aseprite.layers();
+        let mouth = layers.get_by_name("Second Layer").unwrap();
+        aseprite.composite_layer_into(mouth, 2, &mut image).unwrap();
+        assert_eq!(image.get_pixel(9, 27), &color);
+        assert_eq!(image.get_pixel(15, 15), &color);
+
+        assert!(aseprite.image_for_frame_into(4, &mut image).is_err());
+    }
+
+    #[test]
+    fn indexed_images() {
+        let aseprite = Aseprite::from_path("../assets/crow.aseprite").unwrap();
+
+        let frames = aseprite.frames();
+        let frames = frames.get_for(&(0..1));
+        let rgba = frames.get_images().unwrap();
+        let indexed = frames.get_indexed_images().unwrap();
+        assert_eq!(indexed.len(), 1);
+
+        let (indices, palette) = &indexed[0];
+        let transparent = aseprite.transparent_palette.unwrap();
+        for (pixel, &index) in rgba[0].pixels().zip(indices.iter()) {
+            if index == transparent {
+                assert_eq!(pixel[3], 0);
+            } else {
+                let color = palette.entries[index as usize];
+                assert_eq!(pixel.0, [color.red, color.green, color.blue, color.alpha]);
+            }
+        }
+
+        let rgba_file = Aseprite::from_path("./tests/test_cases/simple.aseprite").unwrap();
+        assert!(rgba_file
+            .frames()
+            .get_for(&(0..1))
+            .get_indexed_images()
+            .is_err());
+    }
+
+    #[test]
+    fn straight_and_premultiplied_alpha() {
+        let aseprite =
+            Aseprite::from_path("./tests/test_cases/multiple_frames_layers.aseprite").unwrap();
+        let background = AsepriteColor {
+            red: 200,
+            green: 100,
+            blue: 50,
+            alpha: 128,
+        };
+
+        let straight = aseprite
+            .frames()
+            .get_for(&(0..1))
+            .get_images_with(&AsepriteCompositeOptions {
+                background_color: Some(background),
+                ..Default::default()
+            })
+            .unwrap();
+        let premultiplied = aseprite
+            .frames()
+            .get_for(&(0..1))
+            .get_images_with(&AsepriteCompositeOptions {
+                background_color: Some(background),
+                premultiply_alpha: true,
+                ..Default::default()
+            })
+            .unwrap();
+
+        assert_eq!(straight[0].get_pixel(0, 0), &Rgba([200, 100, 50, 128]));
+        assert_eq!(premultiplied[0].get_pixel(0, 0), &Rgba([100, 50, 25, 128]));
+        // Opaque pixels are the same either way
+        assert_eq!(
+            straight[0].get_pixel(15, 15),
+            premultiplied[0].get_pixel(15, 15)
+        );
+    }
+
+    #[test]
+    fn layer_opacity() {
+        let aseprite = Aseprite::from_path("./tests/test_cases/layer_opacity.aseprite").unwrap();
+        let layers = aseprite.layers();
+        let eyes = layers.get_by_name("First Layer").unwrap();
+        assert_eq!(eyes.opacity(), 128);
+
+        let image = aseprite.frames().get(0).unwrap().image().unwrap();
+        assert!((127..=128).contains(&image.get_pixel(15, 15)[3]));
+    }
+
+    #[test]
+    fn layer_opacity_without_header_flag() {
+        // Old versions of Aseprite don't set the header flag, the layer
+        // opacity stored in the file has to be ignored
+        let aseprite = Aseprite::from_path("./tests/test_cases/no_layer_opacity.aseprite").unwrap();
+        let layers = aseprite.layers();
+        let eyes = layers.get_by_name("First Layer").unwrap();
+        assert_eq!(eyes.opacity(), 255);
+
+        let image = aseprite.frames().get(0).unwrap().image().unwrap();
+        assert_eq!(image.get_pixel(15, 15), &Rgba([0x21, 0x18, 0x1b, 0xff]));
+    }
+
+    #[test]
+    fn tags_for_frame() {
+        let info: AsepriteInfo = Aseprite::from_path("../assets/crow.aseprite")
+            .unwrap()
+            .into();
+        let names = |frame| -> Vec<_> {
+            info.tags_for_frame(frame)
+                .into_iter()
+                .map(|tag| tag.name.as_str())
+                .collect()
+        };
+
+        // `groove` overlaps the middle of `flap_wings`
+        assert_eq!(names(0), vec!["flap_wings"]);
+        assert_eq!(names(3), vec!["flap_wings", "groove"]);
+        assert_eq!(names(5), vec!["flap_wings"]);
+        assert!(names(6).is_empty());
+
+        // Tags sharing a name are found even if they aren't in `tags`
+        let info: AsepriteInfo = Aseprite::from_path("./tests/test_cases/duplicate_tags.aseprite")
+            .unwrap()
+            .into();
+        let tags = |frame| -> Vec<_> {
+            info.tags_for_frame(frame)
+                .into_iter()
+                .map(|tag| (tag.name.as_str(), tag.frames))
+                .collect()
+        };
+        assert_eq!(
+            tags(1),
+            vec![
+                ("walk", FrameSpan::new(0, 1)),
+                ("idle", FrameSpan::new(1, 2))
+            ]
+        );
+        assert_eq!(
+            tags(2),
+            vec![
+                ("walk", FrameSpan::new(2, 3)),
+                ("idle", FrameSpan::new(1, 2))
+            ]
+        );
+    }
+
+    #[test]
+    fn layer_flags() {
+        let aseprite =
+            Aseprite::from_path("./tests/test_cases/multiple_frames_layers.aseprite").unwrap();
+        let layers = aseprite.layers();
+        let layer = layers.get_by_name("First Layer").unwrap();
+
+        assert_eq!(
+            layer.flags(),
+            AsepriteLayerFlags::VISIBLE | AsepriteLayerFlags::EDITABLE
+        );
+        assert!(!layer.flags().contains(AsepriteLayerFlags::LOCK_MOVEMENT));
+        assert!(!layer
+            .flags()
+            .contains(AsepriteLayerFlags::PREFER_LINKED_CELS));
+    }
+
+    #[test]
+    fn tag_sheet() {
+        let aseprite =
+            Aseprite::from_path("./tests/test_cases/multiple_frames_layers.aseprite").unwrap();
+
+        let sheet = aseprite.tag_sheet("smile", 3).unwrap();
+        assert_eq!(sheet.image.dimensions(), (96, 64));
+        assert_eq!(sheet.frames.len(), 4);
+        assert_eq!(
+            sheet.frames[3].rect,
+            AsepriteRect {
+                x: 0,
+                y: 32,
+                width: 32,
+                height: 32
+            }
+        );
+
+        // The eyes are in the first three frames, the mouth from the third one
+        let color = Rgba([0x21, 0x18, 0x1b, 0xff]);
+        assert_eq!(sheet.image.get_pixel(32 + 15, 15), &color);
+        assert_eq!(sheet.image.get_pixel(15, 32 + 15)[3], 0);
+        assert_eq!(sheet.image.get_pixel(9, 27)[3], 0);
+        assert_eq!(sheet.image.get_pixel(64 + 9, 27), &color);
+        assert_eq!(sheet.image.get_pixel(9, 32 + 27), &color);
+
+        assert!(aseprite.tag_sheet("frown", 3).is_err());
+    }
+
+    #[test]
+    fn slice_keys() {
+        let info: AsepriteInfo = Aseprite::from_path("../assets/crow.aseprite")
+            .unwrap()
+            .into();
+
+        // A single key valid from the first frame
+        let head = info.slice_at("head", 5).unwrap();
+        assert_eq!((head.position_x, head.position_y), (37, 27));
+        assert_eq!((head.width, head.height), (16, 19));
+        assert_eq!(head.pivot, None);
+        assert_eq!(info.slice_keys["head"].len(), 1);
+        assert!(info.slice_at("tail", 0).is_none());
+    }
+
+    #[test]
+    fn slices_at_frame() {
+        let aseprite = Aseprite::from_path("./tests/test_cases/slice_keys.aseprite").unwrap();
+        let slices = aseprite.slices();
+        let bounds = |frame: u16| {
+            let mut bounds: Vec<_> = slices
+                .at_frame(frame)
+                .map(|slice| (slice.name.as_str(), slice.position_x, slice.width))
+                .collect();
+            bounds.sort_unstable();
+            bounds
+        };
+
+        assert_eq!(bounds(0), vec![("hitbox", 0, 4)]);
+        assert_eq!(bounds(1), vec![("hitbox", 0, 4), ("late", 1, 2)]);
+        assert_eq!(bounds(2), vec![("hitbox", 2, 6), ("late", 1, 2)]);
+        // The last key stays valid until the end
+        assert_eq!(bounds(9), bounds(2));
+    }
+
+    #[test]
+    fn frame_span() {
+        let span = FrameSpan::new(2, 5);
+        assert_eq!(span.frame_count(), 4);
+        assert!(span.contains(5));
+        assert!(!span.contains(6));
+        assert_eq!(span.as_range(), 2..6);
+
+        let last = FrameSpan::new(u16::MAX - 1, u16::MAX);
+        assert_eq!(last.as_range().len(), 2);
+        assert_eq!(last.iter().count(), 2);
+    }
+
+    #[test]
+    fn layer_group() {
+        let aseprite = Aseprite::from_path("./tests/test_cases/layers.aseprite").unwrap();
+        let layers = aseprite.layers();
+        let names: Vec<_> = layers
+            .in_group("Group")
+            .unwrap()
+            .iter()
+            .map(|layer| layer.name())
+            .collect();
+        assert_eq!(names, vec!["Hidden", "Body"]);
+
+        let frames = aseprite.frames();
+        let full = frames.get_for(&(0..1)).get_images().unwrap();
+        let group = frames
+            .get_for(&(0..1))
+            .get_images_with(&AsepriteCompositeOptions {
+                layer_group: Some("Group".to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+        let blue = Rgba([30, 60, 210, 255]);
+        // The top layer is left out, the hidden one still isn't drawn
+        assert_eq!(*full[0].get_pixel(6, 6), Rgba([240, 220, 20, 255]));
+        assert_eq!(*group[0].get_pixel(6, 6), blue);
+        assert_eq!(*group[0].get_pixel(0, 0), Rgba([0, 0, 0, 0]));
+
+        let missing = frames
+            .get_for(&(0..1))
+            .get_images_with(&AsepriteCompositeOptions {
+                layer_group: Some("Body".to_string()),
+                ..Default::default()
+            });
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn metadata_mode() {
+        let bytes = std::fs::read("./tests/test_cases/layers.aseprite").unwrap();
+        let full = Aseprite::from_bytes(&bytes).unwrap();
+        let metadata = Aseprite::from_bytes_with(&bytes, AsepriteParseMode::Metadata).unwrap();
+        assert_eq!(metadata.frames().count(), full.frames().count());
+        assert_eq!(metadata.tags().in_order(), full.tags().in_order());
+        let hitbox = |aseprite: &Aseprite| {
+            let slices = aseprite.slices();
+            let slice = slices.get_by_name("hitbox").unwrap();
+            (
+                slice.position_x,
+                slice.position_y,
+                slice.width,
+                slice.height,
+            )
+        };
+        assert_eq!(hitbox(&metadata), hitbox(&full));
+        let names = |aseprite: &Aseprite| {
+            aseprite
+                .layers
+                .values()
+                .map(|layer| layer.name().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&metadata), names(&full));
+
+        // The cels are there, without pixels
+        let image = &metadata.frames().all().get_images().unwrap()[0];
+        assert!(image.pixels().all(|pixel| pixel.0[3] == 0));
+    }
+
+    #[test]
+    fn frame_range() {
+        let aseprite = Aseprite::from_path("./tests/test_cases/layers.aseprite").unwrap();
+        let frames = aseprite.frames();
+        assert_eq!(frames.all().get_images().unwrap().len(), 3);
+        assert_eq!(frames.get_for(&(1..1000)).get_images().unwrap().len(), 2);
+        assert_eq!(frames.get_for(&(5..1000)).get_infos().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn merge() {
+        let layers = Aseprite::from_path("./tests/test_cases/layers.aseprite").unwrap();
+        let merged = Aseprite::merge([("Idle", &layers), ("Run", &layers)]).unwrap();
+        assert_eq!(merged.frames().count(), 6);
+        assert_eq!(merged.layers().get_by_id(3).unwrap().name(), "Top");
+        assert!(merged.layers().get_by_id(4).is_none());
+
+        let tags = merged.tags();
+        assert_eq!(tags["Idle"].frames, FrameSpan::new(0, 2));
+        assert_eq!(tags["Run"].frames, FrameSpan::new(3, 5));
+        assert_eq!(tags["Run/bounce"].frames, FrameSpan::new(3, 5));
+        assert_eq!(
+            tags["Run/bounce"].animation_direction,
+            AsepriteAnimationDirection::PingPong
+        );
+        let hitbox = merged
+            .slices()
+            .at_frame(4)
+            .find(|slice| slice.name == "hitbox")
+            .unwrap();
+        assert_eq!(hitbox.valid_frame, 3);
+
+        // Linked cels point into the frames of their own file
+        let images = merged.frames().get_for(&(0..6)).get_images().unwrap();
+        assert_eq!(images[3], images[0]);
+        assert_eq!(images[4], images[1]);
+        assert_eq!(images[5], images[2]);
+
+        let indexed = Aseprite::from_path("./tests/test_cases/indexed.aseprite").unwrap();
+        assert!(Aseprite::merge([("Idle", &layers), ("Indexed", &indexed)]).is_err());
+        assert!(Aseprite::merge(std::iter::empty::<(&str, &Aseprite)>()).is_err());
+    }
+
+    #[test]
+    fn frame_ids() {
+        let layers = Aseprite::from_path("./tests/test_cases/layers.aseprite").unwrap();
+        let merged = Aseprite::merge([("Idle", &layers), ("Run", &layers)]).unwrap();
+        let ids = merged.frames().ids().unwrap();
+        assert_eq!(ids[..3], ids[3..]);
+        assert_eq!(ids[..3], layers.frames().ids().unwrap());
+
+        let companion =
+            Aseprite::from_path("./tests/test_cases/companion_layers.aseprite").unwrap();
+        let frames = companion.frames();
+        assert_ne!(
+            frames.get(0).unwrap().id().unwrap(),
+            frames.get(1).unwrap().id().unwrap()
+        );
+        assert_eq!(
+            frames.get(0).unwrap().id().unwrap(),
+            AsepriteFrameId::of(&frames.get(0).unwrap().image().unwrap())
+        );
+    }
+
+    #[test]
+    fn single_layer() {
+        let aseprite = Aseprite::from_path("./tests/test_cases/companion_layers.aseprite").unwrap();
+        let composite = |options: AsepriteCompositeOptions| {
+            aseprite.frames().get_for(&(0..1)).get_images_with(&options)
+        };
+        let red = Rgba([200, 30, 40, 255]);
+        let normal = Rgba([128, 128, 255, 255]);
+        let emissive = Rgba([255, 200, 0, 255]);
+
+        let full = composite(Default::default()).unwrap();
+        assert_eq!(*full[0].get_pixel(2, 2), normal);
+        let skipped = composite(AsepriteCompositeOptions {
+            skip_layers: vec!["normal".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(*skipped[0].get_pixel(2, 2), red);
+
+        // Hidden layers are drawn when asked for
+        let layer = composite(AsepriteCompositeOptions {
+            layer: Some("emissive".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(*layer[0].get_pixel(2, 2), emissive);
+        assert_eq!(*layer[0].get_pixel(1, 1), Rgba([0, 0, 0, 0]));
+
+        let missing = composite(AsepriteCompositeOptions {
+            layer: Some("specular".to_string()),
+            ..Default::default()
+        });
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn transparent_color() {
+        let aseprite = Aseprite::from_path("./tests/test_cases/companion_layers.aseprite").unwrap();
+        let images = aseprite
+            .frames()
+            .get_for(&(0..1))
+            .get_images_with(&AsepriteCompositeOptions {
+                transparent_color: Some(AsepriteColor {
+                    red: 128,
+                    green: 128,
+                    blue: 255,
+                    alpha: 0,
+                }),
+                ..Default::default()
+            })
+            .unwrap();
+
+        // The keyed pixels of the normal layer let the layer below show
+        assert_eq!(*images[0].get_pixel(2, 2), Rgba([200, 30, 40, 255]));
+        assert_eq!(*images[0].get_pixel(0, 0), Rgba([0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn missing_cel() {
+        let aseprite = Aseprite::from_path("./tests/test_cases/missing_cels.aseprite").unwrap();
+        let composite = |missing_cel| {
+            aseprite
+                .frames()
+                .get_for(&(1..2))
+                .get_images_with(&AsepriteCompositeOptions {
+                    missing_cel,
+                    ..Default::default()
+                })
+                .map(|images| *images[0].get_pixel(0, 0))
+        };
+        let red = Rgba([200, 30, 40, 255]);
+        assert_eq!(
+            composite(AsepriteMissingCel::Transparent).unwrap(),
+            Rgba([0, 0, 0, 0])
+        );
+        assert_eq!(
+            composite(AsepriteMissingCel::PreviousFrameHold).unwrap(),
+            red
+        );
+        assert!(matches!(
+            composite(AsepriteMissingCel::Error),
+            Err(AsepriteError::InvalidConfiguration(
+                AsepriteInvalidError::MissingCel { frame: 1, .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn compatible_mode() {
+        let bytes = std::fs::read("./tests/test_cases/libresprite.aseprite").unwrap();
+        // The layer chunk has data left at its end
+        assert!(Aseprite::from_bytes(&bytes).is_err());
+
+        let aseprite = Aseprite::from_bytes_with(&bytes, AsepriteParseMode::Compatible).unwrap();
+        let image = aseprite.frames().get(0).unwrap().image().unwrap();
+        assert_eq!(*image.get_pixel(0, 0), Rgba([200, 30, 40, 255]));
+        assert_eq!(*image.get_pixel(1, 0), Rgba([20, 180, 60, 255]));
+        assert_eq!(*image.get_pixel(0, 1), Rgba([0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn duplicate_tags() {
+        let aseprite = Aseprite::from_path("./tests/test_cases/duplicate_tags.aseprite").unwrap();
+        let tags = aseprite.tags();
+        // The last tag sharing a name wins, like before they were all kept
+        assert_eq!(
+            tags.get_by_name("walk").unwrap().frames,
+            FrameSpan::new(2, 3)
+        );
+        let walks: Vec<_> = tags.get_all_by_name("walk").map(|tag| tag.frames).collect();
+        assert_eq!(walks, vec![FrameSpan::new(0, 1), FrameSpan::new(2, 3)]);
+        assert_eq!(tags.in_order().len(), 3);
+
+        let mut info: AsepriteInfo = aseprite.into();
+        assert_eq!(info.duplicate_tags(), vec!["walk"]);
+        info.rekey_tags(|index, tag| format!("{}:{}", index, tag.name));
+        let mut keys: Vec<_> = info.tags.keys().cloned().collect();
+        keys.sort();
+        assert_eq!(keys, vec!["0:walk", "1:walk", "2:idle"]);
+        assert_eq!(info.tags["1:walk"].frames, FrameSpan::new(2, 3));
+        assert_eq!(
+            info.first_tag_named("walk").unwrap().frames,
+            FrameSpan::new(0, 1)
+        );
+    }
+
+    #[test]
+    fn slices_out_of_bounds() {
+        let aseprite =
+            Aseprite::from_path("./tests/test_cases/slices_out_of_bounds.aseprite").unwrap();
+        let slices = aseprite.slices();
+        let image = |name: &str| {
+            let slice = slices.get_by_name(name).unwrap();
+            slices
+                .get_images(std::iter::once(slice))
+                .unwrap()
+                .remove(0)
+                .image
+        };
+        let red = Rgba([200, 30, 40, 255]);
+        let transparent = Rgba([0, 0, 0, 0]);
+
+        let left = image("left");
+        assert_eq!(left.dimensions(), (4, 3));
+        assert_eq!(*left.get_pixel(1, 0), transparent);
+        assert_eq!(*left.get_pixel(2, 0), red);
+
+        let corner = image("corner");
+        assert_eq!(corner.dimensions(), (4, 4));
+        assert_eq!(*corner.get_pixel(1, 1), red);
+        assert_eq!(*corner.get_pixel(2, 1), transparent);
+        assert_eq!(*corner.get_pixel(1, 2), transparent);
+
+        let outside = image("outside");
+        assert!(outside.pixels().all(|pixel| *pixel == transparent));
+
+        let inside = slices.get_by_name("inside").unwrap();
+        assert!(slices
+            .get_images_with(std::iter::once(inside), AsepriteSliceOverflow::Error)
+            .is_ok());
+        let left = slices.get_by_name("left").unwrap();
+        assert!(slices
+            .get_images_with(std::iter::once(left), AsepriteSliceOverflow::Error)
+            .is_err());
+    }
+
+    #[test]
+    fn nine_patch_edges_are_clamped() {
+        assert_eq!(super::nine_patch_edges(2, 4, 8), [0, 2, 6, 8]);
+        // Centers touching the edges leave empty borders
+        assert_eq!(super::nine_patch_edges(0, 8, 8), [0, 0, 8, 8]);
+        assert_eq!(super::nine_patch_edges(6, 2, 8), [0, 6, 8, 8]);
+        // Centers past the bounds are clamped to them
+        assert_eq!(super::nine_patch_edges(-2, 4, 8), [0, 0, 2, 8]);
+        assert_eq!(super::nine_patch_edges(6, 4, 8), [0, 6, 8, 8]);
+        assert_eq!(super::nine_patch_edges(10, 4, 8), [0, 8, 8, 8]);
+
+        let aseprite = Aseprite::from_path("./tests/test_cases/layers.aseprite").unwrap();
+        let slices = aseprite.slices();
+        let hitbox = slices.get_by_name("hitbox").unwrap();
+        let images = slices.get_images(std::iter::once(hitbox)).unwrap();
+        let nine_slices = images[0].nine_slices.as_ref().unwrap();
+        assert_eq!(nine_slices[&NineSlice::TopLeft].dimensions(), (2, 2));
+        assert_eq!(nine_slices[&NineSlice::Center].dimensions(), (4, 4));
+        assert_eq!(nine_slices[&NineSlice::RightCenter].dimensions(), (2, 4));
+    }
+
+    #[test]
+    fn clamp_cels_to_canvas() {
+        let mut aseprite =
+            Aseprite::from_path("./tests/test_cases/out_of_canvas.aseprite").unwrap();
+        let canvas = aseprite.canvas_rect();
+        assert_eq!(
+            canvas,
+            AsepriteRect {
+                x: 0,
+                y: 0,
+                width: 32,
+                height: 32
+            }
+        );
+
+        let outside: Vec<_> = aseprite
+            .frames()
+            .iter()
+            .flat_map(|frame| frame.cels())
+            .filter_map(|(_, cel)| cel.bounds())
+            .filter(|bounds| !canvas.contains(bounds))
+            .collect();
+        assert_eq!(
+            outside,
+            vec![AsepriteRect {
+                x: 25,
+                y: 27,
+                width: 15,
+                height: 1
+            }]
+        );
+
+        // Content outside of the canvas is skipped instead of panicking
+        let images = aseprite.frames().get_for(&(0..4)).get_images().unwrap();
+        assert_eq!(images[2].get_pixel(31, 27), &Rgba([0x21, 0x18, 0x1b, 0xff]));
+
+        assert_eq!(aseprite.clamp_cels_to_canvas(), 1);
+        assert_eq!(aseprite.clamp_cels_to_canvas(), 0);
+        let frame = aseprite.frames().get(2).unwrap();
+        let (_, mouth) = frame
+            .cels()
+            .find(|(layer, _)| layer.name() == "Second Layer")
+            .unwrap();
+        assert_eq!(
+            mouth.bounds(),
+            Some(AsepriteRect {
+                x: 25,
+                y: 27,
+                width: 7,
+                height: 1
+            })
+        );
+
+        let clamped = aseprite.frames().get_for(&(0..4)).get_images().unwrap();
+        assert_eq!(clamped, images);
+    }
+
+    #[test]
+    fn diff() {
+        let aseprite =
+            Aseprite::from_path("./tests/test_cases/multiple_frames_layers.aseprite").unwrap();
+        assert!(aseprite.diff(&aseprite).is_empty());
+
+        // The mouth moved, it is drawn from frame 2 on
+        let moved = Aseprite::from_path("./tests/test_cases/out_of_canvas.aseprite").unwrap();
+        let diff = aseprite.diff(&moved);
+        assert_eq!(diff.changed_frames, vec![2, 3]);
+        assert_eq!(diff.changed_layers, vec![1]);
+        assert!(diff.retimed_frames.is_empty());
+        assert!(diff.changed_tags.is_empty());
+        assert!(!diff.dimensions_changed);
+
+        // The eyes are in the first three frames
+        let opacity = Aseprite::from_path("./tests/test_cases/layer_opacity.aseprite").unwrap();
+        let diff = aseprite.diff(&opacity);
+        assert_eq!(diff.changed_frames, vec![0, 1, 2]);
+        assert_eq!(diff.changed_layers, vec![2]);
+
+        let simple = Aseprite::from_path("./tests/test_cases/simple.aseprite").unwrap();
+        let diff = aseprite.diff(&simple);
+        assert!(diff.dimensions_changed);
+        assert_eq!(diff.changed_tags, vec!["smile".to_string()]);
+    }
 }
@@ -101,6 +101,44 @@ pub enum AsepriteError {
     /// An invalid configuration was found while decoding
     #[error("Invalid configuration of the aseprite file")]
     InvalidConfiguration(#[from] AsepriteInvalidError),
+    /// Palette indices were requested from a file not using indexed colors
+    #[error("The aseprite file doesn't use indexed colors")]
+    NotIndexed,
+    /// No tag with the given name exists
+    #[error("No tag named {0} was found")]
+    TagNotFound(String),
+    /// A size stored in the file doesn't match its data, in strict mode
+    #[error("The {what} at byte {offset} has a stored size of {stored} bytes, but {actual} bytes were found")]
+    SizeMismatch {
+        /// What the size is of, `file`, `frame` or `chunk`
+        what: &'static str,
+        /// Byte offset of the stored size from the start of the file
+        offset: usize,
+        /// The stored size
+        stored: usize,
+        /// The size of the data
+        actual: usize,
+    },
+    /// A count stored in the file doesn't match its data, in strict mode
+    #[error("The file stores {stored} {what} at byte {offset}, but {actual} were found")]
+    CountMismatch {
+        /// What is counted
+        what: &'static str,
+        /// Byte offset of the stored count from the start of the file
+        offset: usize,
+        /// The stored count
+        stored: usize,
+        /// The count found in the data
+        actual: usize,
+    },
+    /// The file ends in the middle of a header, in strict mode
+    #[error("The file ends in the {what} header at byte {offset}")]
+    Truncated {
+        /// The header being read, `header`, `frame` or `chunk`
+        what: &'static str,
+        /// Byte offset of the header from the start of the file
+        offset: usize,
+    },
 }
 
 impl<'a> From<AsepriteParseError<&'a [u8]>> for AsepriteError {
@@ -124,6 +162,29 @@ pub enum AsepriteInvalidError {
     /// An invalid palette index was specified as a color
     #[error("An invalid palette index was specified as a color")]
     InvalidPaletteIndex(usize),
+    /// A tag references frames outside of the animation
+    #[error("The tag {0} references frames outside of the animation")]
+    InvalidTag(String),
+    /// A slice reaches outside of the canvas
+    #[error("The slice {0} reaches outside of the canvas")]
+    SliceOutOfBounds(String),
+    /// No layer group has this name
+    #[error("The layer group {0} doesn't exist")]
+    InvalidGroup(String),
+    /// No layer has this name
+    #[error("The layer {0} doesn't exist")]
+    InvalidLayerName(String),
+    /// Files can't be merged together
+    #[error("The files can't be merged: {0}")]
+    InvalidMerge(String),
+    /// A layer has no cel in a frame
+    #[error("The layer {layer} has no cel in frame {frame}")]
+    MissingCel {
+        /// Name of the layer
+        layer: String,
+        /// The frame without a cel
+        frame: u16,
+    },
 }
 
 pub(crate) type AseParseResult<'a, R> = IResult<&'a [u8], R, AsepriteParseError<&'a [u8]>>;
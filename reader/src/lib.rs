@@ -8,6 +8,8 @@ pub mod error;
 /// These are used to then construct the main [`Aseprite`] type.
 pub mod raw;
 
+mod cache;
 mod computed;
 
+pub use cache::AsepriteCache;
 pub use computed::*;
@@ -13,7 +13,7 @@ use nom::{
     number::complete::{le_i16, le_i32, le_u16, le_u32, le_u8},
     Finish,
 };
-use tracing::{debug, debug_span, error, info};
+use tracing::{debug, debug_span, error, info, warn};
 
 // As specified in https://github.com/aseprite/aseprite/blob/fc79146c56f941f834f28809f0d2c4d7fd60076c/docs/ase-file-specs.md
 
@@ -174,7 +174,8 @@ pub struct RawAsepriteFrame {
 
 /// A full RGBA color
 #[allow(missing_docs)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AsepriteColor {
     pub red: u8,
     pub green: u8,
@@ -238,7 +239,7 @@ fn aseprite_layer_type(input: &[u8]) -> AseParseResult<AsepriteLayerType> {
     ))
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 /// The different blend modes
 #[allow(missing_docs)]
 pub enum AsepriteBlendMode {
@@ -297,7 +298,7 @@ fn aseprite_blend_mode(input: &[u8]) -> AseParseResult<AsepriteBlendMode> {
     ))
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 /// A single pixel
 pub enum AsepritePixel {
     /// Pixel in RGBA format
@@ -323,10 +324,10 @@ impl AsepritePixel {
         match self {
             AsepritePixel::RGBA(color) => Ok([color.red, color.green, color.blue, color.alpha]),
             AsepritePixel::Grayscale { intensity, alpha } => Ok([
-                (*intensity / 2) as u8,
-                (*intensity / 2) as u8,
-                (*intensity / 2) as u8,
-                (*alpha / 2) as u8,
+                *intensity as u8,
+                *intensity as u8,
+                *intensity as u8,
+                *alpha as u8,
             ]),
             AsepritePixel::Indexed(idx) => {
                 if transparent_palette != Some(*idx) {
@@ -355,10 +356,17 @@ fn aseprite_pixel<'a>(
             Ok((input, AsepritePixel::RGBA(color)))
         }
         AsepriteColorDepth::Grayscale => {
-            let (input, intensity) = le_u16(input)?;
-            let (input, alpha) = le_u16(input)?;
+            // A byte for the value and one for the alpha
+            let (input, intensity) = le_u8(input)?;
+            let (input, alpha) = le_u8(input)?;
 
-            Ok((input, AsepritePixel::Grayscale { intensity, alpha }))
+            Ok((
+                input,
+                AsepritePixel::Grayscale {
+                    intensity: intensity.into(),
+                    alpha: alpha.into(),
+                },
+            ))
         }
         AsepriteColorDepth::Indexed => {
             let (input, index) = le_u8(input)?;
@@ -487,8 +495,9 @@ fn aseprite_cel<'a>(
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 /// Animation Direction
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AsepriteAnimationDirection {
     /// Forward animation direction
     ///
@@ -502,9 +511,18 @@ pub enum AsepriteAnimationDirection {
     ///
     /// Starts at beginning and reverses direction whenever it hits either end or beginning
     PingPong,
+    /// Reversed Ping-Pong animation direction, added in Aseprite 1.3
+    ///
+    /// Starts at the end and reverses direction whenever it hits either beginning or end
+    PingPongReverse,
 }
 
-fn aseprite_anim_direction(input: &[u8]) -> AseParseResult<AsepriteAnimationDirection> {
+// Directions added by later versions of Aseprite only fail strict parsing,
+// otherwise their tags play forward
+fn aseprite_anim_direction(
+    input: &[u8],
+    mode: AsepriteParseMode,
+) -> AseParseResult<AsepriteAnimationDirection> {
     let (input, dir) = le_u8(input)?;
 
     Ok((
@@ -513,11 +531,19 @@ fn aseprite_anim_direction(input: &[u8]) -> AseParseResult<AsepriteAnimationDire
             0 => AsepriteAnimationDirection::Forward,
             1 => AsepriteAnimationDirection::Reverse,
             2 => AsepriteAnimationDirection::PingPong,
-            unknown => {
+            3 => AsepriteAnimationDirection::PingPongReverse,
+            unknown if mode == AsepriteParseMode::Strict => {
                 return Err(nom::Err::Failure(
                     AsepriteParseError::InvalidAnimationDirection(unknown),
                 ));
             }
+            unknown => {
+                warn!(
+                    "Unknown animation direction {}, playing the tag forward",
+                    unknown
+                );
+                AsepriteAnimationDirection::Forward
+            }
         },
     ))
 }
@@ -530,16 +556,18 @@ pub struct RawAsepriteTag {
     pub to: u16,
     /// animation direction
     pub anim_direction: AsepriteAnimationDirection,
+    /// color of the tag, always opaque
+    pub color: AsepriteColor,
     /// name of the tag
     pub name: String,
 }
 
-fn aseprite_tag(input: &[u8]) -> AseParseResult<RawAsepriteTag> {
+fn aseprite_tag(input: &[u8], mode: AsepriteParseMode) -> AseParseResult<RawAsepriteTag> {
     let (input, from) = le_u16(input)?;
     let (input, to) = le_u16(input)?;
-    let (input, anim_direction) = aseprite_anim_direction(input)?;
+    let (input, anim_direction) = aseprite_anim_direction(input, mode)?;
     let (input, _) = take(8usize)(input)?;
-    let (input, _) = take(3usize)(input)?;
+    let (input, rgb) = take(3usize)(input)?;
     let (input, _) = take(1usize)(input)?;
     let (input, name) = aseprite_string(input)?;
 
@@ -549,6 +577,12 @@ fn aseprite_tag(input: &[u8]) -> AseParseResult<RawAsepriteTag> {
             from,
             to,
             anim_direction,
+            color: AsepriteColor {
+                red: rgb[0],
+                green: rgb[1],
+                blue: rgb[2],
+                alpha: 255,
+            },
             name,
         },
     ))
@@ -649,6 +683,13 @@ pub enum RawAsepriteChunk {
         /// the individual slices
         slices: Vec<RawAsepriteSlice>,
     },
+    /// A palette in the format of old Aseprite versions and LibreSprite
+    ///
+    /// Only read in [compatibility](AsepriteParseMode::Compatible) mode.
+    OldPalette {
+        /// Packets of consecutive palette entries
+        packets: Vec<RawAsepriteOldPalettePacket>,
+    },
     /// An embedded color profile
     ColorProfile {
         /// The type of color profile
@@ -664,6 +705,59 @@ pub enum RawAsepriteChunk {
     },
 }
 
+/// Consecutive entries of an old palette chunk
+pub struct RawAsepriteOldPalettePacket {
+    /// Amount of entries skipped since the previous packet
+    pub skip: u8,
+    /// Colors of the entries, always opaque
+    pub colors: Vec<AsepriteColor>,
+}
+
+fn old_palette_packet<'a>(
+    input: &'a [u8],
+    six_bit: bool,
+) -> AseParseResult<'a, RawAsepriteOldPalettePacket> {
+    let (input, skip) = le_u8(input)?;
+    let (input, color_count) = le_u8(input)?;
+    // 0 stands for 256 colors
+    let color_count = match color_count {
+        0 => 256,
+        color_count => color_count as usize,
+    };
+    let (input, colors) = count(
+        |input: &'a [u8]| {
+            let (input, rgb) = take(3usize)(input)?;
+            // The 0x0011 chunk stores colors in the 0..=63 range
+            let channel = |value: u8| match six_bit {
+                true => (value.min(63) as u16 * 255 / 63) as u8,
+                false => value,
+            };
+            Ok((
+                input,
+                AsepriteColor {
+                    red: channel(rgb[0]),
+                    green: channel(rgb[1]),
+                    blue: channel(rgb[2]),
+                    alpha: 255,
+                },
+            ))
+        },
+        color_count,
+    )(input)?;
+
+    Ok((input, RawAsepriteOldPalettePacket { skip, colors }))
+}
+
+fn old_palette_chunk(input: &[u8], six_bit: bool) -> AseParseResult<RawAsepriteChunk> {
+    let (input, packet_count) = le_u16(input)?;
+    let (input, packets) = count(
+        |input| old_palette_packet(input, six_bit),
+        packet_count as usize,
+    )(input)?;
+
+    Ok((input, RawAsepriteChunk::OldPalette { packets }))
+}
+
 /// A raw Icc Profile
 pub struct RawAsepriteIccProfile {
     /// The bytes of the icc profile
@@ -722,6 +816,7 @@ pub struct RawAsepriteSlice {
 
 #[derive(Debug, Clone)]
 /// 9-Patch slice info
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AsepriteNinePatchInfo {
     /// x center, relative to slice bounds
     pub x_center: i32,
@@ -750,7 +845,9 @@ fn aseprite_nine_patch_info(input: &[u8]) -> AseParseResult<AsepriteNinePatchInf
     ))
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 /// A raw pivot inside a slice
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AsepritePivot {
     /// x position, relative to origin
     pub x_pivot: i32,
@@ -862,14 +959,18 @@ fn palette_chunk(input: &[u8]) -> AseParseResult<RawAsepriteChunk> {
     ))
 }
 
-fn tags(input: &[u8], tag_count: u16) -> AseParseResult<Vec<RawAsepriteTag>> {
-    count(aseprite_tag, tag_count as usize)(input)
+fn tags(
+    input: &[u8],
+    tag_count: u16,
+    mode: AsepriteParseMode,
+) -> AseParseResult<Vec<RawAsepriteTag>> {
+    count(|input| aseprite_tag(input, mode), tag_count as usize)(input)
 }
 
-fn tags_chunk(input: &[u8]) -> AseParseResult<RawAsepriteChunk> {
+fn tags_chunk(input: &[u8], mode: AsepriteParseMode) -> AseParseResult<RawAsepriteChunk> {
     let (input, tag_count) = le_u16(input)?;
     let (input, _) = take(8usize)(input)?;
-    let (input, tags) = tags(input, tag_count)?;
+    let (input, tags) = tags(input, tag_count, mode)?;
 
     Ok((input, RawAsepriteChunk::Tags { tags }))
 }
@@ -902,6 +1003,7 @@ fn cel_extra_chunk(input: &[u8]) -> AseParseResult<RawAsepriteChunk> {
 fn cel_chunk<'a>(
     input: &'a [u8],
     header: &'_ RawAsepriteHeader,
+    mode: AsepriteParseMode,
 ) -> AseParseResult<'a, RawAsepriteChunk> {
     let (input, layer_index) = le_u16(input)?;
     let (input, x) = le_i16(input)?;
@@ -910,7 +1012,17 @@ fn cel_chunk<'a>(
     let (input, cel_type) = le_u16(input)?;
     let (input, _) = take(7usize)(input)?;
     // We do not immediately try to load the cel, as the reserved bytes are decoupled from the type itself
-    let (input, cel) = aseprite_cel(input, header, cel_type)?;
+    let (input, cel) = match (mode, cel_type) {
+        (AsepriteParseMode::Metadata, 0 | 2) => (
+            &input[input.len()..],
+            RawAsepriteCel::Raw {
+                width: 0,
+                height: 0,
+                pixels: vec![],
+            },
+        ),
+        _ => aseprite_cel(input, header, cel_type)?,
+    };
 
     Ok((
         input,
@@ -953,17 +1065,69 @@ fn layer_chunk(input: &[u8]) -> AseParseResult<RawAsepriteChunk> {
 fn aseprite_chunk<'a>(
     input: &'a [u8],
     header: &'_ RawAsepriteHeader,
+    mode: AsepriteParseMode,
 ) -> AseParseResult<'a, Option<RawAsepriteChunk>> {
     let input_len = input.len();
     let (input, chunk_size) = le_u32(input)?;
     let (input, chunk_type) = le_u16(input)?;
+    let mut data_size = (chunk_size as usize).saturating_sub(input_len - input.len());
+    // Some exporters write chunks reaching past the end of the file
+    if mode == AsepriteParseMode::Compatible && data_size > input.len() {
+        warn!("Chunk of kind {} is shorter than its size", chunk_type);
+        data_size = input.len();
+    }
     // Get the remaining data of this chunk and parse it as the corresponding type
-    let (input, chunk_data) = take(chunk_size as usize - (input_len - input.len()))(input)?;
+    let (input, chunk_data) = take(data_size)(input)?;
 
     let _span = debug_span!("chunk", chunk_type);
 
+    match chunk_content(chunk_type, chunk_data, header, mode) {
+        Ok(chunk) => Ok((input, chunk)),
+        Err(err) if mode == AsepriteParseMode::Compatible => {
+            warn!(
+                "Skipping chunk of kind {} which couldn't be parsed: {:?}",
+                chunk_type, err
+            );
+            Ok((input, None))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+// Parses a whole chunk, or only its start in compatibility mode so data left
+// by other exporters is ignored
+fn parse_chunk<'a, O>(
+    parser: impl FnMut(&'a [u8]) -> AseParseResult<'a, O>,
+    data: &'a [u8],
+    mode: AsepriteParseMode,
+) -> AseParseResult<'a, O> {
+    match mode {
+        AsepriteParseMode::Compatible => {
+            let mut parser = parser;
+            parser(data)
+        }
+        _ => all_consuming(parser)(data),
+    }
+}
+
+fn chunk_content<'a>(
+    chunk_type: u16,
+    chunk_data: &'a [u8],
+    header: &'_ RawAsepriteHeader,
+    mode: AsepriteParseMode,
+) -> Result<Option<RawAsepriteChunk>, nom::Err<AsepriteParseError<&'a [u8]>>> {
     let res =
         match chunk_type {
+            0x0004 | 0x0011 if mode == AsepriteParseMode::Compatible => Some(
+                parse_chunk(
+                    |input| old_palette_chunk(input, chunk_type == 0x0011),
+                    chunk_data,
+                    mode,
+                )
+                .map_err(|err| {
+                    err.map(|err| AsepriteParseError::InvalidPaletteChunk(Box::new(err)))
+                })?,
+            ),
             0x0004 => {
                 debug!("Ignoring chunk of kind {} (Old palette chunk)", chunk_type);
                 None
@@ -972,17 +1136,22 @@ fn aseprite_chunk<'a>(
                 debug!("Ignoring chunk of kind {} (Old palette chunk)", chunk_type);
                 None
             }
-            0x2004 => Some(all_consuming(layer_chunk)(chunk_data).map_err(|err| {
+            0x2004 => Some(parse_chunk(layer_chunk, chunk_data, mode).map_err(|err| {
                 err.map(|err| AsepriteParseError::InvalidLayerChunk(Box::new(err)))
             })?),
             0x2005 => Some(
-                all_consuming(|input: &'a [u8]| cel_chunk(input, header))(chunk_data).map_err(
-                    |err| err.map(|err| AsepriteParseError::InvalidCelChunk(Box::new(err))),
-                )?,
+                parse_chunk(
+                    |input: &'a [u8]| cel_chunk(input, header, mode),
+                    chunk_data,
+                    mode,
+                )
+                .map_err(|err| err.map(|err| AsepriteParseError::InvalidCelChunk(Box::new(err))))?,
+            ),
+            0x2006 => Some(
+                parse_chunk(cel_extra_chunk, chunk_data, mode).map_err(|err| {
+                    err.map(|err| AsepriteParseError::InvalidCelExtraChunk(Box::new(err)))
+                })?,
             ),
-            0x2006 => Some(all_consuming(cel_extra_chunk)(chunk_data).map_err(|err| {
-                err.map(|err| AsepriteParseError::InvalidCelExtraChunk(Box::new(err)))
-            })?),
             0x2007 => Some(color_profile_chunk(chunk_data).map_err(|err| {
                 err.map(|err| AsepriteParseError::InvalidColorProfileChunk(Box::new(err)))
             })?),
@@ -990,16 +1159,20 @@ fn aseprite_chunk<'a>(
                 info!("Got a deprecated profile chunk");
                 None
             }
-            0x2018 => Some(all_consuming(tags_chunk)(chunk_data).map_err(|err| {
-                err.map(|err| AsepriteParseError::InvalidTagsChunk(Box::new(err)))
-            })?),
-            0x2019 => Some(all_consuming(palette_chunk)(chunk_data).map_err(|err| {
+            0x2018 => Some(
+                parse_chunk(|input: &'a [u8]| tags_chunk(input, mode), chunk_data, mode).map_err(
+                    |err| err.map(|err| AsepriteParseError::InvalidTagsChunk(Box::new(err))),
+                )?,
+            ),
+            0x2019 => Some(parse_chunk(palette_chunk, chunk_data, mode).map_err(|err| {
                 err.map(|err| AsepriteParseError::InvalidPaletteChunk(Box::new(err)))
             })?),
-            0x2020 => Some(all_consuming(user_data_chunk)(chunk_data).map_err(|err| {
-                err.map(|err| AsepriteParseError::InvalidUserDataChunk(Box::new(err)))
-            })?),
-            0x2022 => Some(all_consuming(slice_chunk)(chunk_data).map_err(|err| {
+            0x2020 => Some(
+                parse_chunk(user_data_chunk, chunk_data, mode).map_err(|err| {
+                    err.map(|err| AsepriteParseError::InvalidUserDataChunk(Box::new(err)))
+                })?,
+            ),
+            0x2022 => Some(parse_chunk(slice_chunk, chunk_data, mode).map_err(|err| {
                 err.map(|err| AsepriteParseError::InvalidSliceChunk(Box::new(err)))
             })?),
             chunk_type => {
@@ -1008,7 +1181,7 @@ fn aseprite_chunk<'a>(
             }
         };
 
-    Ok((input, res.map(|(_, chunk)| chunk)))
+    Ok(res.map(|(_, chunk)| chunk))
 }
 
 const ASEPRITE_FRAME_MAGIC_NUMBER: u16 = 0xF1FA;
@@ -1016,6 +1189,7 @@ const ASEPRITE_FRAME_MAGIC_NUMBER: u16 = 0xF1FA;
 fn aseprite_frame<'a>(
     input: &'a [u8],
     header: &'_ RawAsepriteHeader,
+    mode: AsepriteParseMode,
 ) -> AseParseResult<'a, RawAsepriteFrame> {
     let (input, magic_number) = tag(&ASEPRITE_FRAME_MAGIC_NUMBER.to_le_bytes())(input)?;
     let (input, small_chunk_count) = le_u16(input)?;
@@ -1031,7 +1205,7 @@ fn aseprite_frame<'a>(
     };
 
     let (input, chunks) = count(
-        |input: &'a [u8]| aseprite_chunk(input, header),
+        |input: &'a [u8]| aseprite_chunk(input, header, mode),
         actual_count,
     )(input)?;
 
@@ -1054,13 +1228,17 @@ fn aseprite_frame<'a>(
 fn aseprite_frames<'a>(
     input: &'a [u8],
     header: &'_ RawAsepriteHeader,
+    mode: AsepriteParseMode,
 ) -> AseParseResult<'a, Vec<RawAsepriteFrame>> {
-    all_consuming(many1(
-        |input: &'a [u8]| -> AseParseResult<RawAsepriteFrame> {
-            let (input, _length) = le_u32(input)?;
-            aseprite_frame(input, header)
-        },
-    ))(input)
+    let frame = |input: &'a [u8]| -> AseParseResult<RawAsepriteFrame> {
+        let (input, _length) = le_u32(input)?;
+        aseprite_frame(input, header, mode)
+    };
+    match mode {
+        // Only read the frames listed in the header, ignoring trailing data
+        AsepriteParseMode::Compatible => count(frame, header.frames as usize)(input),
+        _ => all_consuming(many1(frame))(input),
+    }
 }
 
 /// A raw .aseprite file
@@ -1071,24 +1249,229 @@ pub struct RawAseprite {
     pub frames: Vec<RawAsepriteFrame>,
 }
 
-fn aseprite(input: &[u8]) -> AseParseResult<RawAseprite> {
+fn aseprite(input: &[u8], mode: AsepriteParseMode) -> AseParseResult<RawAseprite> {
     let (input, header) = aseprite_header(input)?;
-    let (input, frames) = aseprite_frames(input, &header)?;
+    let (input, frames) = aseprite_frames(input, &header, mode)?;
 
     Ok((input, RawAseprite { header, frames }))
 }
 
 /// Read a [`RawAseprite`] from memory
 pub fn read_aseprite(input: &[u8]) -> Result<RawAseprite, AsepriteError> {
-    let (_, ase) = aseprite(input).finish()?;
+    let (_, ase) = aseprite(input, AsepriteParseMode::Loose).finish()?;
+
+    Ok(ase)
+}
+
+/// How strictly the sizes stored in a file are checked while reading it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum AsepriteParseMode {
+    /// Only parse the content, sizes which don't match it are not noticed
+    /// until they break the parsing
+    #[default]
+    Loose,
+    /// Check the file size, frame lengths and chunk sizes first, see
+    /// [`read_layout`]
+    Strict,
+    /// Tolerate the quirks of files from LibreSprite and old Aseprite
+    /// versions
+    ///
+    /// Data left at the end of chunks or after the frames is ignored, chunks
+    /// shorter than their size or failing to parse are skipped with a
+    /// warning, and old palette chunks are read.
+    Compatible,
+    /// Like [`Loose`](Self::Loose), but the pixels of cels are skipped
+    /// without being decompressed
+    ///
+    /// Much faster for tools which only need the tags, slices, layers or
+    /// frame durations of a file. Its frames can't be composited, every cel
+    /// is empty.
+    Metadata,
+}
+
+/// Read a [`RawAseprite`] from memory, checking its sizes in strict mode
+pub fn read_aseprite_with(
+    input: &[u8],
+    mode: AsepriteParseMode,
+) -> Result<RawAseprite, AsepriteError> {
+    if mode == AsepriteParseMode::Strict {
+        read_layout(input)?;
+    }
+    let (_, ase) = aseprite(input, mode).finish()?;
 
     Ok(ase)
 }
 
+/// Where the frames and chunks of a file are, according to their stored sizes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawAsepriteLayout {
+    /// Size of the file, as stored in its header
+    pub file_size: usize,
+    /// The frames, in order
+    pub frames: Vec<RawAsepriteFrameLayout>,
+}
+
+/// Where a frame and its chunks are in a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawAsepriteFrameLayout {
+    /// Byte offset of the frame from the start of the file
+    pub offset: usize,
+    /// Length of the frame in bytes, including its header
+    pub length: usize,
+    /// The chunks of the frame, in order
+    pub chunks: Vec<RawAsepriteChunkLayout>,
+}
+
+/// Where a chunk is in a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawAsepriteChunkLayout {
+    /// Byte offset of the chunk from the start of the file
+    pub offset: usize,
+    /// Size of the chunk in bytes, including its header
+    pub size: usize,
+    /// Type of the chunk, e.g. `0x2005` for cels
+    pub chunk_type: u16,
+}
+
+const HEADER_SIZE: usize = 128;
+const FRAME_HEADER_SIZE: usize = 16;
+const CHUNK_HEADER_SIZE: usize = 6;
+
+fn u16_at(input: &[u8], offset: usize) -> Option<u16> {
+    let bytes = input.get(offset..offset + 2)?;
+    Some(u16::from_le_bytes(bytes.try_into().ok()?))
+}
+
+fn u32_at(input: &[u8], offset: usize) -> Option<u32> {
+    let bytes = input.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+// Reads a stored size, which has to be at least `min` and fit in `available`
+fn stored_size(
+    input: &[u8],
+    what: &'static str,
+    offset: usize,
+    size: Option<u32>,
+    min: usize,
+    available: usize,
+) -> Result<usize, AsepriteError> {
+    let size = match size {
+        Some(size) => size as usize,
+        None => return Err(AsepriteError::Truncated { what, offset }),
+    };
+    if size < min || size > available {
+        return Err(AsepriteError::SizeMismatch {
+            what,
+            offset,
+            stored: size,
+            actual: available.min(input.len() - offset),
+        });
+    }
+    Ok(size)
+}
+
+/// Read where the frames and chunks of a file are, checking that the stored
+/// sizes match the data exactly
+///
+/// Fails with the byte offset of the first size which doesn't match: the
+/// file size, a frame length or a chunk size, or the frame and chunk counts.
+/// Chunk contents aren't parsed.
+pub fn read_layout(input: &[u8]) -> Result<RawAsepriteLayout, AsepriteError> {
+    if input.len() < HEADER_SIZE {
+        return Err(AsepriteError::Truncated {
+            what: "header",
+            offset: 0,
+        });
+    }
+    let file_size = u32_at(input, 0).unwrap_or_default() as usize;
+    if file_size != input.len() {
+        return Err(AsepriteError::SizeMismatch {
+            what: "file",
+            offset: 0,
+            stored: file_size,
+            actual: input.len(),
+        });
+    }
+    let frame_count = u16_at(input, 6).unwrap_or_default() as usize;
+
+    let mut frames = vec![];
+    let mut offset = HEADER_SIZE;
+    while offset < input.len() {
+        let length = stored_size(
+            input,
+            "frame",
+            offset,
+            u32_at(input, offset),
+            FRAME_HEADER_SIZE,
+            input.len() - offset,
+        )?;
+        if u16_at(input, offset + 4) != Some(ASEPRITE_FRAME_MAGIC_NUMBER) {
+            return Err(AsepriteError::Parse(format!(
+                "Expected the magic number of a frame at byte {}",
+                offset
+            )));
+        }
+        let chunk_count = match u32_at(input, offset + 12) {
+            Some(0) | None => u16_at(input, offset + 6).unwrap_or_default() as usize,
+            Some(chunk_count) => chunk_count as usize,
+        };
+
+        let end = offset + length;
+        let mut chunks = vec![];
+        let mut chunk_offset = offset + FRAME_HEADER_SIZE;
+        for _ in 0..chunk_count {
+            let size = stored_size(
+                input,
+                "chunk",
+                chunk_offset,
+                u32_at(input, chunk_offset).filter(|_| chunk_offset + 4 <= end),
+                CHUNK_HEADER_SIZE,
+                end - chunk_offset,
+            )?;
+            chunks.push(RawAsepriteChunkLayout {
+                offset: chunk_offset,
+                size,
+                chunk_type: u16_at(input, chunk_offset + 4).unwrap_or_default(),
+            });
+            chunk_offset += size;
+        }
+        if chunk_offset != end {
+            return Err(AsepriteError::SizeMismatch {
+                what: "frame",
+                offset,
+                stored: length,
+                actual: chunk_offset - offset,
+            });
+        }
+
+        frames.push(RawAsepriteFrameLayout {
+            offset,
+            length,
+            chunks,
+        });
+        offset = end;
+    }
+    if frames.len() != frame_count {
+        return Err(AsepriteError::CountMismatch {
+            what: "frames",
+            offset: 6,
+            stored: frame_count,
+            actual: frames.len(),
+        });
+    }
+
+    Ok(RawAsepriteLayout { file_size, frames })
+}
+
 #[cfg(test)]
 #[allow(deprecated)]
 mod test {
-    use super::{aseprite_frames, aseprite_header, RawAsepriteHeader, ASEPRITE_MAGIC_NUMBER};
+    use super::{
+        aseprite_anim_direction, aseprite_frames, aseprite_header, read_layout,
+        AsepriteAnimationDirection, AsepriteParseMode, RawAsepriteHeader, ASEPRITE_MAGIC_NUMBER,
+    };
+    use crate::error::AsepriteError;
 
     #[test]
     fn check_valid_file_header() {
@@ -1124,7 +1507,8 @@ mod test {
 
         let (body, raw_header) = aseprite_header(&ase_file).unwrap();
 
-        let (rest, raw_body) = aseprite_frames(body, &raw_header).unwrap();
+        let (rest, raw_body) =
+            aseprite_frames(body, &raw_header, AsepriteParseMode::Loose).unwrap();
 
         assert_eq!(rest.len(), 0);
         assert_eq!(raw_body.len(), 1);
@@ -1132,4 +1516,54 @@ mod test {
 
         assert_eq!(frame.duration_ms, 125);
     }
+
+    #[test]
+    fn animation_directions() {
+        let (reverse, newer) = ([3], [7]);
+        let direction = |input, mode| aseprite_anim_direction(input, mode).map(|(_, d)| d);
+        assert_eq!(
+            direction(&reverse, AsepriteParseMode::Loose).unwrap(),
+            AsepriteAnimationDirection::PingPongReverse
+        );
+
+        // Directions of newer versions play forward, unless parsing strictly
+        assert_eq!(
+            direction(&newer, AsepriteParseMode::Loose).unwrap(),
+            AsepriteAnimationDirection::Forward
+        );
+        assert!(direction(&newer, AsepriteParseMode::Strict).is_err());
+    }
+
+    #[test]
+    fn layout() {
+        let ase_file = std::fs::read("./tests/test_cases/simple.aseprite").unwrap();
+        let layout = read_layout(&ase_file).unwrap();
+        assert_eq!(layout.file_size, 787);
+        assert_eq!(layout.frames.len(), 1);
+        let frame = &layout.frames[0];
+        assert_eq!((frame.offset, frame.length), (128, 787 - 128));
+        assert_eq!(frame.chunks[0].offset, 128 + 16);
+
+        let mut wrong_file_size = ase_file.clone();
+        wrong_file_size[0] += 1;
+        assert!(matches!(
+            read_layout(&wrong_file_size),
+            Err(AsepriteError::SizeMismatch {
+                what: "file",
+                offset: 0,
+                ..
+            })
+        ));
+
+        // The first chunk reaching into the next one
+        let mut wrong_chunk_size = ase_file.clone();
+        wrong_chunk_size[128 + 16] += 1;
+        assert!(matches!(
+            read_layout(&wrong_chunk_size),
+            Err(AsepriteError::SizeMismatch {
+                what: "chunk" | "frame",
+                ..
+            })
+        ));
+    }
 }
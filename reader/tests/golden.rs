@@ -0,0 +1,112 @@
+//! Compares the composited frames of the test files with golden images
+//!
+//! After an intended change of the output, run the tests with `UPDATE_GOLDEN`
+//! set to overwrite the golden images, and check the new images by eye.
+
+use std::path::Path;
+
+use bevy_aseprite_reader::{raw::AsepriteAnimationDirection, Aseprite, FrameSpan};
+use image::RgbaImage;
+
+fn load(name: &str) -> Aseprite {
+    let path = format!("./tests/test_cases/{name}.aseprite");
+    Aseprite::from_path(&path).unwrap_or_else(|err| panic!("Could not load {path}: {err}"))
+}
+
+fn assert_golden(name: &str) {
+    let aseprite = load(name);
+    for frame in aseprite.frames().iter() {
+        let image = frame.image().unwrap();
+        let path = format!("./tests/golden/{name}_{}.png", frame.index());
+        assert_image_eq(&image, Path::new(&path));
+    }
+}
+
+fn assert_image_eq(image: &RgbaImage, golden_path: &Path) {
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        image.save(golden_path).unwrap();
+        return;
+    }
+
+    let golden = image::open(golden_path)
+        .unwrap_or_else(|err| panic!("Could not open {}: {err}", golden_path.display()))
+        .to_rgba8();
+    assert_eq!(
+        image.dimensions(),
+        golden.dimensions(),
+        "{} has another size",
+        golden_path.display()
+    );
+
+    let mut differences = image
+        .enumerate_pixels()
+        .filter(|(x, y, pixel)| golden.get_pixel(*x, *y) != *pixel);
+    if let Some((x, y, pixel)) = differences.next() {
+        panic!(
+            "{} pixels differ from {}, the first at ({x}, {y}) is {:?} instead of {:?}",
+            differences.count() + 1,
+            golden_path.display(),
+            pixel.0,
+            golden.get_pixel(x, y).0,
+        );
+    }
+}
+
+#[test]
+fn simple() {
+    assert_golden("simple");
+}
+
+#[test]
+fn groups_and_linked_cels() {
+    assert_golden("multiple_frames_layers");
+}
+
+#[test]
+fn cels_outside_of_the_canvas() {
+    assert_golden("out_of_canvas");
+}
+
+#[test]
+fn indexed() {
+    assert_golden("indexed");
+}
+
+#[test]
+fn multiple_palettes() {
+    assert_golden("multiple_palettes");
+}
+
+#[test]
+fn grayscale() {
+    assert_golden("grayscale");
+}
+
+#[test]
+fn layers_tags_and_slices() {
+    assert_golden("layers");
+
+    let aseprite = load("layers");
+    let tags = aseprite.tags();
+    let tag = tags.get_by_name("bounce").unwrap();
+    assert_eq!(tag.frames, FrameSpan::new(0, 2));
+    assert_eq!(
+        tag.animation_direction,
+        AsepriteAnimationDirection::PingPong
+    );
+
+    let slices = aseprite.slices();
+    let slice = slices.get_by_name("hitbox").unwrap();
+    let nine_patch = slice.nine_patch_info.as_ref().unwrap();
+    assert_eq!(
+        (
+            nine_patch.x_center,
+            nine_patch.y_center,
+            nine_patch.width,
+            nine_patch.height
+        ),
+        (2, 2, 4, 4)
+    );
+    let pivot = slice.pivot.unwrap();
+    assert_eq!((pivot.x_pivot, pivot.y_pivot), (4, 8));
+}
@@ -1,9 +1,17 @@
-use std::time::Duration;
+use std::{ops::Range, time::Duration};
 
-use bevy::prelude::*;
+use bevy::{
+    asset::LoadState,
+    diagnostic::Diagnostics,
+    ecs::query::Has,
+    prelude::*,
+    render::{primitives::Aabb, view::NoFrustumCulling},
+    sprite::Anchor,
+};
 
-use crate::{Aseprite, AsepriteInfo};
+use crate::{diagnostics::AsepriteDiagnostics, group::AsepriteGroup, Aseprite, AsepriteInfo};
 use bevy_aseprite_reader as reader;
+use reader::raw::AsepriteAnimationDirection;
 
 /// A tag representing an animation
 #[derive(Debug, Default, Component, Copy, Clone, PartialEq, Eq)]
@@ -24,17 +32,166 @@ impl AsepriteTag {
     }
 }
 
+/// How an animation continues once it reaches the end of its tag
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub enum PlayMode {
+    /// Start over from the beginning of the tag
+    #[default]
+    Loop,
+    /// Pause on the last frame of the tag
+    ///
+    /// Ping-pong tags play a single pass forward and back.
+    Once,
+}
+
+/// How the tag of an animation is looked up in [`AsepriteInfo::tags`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub enum TagMatch {
+    /// The tag must be a key of the tags, as set by
+    /// [`TagKeys`](crate::TagKeys)
+    #[default]
+    Exact,
+    /// Play the first tag in the timeline with the name, e.g. to play
+    /// `"walk"` when tags are keyed with
+    /// [`TagKeys::IndexPrefixed`](crate::TagKeys::IndexPrefixed)
+    ///
+    /// Falls back to the key of a tag, e.g. `"1:walk"`.
+    FirstMatch,
+}
+
+impl TagMatch {
+    /// Find a tag in the info of a file
+    pub fn find<'a>(&self, info: &'a AsepriteInfo, tag: &str) -> Option<&'a reader::AsepriteTag> {
+        match self {
+            TagMatch::Exact => info.tags.get(tag),
+            TagMatch::FirstMatch => info.first_tag_named(tag).or_else(|| info.tags.get(tag)),
+        }
+    }
+}
+
+/// Playback settings of a tag, authored in the text of its user data
+///
+/// The text holds `key=value` pairs separated by `;`, e.g.
+/// `speed=1.5; once=true`:
+/// - `speed` multiplies the speed of the animation while the tag plays
+/// - `once` plays the tag a single time (`true`) or loops it (`false`),
+///   overriding the [`PlayMode`] of the animation
+///
+/// [`AsepriteAnimation`] applies them whenever it starts playing the tag.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct TagSettings {
+    pub speed: Option<f32>,
+    pub once: Option<bool>,
+}
+
+impl TagSettings {
+    /// Parse the settings from the text of the user data of a tag
+    ///
+    /// Unknown keys and invalid values are skipped with a warning.
+    pub fn parse(text: &str) -> Self {
+        let mut settings = Self::default();
+        for pair in text
+            .split(';')
+            .map(str::trim)
+            .filter(|pair| !pair.is_empty())
+        {
+            let (key, value) = match pair.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => {
+                    warn!("Tag setting {} has no value", pair);
+                    continue;
+                }
+            };
+            let valid = match key {
+                "speed" => value
+                    .parse()
+                    .map(|speed| settings.speed = Some(speed))
+                    .is_ok(),
+                "once" => value.parse().map(|once| settings.once = Some(once)).is_ok(),
+                _ => {
+                    warn!("Unknown tag setting {}", key);
+                    continue;
+                }
+            };
+            if !valid {
+                warn!("Invalid value {} for tag setting {}", value, key);
+            }
+        }
+        settings
+    }
+
+    /// Get the settings of a tag, the defaults if it has no text
+    pub fn of(tag: &reader::AsepriteTag) -> Self {
+        tag.user_data
+            .as_ref()
+            .and_then(|user_data| user_data.text.as_deref())
+            .map(Self::parse)
+            .unwrap_or_default()
+    }
+}
+
+/// The animation state of an aseprite
+///
+/// Build it with [`AsepriteAnimation::tag`] (or [`AsepriteAnimation::frames`])
+/// and the builder methods:
+///
+/// ```rust,ignore
+/// AsepriteAnimation::tag("run")
+///     .speed(1.5)
+///     .mode(PlayMode::Once)
+///     .starting_frame(2)
+///     .paused()
+/// ```
+///
+/// With the `serialize` feature the whole state can be saved and restored,
+/// e.g. in save games.
+///
+/// Time passing doesn't mark it as changed: `Changed<AsepriteAnimation>`
+/// matches when it shows another frame, stops at the end of its tag or is
+/// modified from outside of the plugin.
 #[derive(Debug, Component, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
 pub struct AsepriteAnimation {
     pub is_playing: bool,
     tag: Option<String>,
     pub current_frame: usize,
+    /// Size the sprite is drawn with, overriding `TextureAtlasSprite::custom_size`
+    ///
+    /// When `None` the size set on the sprite is used, so it can be edited
+    /// directly. Setting it back to `None` doesn't reset the sprite.
     pub custom_size: Option<Vec2>,
     forward: bool,
     time_elapsed: Duration,
     tag_changed: bool,
+    // Frames and direction used when no tag is set
+    frames: Option<Range<u16>>,
+    direction: AsepriteAnimationDirection,
+    min_frame_duration: Duration,
+    // Frame (relative to the tag) to pause on once reached
+    pause_at: Option<usize>,
+    frozen: bool,
+    // Set when seeking, so the sprite gets updated even while paused
+    seeked: bool,
+    speed: f32,
+    mode: PlayMode,
+    // Frame (relative to the tag) the animation starts from
+    starting_frame: usize,
+    // Passes through the tag completed since it started
+    loops: u32,
+    tag_match: TagMatch,
+    // Settings of the tag being played, from its user data
+    tag_settings: TagSettings,
+    // Frames shown and left again during the last update
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    skipped: Vec<usize>,
 }
 
+/// Default for [`AsepriteAnimation::with_min_frame_duration`]
+pub const DEFAULT_MIN_FRAME_DURATION: Duration = Duration::from_millis(1);
+
 impl Default for AsepriteAnimation {
     fn default() -> Self {
         Self {
@@ -45,136 +202,604 @@ impl Default for AsepriteAnimation {
             forward: Default::default(),
             time_elapsed: Default::default(),
             tag_changed: true,
+            frames: None,
+            direction: AsepriteAnimationDirection::Forward,
+            min_frame_duration: DEFAULT_MIN_FRAME_DURATION,
+            pause_at: None,
+            frozen: false,
+            seeked: false,
+            speed: 1.,
+            mode: PlayMode::Loop,
+            starting_frame: 0,
+            loops: 0,
+            tag_match: TagMatch::Exact,
+            tag_settings: TagSettings::default(),
+            skipped: vec![],
         }
     }
 }
 
 impl AsepriteAnimation {
-    fn reset(&mut self, info: &AsepriteInfo) {
-        self.tag_changed = false;
+    /// Play a tag
+    ///
+    /// Same as `AsepriteAnimation::from(tag)`, to start a builder chain.
+    pub fn tag(tag: impl Into<String>) -> Self {
+        Self {
+            tag: Some(tag.into()),
+            ..Default::default()
+        }
+    }
+
+    /// Switch a running animation to another tag
+    ///
+    /// Only the tag changes, the speed, mode, `custom_size` and whether it
+    /// is playing are kept, unlike replacing the component with
+    /// `AsepriteAnimation::from(tag)`. The tag starts over on the next
+    /// update, see [`goto`](Self::goto) to switch at once.
+    pub fn set_tag(&mut self, tag: impl Into<String>) {
+        self.tag = Some(tag.into());
+        self.tag_changed = true;
+    }
+
+    /// Play the animation faster or slower, `1.` being the speed of the file
+    ///
+    /// Negative speeds are treated as `0.`, play the tag in reverse with
+    /// a tagless range and [`reverse`](Self::reverse) instead.
+    pub fn speed(mut self, speed: f32) -> Self {
+        self.set_speed(speed);
+        self
+    }
+
+    /// Set the speed of a running animation, see [`speed`](Self::speed)
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.max(0.);
+    }
+
+    /// Get the speed of the animation
+    ///
+    /// The [`TagSettings`] of the tag being played aren't included.
+    pub fn playback_speed(&self) -> f32 {
+        self.speed
+    }
+
+    /// Get the settings of the tag being played, applied on top of the
+    /// speed and mode of the animation
+    pub fn tag_settings(&self) -> &TagSettings {
+        &self.tag_settings
+    }
+
+    /// Set what happens once the end of the tag is reached
+    pub fn mode(mut self, mode: PlayMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Start from a frame other than the first one of the tag
+    ///
+    /// The frame is relative to the start of the tag in its playing
+    /// direction, and clamped to its last frame.
+    pub fn starting_frame(mut self, frame_in_tag: usize) -> Self {
+        self.starting_frame = frame_in_tag;
+        self.tag_changed = true;
+        self
+    }
+
+    /// Set how the tag is looked up, exactly by default
+    pub fn tag_match(mut self, tag_match: TagMatch) -> Self {
+        self.tag_match = tag_match;
+        self.tag_changed = true;
+        self
+    }
+
+    /// Start the animation paused, see [`play`](Self::play)
+    pub fn paused(mut self) -> Self {
+        self.is_playing = false;
+        self
+    }
+
+    /// Play a custom range of frames instead of a tag
+    ///
+    /// The range is played forward by default, see [`forward`](Self::forward),
+    /// [`reverse`](Self::reverse) and [`ping_pong`](Self::ping_pong).
+    pub fn frames(frames: Range<u16>) -> Self {
+        Self {
+            frames: Some(frames),
+            ..Default::default()
+        }
+    }
+
+    /// Play the tagless frames forward
+    pub fn forward(self) -> Self {
+        self.with_direction(AsepriteAnimationDirection::Forward)
+    }
+
+    /// Play the tagless frames in reverse
+    pub fn reverse(self) -> Self {
+        self.with_direction(AsepriteAnimationDirection::Reverse)
+    }
+
+    /// Play the tagless frames back and forth
+    pub fn ping_pong(self) -> Self {
+        self.with_direction(AsepriteAnimationDirection::PingPong)
+    }
+
+    /// Play the tagless frames back and forth, starting from the last one
+    pub fn ping_pong_reverse(self) -> Self {
+        self.with_direction(AsepriteAnimationDirection::PingPongReverse)
+    }
+
+    /// Set the direction used when no tag is set
+    ///
+    /// Tags always use the direction authored in the file.
+    pub fn with_direction(mut self, direction: AsepriteAnimationDirection) -> Self {
+        self.direction = direction;
+        self.tag_changed = true;
+        self
+    }
+
+    // The frames being played and in which direction
+    fn playback(&self, info: &AsepriteInfo) -> Option<(Range<usize>, AsepriteAnimationDirection)> {
         match &self.tag {
-            Some(tag) => {
-                let tag = match info.tags.get(tag) {
-                    Some(tag) => tag,
-                    None => {
-                        error!("Tag {} wasn't found.", tag);
-                        return;
+            Some(tag) => match self.tag_match.find(info, tag) {
+                Some(tag) => Some((tag.frames.as_range(), tag.animation_direction)),
+                None => {
+                    error!("Tag {} wasn't found.", tag);
+                    None
+                }
+            },
+            None => {
+                let range = match &self.frames {
+                    Some(frames) => {
+                        frames.start as usize..(frames.end as usize).min(info.frame_count)
                     }
+                    None => 0..info.frame_count,
                 };
-
-                let range = tag.frames.clone();
-                use reader::raw::AsepriteAnimationDirection;
-                match tag.animation_direction {
-                    AsepriteAnimationDirection::Forward | AsepriteAnimationDirection::PingPong => {
-                        self.current_frame = range.start as usize;
-                        self.forward = true;
-                    }
-                    AsepriteAnimationDirection::Reverse => {
-                        self.current_frame = range.end as usize - 1;
-                        self.forward = false;
-                    }
-                }
+                Some((range, self.direction))
             }
-            None => {
-                self.current_frame = 0;
+        }
+    }
+
+    fn reset(&mut self, info: &AsepriteInfo) {
+        self.tag_changed = false;
+        self.loops = 0;
+        self.tag_settings = match &self.tag {
+            Some(tag) => self
+                .tag_match
+                .find(info, tag)
+                .map(TagSettings::of)
+                .unwrap_or_default(),
+            None => TagSettings::default(),
+        };
+        let (range, direction) = match self.playback(info) {
+            Some(playback) => playback,
+            None => return,
+        };
+        if range.is_empty() {
+            return;
+        }
+
+        let offset = self.starting_frame.min(range.len() - 1);
+        match direction {
+            AsepriteAnimationDirection::Forward | AsepriteAnimationDirection::PingPong => {
+                self.current_frame = range.start + offset;
                 self.forward = true;
             }
+            AsepriteAnimationDirection::Reverse | AsepriteAnimationDirection::PingPongReverse => {
+                self.current_frame = range.end - 1 - offset;
+                self.forward = false;
+            }
+        }
+    }
+
+    // Whether the next frame starts another pass through the tag
+    fn ends_pass(&self, info: &AsepriteInfo) -> bool {
+        let (next_frame, forward) = match self.following_frame(info) {
+            Some(following) => following,
+            None => return true,
+        };
+        match self.playback(info) {
+            Some((_, AsepriteAnimationDirection::Forward)) => next_frame <= self.current_frame,
+            Some((_, AsepriteAnimationDirection::Reverse)) => next_frame >= self.current_frame,
+            Some((_, AsepriteAnimationDirection::PingPong)) => !self.forward && forward,
+            Some((_, AsepriteAnimationDirection::PingPongReverse)) => self.forward && !forward,
+            None => true,
         }
     }
 
     fn next_frame(&mut self, info: &AsepriteInfo) {
-        match &self.tag {
-            Some(tag) => {
-                let tag = match info.tags.get(tag) {
-                    Some(tag) => tag,
-                    None => {
-                        error!("Tag {} wasn't found.", tag);
-                        return;
-                    }
-                };
+        if let Some((frame, forward)) = self.following_frame(info) {
+            self.current_frame = frame;
+            self.forward = forward;
+        }
+    }
 
-                let range = tag.frames.clone();
-                match tag.animation_direction {
-                    reader::raw::AsepriteAnimationDirection::Forward => {
-                        let next_frame = self.current_frame + 1;
-                        if range.contains(&(next_frame as u16)) {
-                            self.current_frame = next_frame;
-                        } else {
-                            self.current_frame = range.start as usize;
-                        }
-                    }
-                    reader::raw::AsepriteAnimationDirection::Reverse => {
-                        let next_frame = self.current_frame.checked_sub(1);
-                        if let Some(next_frame) = next_frame {
-                            if range.contains(&((next_frame) as u16)) {
-                                self.current_frame = next_frame;
-                            } else {
-                                self.current_frame = range.end as usize - 1;
-                            }
-                        } else {
-                            self.current_frame = range.end as usize - 1;
-                        }
+    // The frame shown after the current one, e.g. to have it ready in advance
+    pub(crate) fn next_frame_index(&self, info: &AsepriteInfo) -> Option<usize> {
+        self.following_frame(info).map(|(frame, _)| frame)
+    }
+
+    // The frame shown after the current one, and whether it plays forward
+    fn following_frame(&self, info: &AsepriteInfo) -> Option<(usize, bool)> {
+        let (range, direction) = self.playback(info)?;
+        if range.is_empty() {
+            return None;
+        }
+
+        let following = match direction {
+            AsepriteAnimationDirection::Forward => {
+                let next_frame = self.current_frame + 1;
+                if range.contains(&next_frame) {
+                    (next_frame, self.forward)
+                } else {
+                    (range.start, self.forward)
+                }
+            }
+            AsepriteAnimationDirection::Reverse => match self.current_frame.checked_sub(1) {
+                Some(next_frame) if range.contains(&next_frame) => (next_frame, self.forward),
+                _ => (range.end - 1, self.forward),
+            },
+            AsepriteAnimationDirection::PingPong | AsepriteAnimationDirection::PingPongReverse => {
+                if self.forward {
+                    let next_frame = self.current_frame + 1;
+                    if range.contains(&next_frame) {
+                        (next_frame, true)
+                    } else {
+                        (self.current_frame.saturating_sub(1).max(range.start), false)
                     }
-                    reader::raw::AsepriteAnimationDirection::PingPong => {
-                        if self.forward {
-                            let next_frame = self.current_frame + 1;
-                            if range.contains(&(next_frame as u16)) {
-                                self.current_frame = next_frame;
-                            } else {
-                                self.current_frame = next_frame.saturating_sub(1);
-                                self.forward = false;
-                            }
-                        } else {
-                            let next_frame = self.current_frame.checked_sub(1);
-                            if let Some(next_frame) = next_frame {
-                                if range.contains(&(next_frame as u16)) {
-                                    self.current_frame = next_frame
-                                }
-                            }
-                            self.current_frame += 1;
-                            self.forward = true;
-                        }
+                } else {
+                    match self.current_frame.checked_sub(1) {
+                        Some(next_frame) if range.contains(&next_frame) => (next_frame, false),
+                        _ => ((self.current_frame + 1).min(range.end - 1), true),
                     }
                 }
             }
-            None => {
-                self.current_frame = (self.current_frame + 1) % info.frame_count;
+        };
+        Some(following)
+    }
+
+    /// Get how far into the current frame the animation is, from 0 to 1
+    pub fn frame_progress(&self, info: &AsepriteInfo) -> f32 {
+        self.time_elapsed.as_secs_f32() / self.current_frame_duration(info).as_secs_f32()
+    }
+
+    // Where the pivot of a slice is drawn, interpolated towards the frame
+    // shown next. Returned relative to its position in the current frame.
+    fn interpolated_offset(&self, info: &AsepriteInfo, slice: &str) -> Option<Vec2> {
+        let pivot = |frame: usize| {
+            let key = info.slice_at(slice, frame as u16)?;
+            let pivot = match key.pivot {
+                Some(pivot) => Vec2::new(pivot.x_pivot as f32, pivot.y_pivot as f32),
+                None => Vec2::new(key.width as f32, key.height as f32) / 2.,
+            };
+            Some(Vec2::new(key.position_x as f32, key.position_y as f32) + pivot)
+        };
+
+        let current = pivot(self.current_frame)?;
+        // Looping back to the start of the tag jumps instead of sliding back
+        let (next_frame, _) = self.following_frame(info)?;
+        let wraps = match self.playback(info)?.1 {
+            AsepriteAnimationDirection::Forward => next_frame < self.current_frame,
+            AsepriteAnimationDirection::Reverse => next_frame > self.current_frame,
+            AsepriteAnimationDirection::PingPong | AsepriteAnimationDirection::PingPongReverse => {
+                false
             }
+        };
+        if wraps {
+            return Some(Vec2::ZERO);
         }
+        let next = pivot(next_frame)?;
+        Some((next - current) * self.frame_progress(info).clamp(0., 1.))
     }
 
+    /// Get the duration of the current frame
+    ///
+    /// Durations shorter than the minimum frame duration (such as frames
+    /// saved with a duration of 0) are clamped to it.
     pub fn current_frame_duration(&self, info: &AsepriteInfo) -> Duration {
-        Duration::from_millis(info.frame_infos[self.current_frame].delay_ms as u64)
+        self.frame_duration(info, self.current_frame)
+    }
+
+    fn frame_duration(&self, info: &AsepriteInfo, frame: usize) -> Duration {
+        info.frame_infos
+            .get(frame)
+            .map(|frame_info| frame_info.duration)
+            .unwrap_or_default()
+            .max(self.min_frame_duration)
+    }
+
+    // The frames of the tag in the order of a single pass through it,
+    // ping-pong tags use their first pass
+    fn timeline(&self, info: &AsepriteInfo) -> Vec<usize> {
+        match self.playback(info) {
+            Some((
+                range,
+                AsepriteAnimationDirection::Reverse | AsepriteAnimationDirection::PingPongReverse,
+            )) => range.rev().collect(),
+            Some((range, _)) => range.collect(),
+            None => vec![],
+        }
+    }
+
+    /// Get the duration of a single pass through the tag
+    pub fn tag_duration(&self, info: &AsepriteInfo) -> Duration {
+        self.timeline(info)
+            .into_iter()
+            .map(|frame| self.frame_duration(info, frame))
+            .sum()
+    }
+
+    /// Get how far into the tag the animation currently is
+    ///
+    /// For ping-pong tags this is the position along their first pass.
+    pub fn time_in_tag(&self, info: &AsepriteInfo) -> Duration {
+        let mut time = Duration::ZERO;
+        for frame in self.timeline(info) {
+            if frame == self.current_frame {
+                return time + self.time_elapsed;
+            }
+            time += self.frame_duration(info, frame);
+        }
+        Duration::ZERO
+    }
+
+    /// Seek to a time within the tag, e.g. to scrub through an animation
+    ///
+    /// The time is clamped to the [`tag_duration`](Self::tag_duration), the
+    /// frame is updated even while the animation is paused.
+    pub fn set_time_in_tag(&mut self, info: &AsepriteInfo, time: Duration) {
+        let timeline = self.timeline(info);
+        let last = match timeline.last() {
+            Some(&last) => last,
+            None => return,
+        };
+
+        let mut remaining = time;
+        self.current_frame = last;
+        self.time_elapsed = self
+            .frame_duration(info, last)
+            .saturating_sub(Duration::from_nanos(1));
+        for frame in timeline {
+            let duration = self.frame_duration(info, frame);
+            if remaining < duration {
+                self.current_frame = frame;
+                self.time_elapsed = remaining;
+                break;
+            }
+            remaining -= duration;
+        }
+
+        self.forward = !matches!(
+            self.playback(info),
+            Some((
+                _,
+                AsepriteAnimationDirection::Reverse | AsepriteAnimationDirection::PingPongReverse
+            ))
+        );
+        self.tag_changed = false;
+        self.seeked = true;
+    }
+
+    /// Switch to a frame of another tag at once, keeping the rest of the
+    /// animation as it is
+    ///
+    /// The frame is relative to the start of the tag in its playing
+    /// direction, like [`starting_frame`](Self::starting_frame). Unlike
+    /// replacing the component the frame is shown on the next update, even
+    /// while paused, and the [`TagSettings`] of the tag apply from then on.
+    /// Returns `false` and leaves the animation untouched if
+    /// the tag or the frame doesn't exist.
+    pub fn goto(&mut self, info: &AsepriteInfo, tag: &str, frame_in_tag: usize) -> bool {
+        let found = match self.tag_match.find(info, tag) {
+            Some(found) => found,
+            None => {
+                error!("Tag {} wasn't found.", tag);
+                return false;
+            }
+        };
+        let range = found.frames.as_range();
+        if frame_in_tag >= range.len() {
+            error!(
+                "Tag {} has {} frames, can't go to frame {}",
+                tag,
+                range.len(),
+                frame_in_tag
+            );
+            return false;
+        }
+
+        let (current_frame, forward) = match found.animation_direction {
+            AsepriteAnimationDirection::Forward | AsepriteAnimationDirection::PingPong => {
+                (range.start + frame_in_tag, true)
+            }
+            AsepriteAnimationDirection::Reverse | AsepriteAnimationDirection::PingPongReverse => {
+                (range.end - 1 - frame_in_tag, false)
+            }
+        };
+        self.tag = Some(tag.to_string());
+        self.tag_settings = TagSettings::of(found);
+        self.current_frame = current_frame;
+        self.forward = forward;
+        self.time_elapsed = Duration::ZERO;
+        self.loops = 0;
+        self.tag_changed = false;
+        self.seeked = true;
+        true
+    }
+
+    /// Set the minimum duration a frame is shown for
+    ///
+    /// Zero-duration frames would otherwise make a single update loop
+    /// through frames without end. Defaults to [`DEFAULT_MIN_FRAME_DURATION`],
+    /// a zero duration is ignored.
+    pub fn with_min_frame_duration(mut self, min_frame_duration: Duration) -> Self {
+        if min_frame_duration.is_zero() {
+            warn!("Ignoring a minimum frame duration of zero");
+            return self;
+        }
+        self.min_frame_duration = min_frame_duration;
+        self
+    }
+
+    // Pauses the animation if it reached the frame set by `pause_at`
+    fn check_pause_at(&mut self, info: &AsepriteInfo) -> bool {
+        let frame = match self.pause_at {
+            Some(frame) => frame,
+            None => return false,
+        };
+        let (range, direction) = match self.playback(info) {
+            Some(playback) => playback,
+            None => return false,
+        };
+        if range.is_empty() {
+            return false;
+        }
+        let frame = match frame < range.len() {
+            true => frame,
+            false => {
+                error!(
+                    "Animation has {} frames, pausing at the last one instead of frame {}",
+                    range.len(),
+                    frame
+                );
+                self.pause_at = Some(range.len() - 1);
+                range.len() - 1
+            }
+        };
+        let target = match direction {
+            AsepriteAnimationDirection::Forward | AsepriteAnimationDirection::PingPong => {
+                range.start + frame
+            }
+            AsepriteAnimationDirection::Reverse | AsepriteAnimationDirection::PingPongReverse => {
+                range.end - 1 - frame
+            }
+        };
+        if self.current_frame != target {
+            return false;
+        }
+        self.pause_at = None;
+        self.time_elapsed = Duration::ZERO;
+        self.pause();
+        true
     }
 
     // Returns whether the frame was changed
     pub fn update(&mut self, info: &AsepriteInfo, dt: Duration) -> bool {
+        self.skipped.clear();
+        if self.frozen {
+            return false;
+        }
+
         if self.tag_changed {
             self.reset(info);
+            self.check_pause_at(info);
+            return true;
+        }
+
+        if std::mem::take(&mut self.seeked) {
             return true;
         }
 
-        if self.is_paused() {
+        if self.is_paused() || info.frame_count <= 1 {
             return false;
         }
 
-        self.time_elapsed += dt;
+        let speed = self.speed * self.tag_settings.speed.unwrap_or(1.).max(0.);
+        let once = match self.tag_settings.once {
+            Some(once) => once,
+            None => self.mode == PlayMode::Once,
+        };
+        self.time_elapsed += dt.mul_f32(speed);
         let mut current_frame_duration = self.current_frame_duration(info);
         let mut frame_changed = false;
         while self.time_elapsed >= current_frame_duration {
+            if self.ends_pass(info) {
+                self.loops += 1;
+                if once {
+                    self.time_elapsed = Duration::ZERO;
+                    self.pause();
+                    break;
+                }
+            }
             self.time_elapsed -= current_frame_duration;
+            if frame_changed {
+                self.skipped.push(self.current_frame);
+            }
             self.next_frame(info);
             current_frame_duration = self.current_frame_duration(info);
             frame_changed = true;
+            if self.check_pause_at(info) {
+                break;
+            }
         }
         frame_changed
     }
 
+    /// Returns `true` if the current frame is part of the given tag
+    ///
+    /// This doesn't need to be the tag being played, e.g. to check whether
+    /// any attack is playing when tags overlap.
+    pub fn is_in_tag(&self, info: &AsepriteInfo, tag: &str) -> bool {
+        self.tag_match
+            .find(info, tag)
+            .map(|tag| tag.frames.contains(self.current_frame as u16))
+            .unwrap_or(false)
+    }
+
+    /// The tag being played, `None` when playing frames without a tag
+    pub fn current_tag(&self) -> Option<&str> {
+        self.tag.as_deref()
+    }
+
     /// Get the current frame
     pub fn current_frame(&self) -> usize {
         self.current_frame
     }
 
+    /// Get the frames passed over during the last update, in the order they
+    /// played
+    ///
+    /// Long updates (e.g. lag spikes) can advance several frames at once, only
+    /// the last one is shown. Systems firing events on frames (footsteps,
+    /// hitboxes) can go through these first so none are lost. The current
+    /// frame isn't included.
+    pub fn skipped_frames(&self) -> &[usize] {
+        &self.skipped
+    }
+
+    /// Get how many passes through the tag the animation completed
+    ///
+    /// Ping-pong tags complete a pass once they are back on their first
+    /// frame. Changing the tag starts counting from zero again.
+    pub fn loop_count(&self) -> u32 {
+        self.loops
+    }
+
+    /// Get how many frames are left after the current one before the pass
+    /// through the tag is completed
+    pub fn remaining_frames(&self, info: &AsepriteInfo) -> usize {
+        let (range, direction) = match self.playback(info) {
+            Some(playback) => playback,
+            None => return 0,
+        };
+        if !range.contains(&self.current_frame) {
+            return 0;
+        }
+
+        let last = range.end - 1;
+        match direction {
+            AsepriteAnimationDirection::Forward => last - self.current_frame,
+            AsepriteAnimationDirection::Reverse => self.current_frame - range.start,
+            AsepriteAnimationDirection::PingPong if self.forward => {
+                (last - self.current_frame) + (last - range.start)
+            }
+            AsepriteAnimationDirection::PingPong => self.current_frame - range.start,
+            AsepriteAnimationDirection::PingPongReverse if !self.forward => {
+                (self.current_frame - range.start) + (last - range.start)
+            }
+            AsepriteAnimationDirection::PingPongReverse => last - self.current_frame,
+        }
+    }
+
     /// Start or resume playing an animation
     pub fn play(&mut self) {
         self.is_playing = true;
@@ -199,30 +824,410 @@ impl AsepriteAnimation {
     pub fn toggle(&mut self) {
         self.is_playing = !self.is_playing;
     }
-    
+
+    /// Keep playing until the given frame is reached, then pause on it
+    ///
+    /// The frame is relative to the start of the tag (or of the custom
+    /// frames when no tag is set) in its playing direction, like
+    /// [`starting_frame`](Self::starting_frame): frame 0 of a reverse tag is
+    /// its last frame. Frames past the end of the tag are clamped to its last
+    /// frame. Calling [`play`](Self::play) afterwards resumes from that frame.
+    pub fn pause_at(&mut self, frame_in_tag: usize) {
+        self.pause_at = Some(frame_in_tag);
+        self.play();
+    }
+
+    /// Freeze the animation on its current frame
+    ///
+    /// Unlike [`pause`](Self::pause) no time accumulates and tag changes are
+    /// held back until [`unfreeze`](Self::unfreeze) is called, e.g. for
+    /// hit-stop effects or cutscene poses.
+    pub fn freeze(&mut self) {
+        self.frozen = true;
+    }
+
+    /// Resume an animation frozen with [`freeze`](Self::freeze)
+    pub fn unfreeze(&mut self) {
+        self.frozen = false;
+    }
+
+    /// Returns `true` if the animation is frozen
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    /// Draw the sprite with a custom size instead of the size of the frames
+    ///
+    /// See [`custom_size`](Self::custom_size)
     pub const fn with_size(mut self, size: Option<Vec2>) -> Self {
         self.custom_size = size;
         self
     }
 }
 
+/// Smooths the movement of a sprite exported at a low frame rate
+///
+/// The pivot of the slice (or its center without a pivot) marks where the
+/// sprite is in each frame. Between frames the sprite is offset towards the
+/// position of the pivot in the next frame, so art moving across the canvas
+/// slides instead of jumping. The offset is applied through the anchor of the
+/// sprite, its `Transform` is left alone.
+#[derive(Debug, Clone, Component)]
+pub struct AsepriteSmoothing {
+    /// The slice whose pivot is followed
+    pub slice: String,
+    /// The anchor of the sprite without any offset
+    pub anchor: Anchor,
+}
+
+impl AsepriteSmoothing {
+    /// Follow the pivot of a slice, around the center of the sprite
+    pub fn new(slice: impl Into<String>) -> Self {
+        Self {
+            slice: slice.into(),
+            anchor: Anchor::Center,
+        }
+    }
+}
+
+// Marks the smoothed sprites whose slice wasn't found
+#[derive(Debug, Component)]
+pub(crate) struct MissingSmoothingSlice(String);
+
+pub(crate) fn smooth_motion(
+    mut commands: Commands,
+    aseprites: Res<Assets<Aseprite>>,
+    mut smoothed_query: Query<(
+        Entity,
+        &Handle<Aseprite>,
+        &AsepriteAnimation,
+        &AsepriteSmoothing,
+        &mut TextureAtlasSprite,
+        Option<&MissingSmoothingSlice>,
+    )>,
+) {
+    for (entity, handle, animation, smoothing, mut sprite, missing) in smoothed_query.iter_mut() {
+        let aseprite = match aseprites.get(handle) {
+            Some(aseprite) => aseprite,
+            None => continue,
+        };
+        let info = match aseprite.info() {
+            Some(info) => info,
+            None => continue,
+        };
+        // Only warned about once, until the slice is found or another one is set
+        let offset = match (
+            animation.interpolated_offset(info, &smoothing.slice),
+            missing,
+        ) {
+            (Some(offset), _) => offset,
+            (None, Some(missing)) if missing.0 == smoothing.slice => continue,
+            (None, _) => {
+                warn!(
+                    "Slice {} followed by entity {:?} wasn't found",
+                    smoothing.slice, entity
+                );
+                commands
+                    .entity(entity)
+                    .insert(MissingSmoothingSlice(smoothing.slice.clone()));
+                continue;
+            }
+        };
+        if missing.is_some() {
+            commands.entity(entity).remove::<MissingSmoothingSlice>();
+        }
+
+        // Aseprite positions point down, anchors are fractions of the size
+        let size = Vec2::new(info.dimensions.0 as f32, info.dimensions.1 as f32);
+        let mut anchor = smoothing.anchor.as_vec() - Vec2::new(offset.x, -offset.y) / size;
+        if let Some(rect) = trimmed_rect(aseprite, None, animation.current_frame) {
+            anchor = trimmed_placement(rect, size, size, anchor).1;
+        }
+        if sprite.anchor.as_vec() != anchor {
+            sprite.anchor = Anchor::Custom(anchor);
+        }
+    }
+}
+
+// Part of the canvas the current frame covers, if the frames are trimmed
+fn trimmed_rect(aseprite: &Aseprite, group: Option<&AsepriteGroup>, frame: usize) -> Option<URect> {
+    // Layer group atlases aren't trimmed
+    if !aseprite.settings.trim_frames || group.is_some() {
+        return None;
+    }
+    aseprite.frame_rect(frame)
+}
+
+// Size and anchor of a trimmed frame drawn at its place on the canvas, the
+// canvas being drawn with a size around an anchor
+fn trimmed_placement(rect: URect, canvas: Vec2, size: Vec2, canvas_anchor: Vec2) -> (Vec2, Vec2) {
+    let frame = rect.size().as_vec2();
+    // Aseprite positions point down, anchors point up
+    let center = rect.min.as_vec2() + frame / 2. - canvas / 2.;
+    let center = Vec2::new(center.x, -center.y);
+    (
+        frame * size / canvas,
+        (canvas_anchor * canvas - center) / frame,
+    )
+}
+
+// Bevy computes the bounds of a sprite once, from its first frame. Frames
+// of other sizes, a custom size or an anchor moved by smoothing would get the
+// sprite culled while it's still visible.
+pub(crate) fn update_aabbs(
+    mut commands: Commands,
+    atlases: Res<Assets<TextureAtlas>>,
+    mut sprites_query: Query<
+        (
+            Entity,
+            &TextureAtlasSprite,
+            &Handle<TextureAtlas>,
+            Option<&mut Aabb>,
+        ),
+        (
+            With<Handle<Aseprite>>,
+            Without<NoFrustumCulling>,
+            Or<(
+                Changed<TextureAtlasSprite>,
+                Changed<Handle<TextureAtlas>>,
+                Without<Aabb>,
+            )>,
+        ),
+    >,
+) {
+    for (entity, sprite, atlas, aabb) in sprites_query.iter_mut() {
+        let size = match sprite.custom_size {
+            Some(size) => size,
+            None => match atlases
+                .get(atlas)
+                .and_then(|atlas| atlas.textures.get(sprite.index))
+            {
+                Some(rect) => rect.size(),
+                None => continue,
+            },
+        };
+        let bounds = Aabb {
+            center: (-sprite.anchor.as_vec() * size).extend(0.).into(),
+            half_extents: (size / 2.).extend(0.).into(),
+        };
+        match aabb {
+            Some(mut aabb) => {
+                if aabb.center != bounds.center || aabb.half_extents != bounds.half_extents {
+                    *aabb = bounds;
+                }
+            }
+            None => {
+                commands.entity(entity).insert(bounds);
+            }
+        }
+    }
+}
+
+/// Decides which frame an animation shows, see [`AsepriteSequencer`]
+///
+/// The sprite is updated from `current_frame` whenever
+/// [`advance`](Self::advance) reports a change, atlases and everything
+/// else are still managed by the plugin.
+pub trait FrameSequencer: Send + Sync + 'static {
+    /// Advance the animation by `delta`, returns `true` if its frame changed
+    fn advance(
+        &mut self,
+        animation: &mut AsepriteAnimation,
+        info: &AsepriteInfo,
+        delta: Duration,
+    ) -> bool;
+}
+
+/// Plays the tags as authored in aseprite, used for animations without an
+/// [`AsepriteSequencer`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TagSequencer;
+
+impl FrameSequencer for TagSequencer {
+    fn advance(
+        &mut self,
+        animation: &mut AsepriteAnimation,
+        info: &AsepriteInfo,
+        delta: Duration,
+    ) -> bool {
+        animation.update(info, delta)
+    }
+}
+
+/// Picks the frames of an animation with a sequencer of its own, e.g. for
+/// random frames, stepping on the beat of the music or following an
+/// external timeline
+///
+/// ```rust,ignore
+/// struct RandomFrame;
+///
+/// impl FrameSequencer for RandomFrame {
+///     fn advance(&mut self, animation: &mut AsepriteAnimation, info: &AsepriteInfo, _: Duration) -> bool {
+///         animation.current_frame = rand::random::<usize>() % info.frame_count;
+///         true
+///     }
+/// }
+///
+/// commands.spawn((AsepriteBundle { .. }, AsepriteSequencer::new(RandomFrame)));
+/// ```
+#[derive(Component)]
+pub struct AsepriteSequencer(pub Box<dyn FrameSequencer>);
+
+impl AsepriteSequencer {
+    /// Pick the frames with a sequencer
+    pub fn new(sequencer: impl FrameSequencer) -> Self {
+        Self(Box::new(sequencer))
+    }
+}
+
+impl std::fmt::Debug for AsepriteSequencer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AsepriteSequencer").finish_non_exhaustive()
+    }
+}
+
+/// Only update an animation every few frames, e.g. for sprites far away
+///
+/// The time of the skipped frames is carried over, so the animation keeps
+/// its speed and only changes frames less precisely. Updates are spread over
+/// the frames so entities with the same interval don't all update at once.
+/// Games can pick the interval from the distance to the camera:
+///
+/// ```rust,ignore
+/// lod.interval = if distance > 1000. { 3 } else { 1 };
+/// ```
+#[derive(Debug, Clone, Component)]
+pub struct AnimationLod {
+    /// Update the animation once every `interval` frames, `1` (or `0`)
+    /// updating it every frame
+    pub interval: u32,
+    // Frames skipped since the last update, `None` until the first one
+    skipped: Option<u32>,
+    // Time skipped since the last update
+    pending: Duration,
+}
+
+impl Default for AnimationLod {
+    fn default() -> Self {
+        Self::every(1)
+    }
+}
+
+impl AnimationLod {
+    /// Update the animation once every `interval` frames
+    pub fn every(interval: u32) -> Self {
+        Self {
+            interval,
+            skipped: None,
+            pending: Duration::ZERO,
+        }
+    }
+
+    // Returns the time to advance the animation by when it should be
+    // updated this frame
+    fn tick(&mut self, entity: Entity, delta: Duration) -> Option<Duration> {
+        let interval = self.interval.max(1);
+        // Stagger the updates of entities spawned together
+        let skipped = self.skipped.get_or_insert(entity.index() % interval);
+        self.pending += delta;
+        *skipped += 1;
+        if *skipped < interval {
+            return None;
+        }
+        *skipped = 0;
+        Some(std::mem::take(&mut self.pending))
+    }
+}
+
+/// What happens to animated entities whose aseprite isn't loaded anymore
+///
+/// Files can be unloaded while entities still use them, e.g. when the asset
+/// collection holding their handles is dropped. A warning is logged once per
+/// entity, then the policy set with
+/// [`AsepritePlugin::on_missing_aseprite`](crate::AsepritePlugin::on_missing_aseprite)
+/// applies.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MissingAsepritePolicy {
+    /// Hide the sprite until the file is loaded again
+    #[default]
+    Hide,
+    /// Despawn the entity with its children
+    Despawn,
+}
+
+/// Marks the entities whose aseprite isn't loaded anymore
+///
+/// They are hidden until the file is loaded again, with
+/// [`MissingAsepritePolicy::Hide`].
+#[derive(Debug, Clone, Component)]
+pub struct AsepriteMissing {
+    // Visibility of the entity before it was hidden
+    visibility: Option<Visibility>,
+}
+
+// How the animation systems handle files which aren't loaded anymore
+#[derive(Debug, Clone, Resource)]
+pub(crate) struct AnimationSettings {
+    pub(crate) missing: MissingAsepritePolicy,
+}
+
 pub(crate) fn update_animations(
+    mut commands: Commands,
     time: Res<Time>,
+    mut diagnostics: Diagnostics,
+    settings: Res<AnimationSettings>,
+    asset_server: Res<AssetServer>,
     aseprites: Res<Assets<Aseprite>>,
     mut aseprites_query: Query<(
+        Entity,
         &Handle<Aseprite>,
         &mut AsepriteAnimation,
-        &mut TextureAtlasSprite,
+        Option<&mut TextureAtlasSprite>,
+        Option<&mut AnimationLod>,
+        Option<&AsepriteGroup>,
+        Has<AsepriteSmoothing>,
+        Option<&mut AsepriteSequencer>,
+        Option<&mut Visibility>,
+        Option<&AsepriteMissing>,
     )>,
 ) {
-    for (handle, mut animation, mut sprite) in aseprites_query.iter_mut() {
+    let mut animated = 0;
+    let mut advanced = 0;
+    for (
+        entity,
+        handle,
+        mut animation,
+        mut sprite,
+        lod,
+        group,
+        smoothed,
+        sequencer,
+        visibility,
+        missing,
+    ) in aseprites_query.iter_mut()
+    {
         let aseprite = match aseprites.get(handle) {
             Some(aseprite) => aseprite,
             None => {
-                error!("Aseprite handle is invalid");
+                handle_missing(
+                    &mut commands,
+                    &asset_server,
+                    settings.missing,
+                    entity,
+                    handle,
+                    visibility,
+                    missing,
+                );
                 continue;
             }
         };
+        // The file was loaded again, show the entity as it was
+        if let Some(missing) = missing {
+            if let (Some(mut visibility), Some(previous)) = (visibility, missing.visibility) {
+                *visibility = previous;
+            }
+            commands.entity(entity).remove::<AsepriteMissing>();
+        }
         let info = match &aseprite.info {
             Some(info) => info,
             None => {
@@ -230,29 +1235,616 @@ pub(crate) fn update_animations(
                 continue;
             }
         };
+        animated += 1;
+
+        // Headless apps animate without any sprite. Without a size of its
+        // own the animation leaves the size set on the sprite alone.
+        let trimmed = aseprite.settings.trim_frames && group.is_none();
+        if let (Some(sprite), Some(size), false) = (sprite.as_mut(), animation.custom_size, trimmed)
+        {
+            if sprite.custom_size != Some(size) {
+                sprite.custom_size = Some(size);
+            }
+        }
+
+        let delta = match lod {
+            Some(mut lod) => match lod.tick(entity, time.delta()) {
+                Some(delta) => delta,
+                None => continue,
+            },
+            None => time.delta(),
+        };
 
-        sprite.custom_size = animation.custom_size;
+        // Time passing isn't a change of the animation, only a new frame or
+        // it stopping is
+        let was_playing = animation.is_playing;
+        let state = animation.bypass_change_detection();
+        let frame_changed = match sequencer {
+            Some(mut sequencer) => sequencer.0.advance(state, info, delta),
+            None => TagSequencer.advance(state, info, delta),
+        };
+        if frame_changed || animation.is_playing != was_playing {
+            animation.set_changed();
+        }
+        if frame_changed {
+            advanced += 1;
+            // Streamed files point their sprites to a slot in `stream_frames`
+            if let (Some(sprite), false) = (sprite.as_mut(), aseprite.is_streamed()) {
+                match aseprite.frame_index(group, animation.current_frame) {
+                    Some(index) if sprite.index != index => sprite.index = index,
+                    Some(_) => {}
+                    None => error!("Frame {} is out of bounds", animation.current_frame),
+                }
+            }
+        }
 
-        if animation.update(info, time.delta()) {
-            sprite.index = aseprite.frame_to_idx[animation.current_frame];
+        // Trimmed frames are smaller than the canvas, the size and anchor of
+        // the sprite put them back at their place on it
+        if let (Some(sprite), Some(rect)) = (
+            sprite.as_mut(),
+            trimmed_rect(aseprite, group, animation.current_frame),
+        ) {
+            let canvas = Vec2::new(info.dimensions.0 as f32, info.dimensions.1 as f32);
+            let size = animation.custom_size.unwrap_or(canvas);
+            let (size, anchor) = trimmed_placement(rect, canvas, size, Vec2::ZERO);
+            if sprite.custom_size != Some(size) {
+                sprite.custom_size = Some(size);
+            }
+            // Smoothing moves the anchor of the frame on its own
+            if sprite.anchor.as_vec() != anchor && !smoothed {
+                sprite.anchor = Anchor::Custom(anchor);
+            }
+        }
+    }
+    diagnostics.add_measurement(AsepriteDiagnostics::ANIMATED_ENTITIES, || animated as f64);
+    diagnostics.add_measurement(AsepriteDiagnostics::FRAMES_ADVANCED, || advanced as f64);
+}
+
+// Applies the policy to an entity whose aseprite isn't there, once
+fn handle_missing(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    policy: MissingAsepritePolicy,
+    entity: Entity,
+    handle: &Handle<Aseprite>,
+    visibility: Option<Mut<Visibility>>,
+    missing: Option<&AsepriteMissing>,
+) {
+    // Files still loading aren't missing, their entities just wait
+    if missing.is_some()
+        || matches!(
+            asset_server.get_load_state(handle),
+            Some(LoadState::NotLoaded | LoadState::Loading)
+        )
+    {
+        return;
+    }
+
+    match policy {
+        MissingAsepritePolicy::Hide => {
+            warn!(
+                "Aseprite {:?} of entity {:?} isn't loaded anymore, hiding it",
+                handle.id(),
+                entity
+            );
+            let previous = visibility.map(|mut visibility| {
+                let previous = *visibility;
+                *visibility = Visibility::Hidden;
+                previous
+            });
+            // The entity may be despawned before the commands are applied
+            commands.add(move |world: &mut World| {
+                if let Some(mut entity) = world.get_entity_mut(entity) {
+                    entity.insert(AsepriteMissing {
+                        visibility: previous,
+                    });
+                }
+            });
+        }
+        MissingAsepritePolicy::Despawn => {
+            warn!(
+                "Aseprite {:?} of entity {:?} isn't loaded anymore, despawning it",
+                handle.id(),
+                entity
+            );
+            commands.entity(entity).despawn_recursive();
         }
     }
 }
 
 impl From<&str> for AsepriteAnimation {
     fn from(tag: &str) -> AsepriteAnimation {
-        AsepriteAnimation {
-            tag: Some(tag.to_owned()),
-            ..Default::default()
-        }
+        AsepriteAnimation::tag(tag)
     }
 }
 
 impl From<String> for AsepriteAnimation {
     fn from(tag: String) -> AsepriteAnimation {
-        AsepriteAnimation {
-            tag: Some(tag),
-            ..Default::default()
+        AsepriteAnimation::tag(tag)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, time::Duration};
+
+    use bevy::math::{URect, Vec2};
+
+    use super::{AnimationLod, AsepriteAnimation, PlayMode, TagMatch, TagSettings};
+    use crate::reader::{
+        raw::{AsepriteAnimationDirection, AsepriteColor, AsepritePivot},
+        AsepriteFrameInfo, AsepriteSlice, AsepriteTag, AsepriteUserData, FrameSpan,
+    };
+    use crate::AsepriteInfo;
+
+    fn info(
+        delays: &[usize],
+        tags: &[(&str, std::ops::Range<u16>, AsepriteAnimationDirection)],
+    ) -> AsepriteInfo {
+        let tag_list: Vec<_> = tags
+            .iter()
+            .map(|(name, frames, animation_direction)| AsepriteTag {
+                frames: FrameSpan::new(frames.start, frames.end - 1),
+                animation_direction: *animation_direction,
+                color: AsepriteColor {
+                    red: 0,
+                    green: 0,
+                    blue: 0,
+                    alpha: 255,
+                },
+                name: name.to_string(),
+                user_data: None,
+            })
+            .collect();
+        AsepriteInfo {
+            dimensions: (1, 1),
+            tags: tag_list
+                .iter()
+                .map(|tag| (tag.name.clone(), tag.clone()))
+                .collect(),
+            tag_list,
+            slices: HashMap::new(),
+            slice_keys: HashMap::new(),
+            frame_count: delays.len(),
+            palette: None,
+            transparent_palette: None,
+            frame_infos: delays
+                .iter()
+                .map(|&delay_ms| AsepriteFrameInfo {
+                    duration: Duration::from_millis(delay_ms as u64),
+                    markers: vec![],
+                })
+                .collect(),
+            user_data: None,
         }
     }
+
+    // A single tag named walk
+    fn walk(
+        delays: &[usize],
+        frames: std::ops::Range<u16>,
+        direction: AsepriteAnimationDirection,
+    ) -> AsepriteInfo {
+        info(delays, &[("walk", frames, direction)])
+    }
+
+    fn play(anim: &mut AsepriteAnimation, info: &AsepriteInfo, steps: usize) -> Vec<usize> {
+        anim.update(info, Duration::ZERO);
+        (0..steps)
+            .map(|_| {
+                anim.update(info, Duration::from_millis(100));
+                anim.current_frame()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn zero_duration_frame_is_shown_once() {
+        let info = info(&[100, 0, 100], &[]);
+        let mut anim = AsepriteAnimation::default();
+        anim.update(&info, Duration::ZERO);
+
+        assert!(anim.update(&info, Duration::from_millis(100)));
+        assert_eq!(anim.current_frame(), 1);
+        assert!(anim.update(&info, Duration::from_millis(1)));
+        assert_eq!(anim.current_frame(), 2);
+    }
+
+    #[test]
+    fn only_zero_duration_frames_terminate() {
+        let info = info(&[0, 0, 0], &[]);
+        let mut anim = AsepriteAnimation::default();
+        anim.update(&info, Duration::ZERO);
+
+        assert!(anim.update(&info, Duration::from_millis(16)));
+        assert_eq!(anim.current_frame(), 16 % 3);
+    }
+
+    #[test]
+    fn tagless_ping_pong() {
+        let info = info(&[100; 6], &[]);
+        let mut anim = AsepriteAnimation::frames(1..4).ping_pong();
+
+        assert_eq!(play(&mut anim, &info, 6), vec![2, 3, 2, 1, 2, 3]);
+    }
+
+    #[test]
+    fn skipped_frames() {
+        let info = info(&[100; 4], &[]);
+        let mut anim = AsepriteAnimation::default();
+        anim.update(&info, Duration::ZERO);
+
+        assert!(anim.update(&info, Duration::from_millis(100)));
+        assert!(anim.skipped_frames().is_empty());
+
+        // A lag spike passes over two frames, looping back to the start
+        assert!(anim.update(&info, Duration::from_millis(350)));
+        assert_eq!(anim.current_frame(), 0);
+        assert_eq!(anim.skipped_frames(), &[2, 3]);
+
+        assert!(!anim.update(&info, Duration::from_millis(10)));
+        assert!(anim.skipped_frames().is_empty());
+    }
+
+    #[test]
+    fn tag_reverse() {
+        let info = walk(&[100; 6], 2..5, AsepriteAnimationDirection::Reverse);
+        let mut anim = AsepriteAnimation::from("walk");
+
+        assert_eq!(play(&mut anim, &info, 4), vec![3, 2, 4, 3]);
+    }
+
+    #[test]
+    fn tag_ping_pong_reverse() {
+        let info = walk(&[100; 6], 1..4, AsepriteAnimationDirection::PingPongReverse);
+        let mut anim = AsepriteAnimation::from("walk");
+
+        assert_eq!(play(&mut anim, &info, 6), vec![2, 1, 2, 3, 2, 1]);
+        assert_eq!(anim.loop_count(), 1);
+        assert_eq!(anim.remaining_frames(&info), 2);
+    }
+
+    #[test]
+    fn pause_at_frame() {
+        let info = walk(&[100; 6], 2..6, AsepriteAnimationDirection::Forward);
+        let mut anim = AsepriteAnimation::from("walk");
+        anim.pause_at(2);
+
+        assert_eq!(play(&mut anim, &info, 4), vec![3, 4, 4, 4]);
+        assert!(anim.is_paused());
+
+        // Counted from the end of reverse tags
+        let info = walk(&[100; 6], 2..6, AsepriteAnimationDirection::Reverse);
+        let mut anim = AsepriteAnimation::from("walk");
+        anim.pause_at(1);
+        assert_eq!(play(&mut anim, &info, 3), vec![4, 4, 4]);
+        assert!(anim.is_paused());
+
+        // Clamped to the last frame of the tag
+        let info = walk(&[100; 6], 2..6, AsepriteAnimationDirection::Forward);
+        let mut anim = AsepriteAnimation::from("walk");
+        anim.pause_at(10);
+        assert_eq!(play(&mut anim, &info, 5), vec![3, 4, 5, 5, 5]);
+        assert!(anim.is_paused());
+    }
+
+    #[test]
+    fn freeze_holds_frame() {
+        let info = info(&[100; 4], &[]);
+        let mut anim = AsepriteAnimation::default();
+        anim.update(&info, Duration::ZERO);
+        anim.update(&info, Duration::from_millis(100));
+        anim.freeze();
+
+        assert!(!anim.update(&info, Duration::from_millis(500)));
+        assert_eq!(anim.current_frame(), 1);
+
+        anim.unfreeze();
+        assert!(anim.update(&info, Duration::from_millis(100)));
+        assert_eq!(anim.current_frame(), 2);
+    }
+
+    #[test]
+    fn scrub_tag() {
+        let info = walk(
+            &[100, 50, 100, 200, 100],
+            1..4,
+            AsepriteAnimationDirection::Forward,
+        );
+        let mut anim = AsepriteAnimation::from("walk");
+        anim.update(&info, Duration::ZERO);
+        assert_eq!(anim.tag_duration(&info), Duration::from_millis(350));
+
+        anim.pause();
+        anim.set_time_in_tag(&info, Duration::from_millis(175));
+        assert!(anim.update(&info, Duration::from_millis(16)));
+        assert_eq!(anim.current_frame(), 3);
+        assert_eq!(anim.time_in_tag(&info), Duration::from_millis(175));
+
+        anim.set_time_in_tag(&info, Duration::from_secs(10));
+        assert_eq!(anim.current_frame(), 3);
+        assert!(anim.time_in_tag(&info) < anim.tag_duration(&info));
+    }
+
+    #[test]
+    fn scrub_reverse_tag() {
+        let info = walk(&[100, 50, 100], 0..3, AsepriteAnimationDirection::Reverse);
+        let mut anim = AsepriteAnimation::from("walk");
+
+        anim.set_time_in_tag(&info, Duration::from_millis(120));
+        assert_eq!(anim.current_frame(), 1);
+        assert_eq!(anim.time_in_tag(&info), Duration::from_millis(120));
+    }
+
+    #[cfg(feature = "serialize")]
+    #[test]
+    fn serialize_state() {
+        let info = walk(&[100; 4], 1..4, AsepriteAnimationDirection::PingPong);
+        let mut anim = AsepriteAnimation::from("walk");
+        play(&mut anim, &info, 3);
+        anim.update(&info, Duration::from_millis(40));
+
+        let json = serde_json::to_string(&anim).unwrap();
+        let mut restored: AsepriteAnimation = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, anim);
+
+        assert_eq!(
+            anim.update(&info, Duration::from_millis(60)),
+            restored.update(&info, Duration::from_millis(60))
+        );
+        assert_eq!(restored.current_frame(), anim.current_frame());
+    }
+
+    #[test]
+    fn overlapping_tags() {
+        let info = info(
+            &[100; 6],
+            &[
+                ("attack", 0..4, AsepriteAnimationDirection::Forward),
+                ("attack_hit", 2..3, AsepriteAnimationDirection::Forward),
+            ],
+        );
+        let mut anim = AsepriteAnimation::from("attack");
+
+        assert_eq!(play(&mut anim, &info, 2), vec![1, 2]);
+        assert!(anim.is_in_tag(&info, "attack"));
+        assert!(anim.is_in_tag(&info, "attack_hit"));
+        assert!(!anim.is_in_tag(&info, "missing"));
+    }
+
+    #[test]
+    fn smoothing_follows_pivot() {
+        let mut info = info(&[100, 100, 100], &[]);
+        let key = |valid_frame, position_x| AsepriteSlice {
+            name: "feet".to_string(),
+            valid_frame,
+            position_x,
+            position_y: 0,
+            width: 2,
+            height: 2,
+            nine_patch_info: None,
+            pivot: Some(AsepritePivot {
+                x_pivot: 1,
+                y_pivot: 2,
+            }),
+            user_data: None,
+        };
+        info.slice_keys
+            .insert("feet".to_string(), vec![key(0, 0), key(1, 4), key(2, 8)]);
+
+        let mut anim = AsepriteAnimation::default();
+        anim.update(&info, Duration::ZERO);
+        anim.update(&info, Duration::from_millis(50));
+        assert_eq!(
+            anim.interpolated_offset(&info, "feet"),
+            Some(Vec2::new(2., 0.))
+        );
+
+        // Looping back to the first frame doesn't slide back across the canvas
+        anim.update(&info, Duration::from_millis(200));
+        assert_eq!(anim.current_frame(), 2);
+        assert_eq!(anim.interpolated_offset(&info, "feet"), Some(Vec2::ZERO));
+        assert_eq!(anim.interpolated_offset(&info, "head"), None);
+    }
+
+    #[test]
+    fn play_once() {
+        let info = walk(
+            &[100, 100, 100, 100],
+            1..3,
+            AsepriteAnimationDirection::Forward,
+        );
+        let mut anim = AsepriteAnimation::tag("walk").mode(PlayMode::Once);
+        assert_eq!(play(&mut anim, &info, 4), vec![2, 2, 2, 2]);
+        assert!(anim.is_paused());
+
+        let info = walk(&[100, 100, 100], 0..3, AsepriteAnimationDirection::PingPong);
+        let mut anim = AsepriteAnimation::tag("walk").mode(PlayMode::Once);
+        assert_eq!(play(&mut anim, &info, 6), vec![1, 2, 1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn tag_match() {
+        let forward = AsepriteAnimationDirection::Forward;
+        let mut info = info(
+            &[100, 100, 100, 100],
+            &[("walk", 0..2, forward), ("walk", 2..4, forward)],
+        );
+
+        // Keyed by name, the last tag is kept unless the first one is asked for
+        let mut anim = AsepriteAnimation::tag("walk");
+        assert_eq!(play(&mut anim, &info, 3), vec![3, 2, 3]);
+        let mut anim = AsepriteAnimation::tag("walk").tag_match(TagMatch::FirstMatch);
+        assert_eq!(play(&mut anim, &info, 3), vec![1, 0, 1]);
+
+        info.rekey_tags(|index, tag| format!("{}:{}", index, tag.name));
+
+        let mut anim = AsepriteAnimation::tag("1:walk");
+        assert_eq!(play(&mut anim, &info, 3), vec![3, 2, 3]);
+        let mut anim = AsepriteAnimation::tag("1:walk").tag_match(TagMatch::FirstMatch);
+        assert_eq!(play(&mut anim, &info, 3), vec![3, 2, 3]);
+
+        // Only found by falling back to the first tag named like it
+        let mut anim = AsepriteAnimation::tag("walk");
+        anim.update(&info, Duration::ZERO);
+        assert_eq!(anim.current_frame(), 0);
+        assert!(!anim.is_in_tag(&info, "walk"));
+        let mut anim = AsepriteAnimation::tag("walk").tag_match(TagMatch::FirstMatch);
+        assert_eq!(play(&mut anim, &info, 3), vec![1, 0, 1]);
+        assert!(anim.is_in_tag(&info, "walk"));
+    }
+
+    #[test]
+    fn trimmed_frames_keep_their_place() {
+        let canvas = Vec2::new(8., 4.);
+        // The right half of the canvas
+        let rect = URect::new(4, 0, 8, 4);
+        let (size, anchor) = super::trimmed_placement(rect, canvas, canvas, Vec2::ZERO);
+        assert_eq!(size, Vec2::new(4., 4.));
+        assert_eq!(anchor, Vec2::new(-0.5, 0.));
+
+        // Drawn twice as large, around the bottom left corner of the canvas
+        let (size, anchor) =
+            super::trimmed_placement(rect, canvas, canvas * 2., Vec2::new(-0.5, -0.5));
+        assert_eq!(size, Vec2::new(8., 8.));
+        assert_eq!(anchor, Vec2::new(-1.5, -0.5));
+    }
+
+    #[test]
+    fn tag_settings() {
+        assert_eq!(
+            TagSettings::parse("speed = 1.5;once=false; bogus=1; speed; once=maybe"),
+            TagSettings {
+                speed: Some(1.5),
+                once: Some(false),
+            }
+        );
+
+        let mut info = info(
+            &[100, 100, 100, 100],
+            &[("attack", 0..4, AsepriteAnimationDirection::Forward)],
+        );
+        let user_data = Some(AsepriteUserData {
+            text: Some("speed=2; once=true".to_string()),
+            color: None,
+        });
+        info.tags.get_mut("attack").unwrap().user_data = user_data.clone();
+        info.tag_list[0].user_data = user_data;
+
+        let mut anim = AsepriteAnimation::tag("attack");
+        assert_eq!(play(&mut anim, &info, 3), vec![2, 3, 3]);
+        assert_eq!(anim.tag_settings().speed, Some(2.));
+        assert_eq!(anim.playback_speed(), 1.);
+        assert!(anim.is_paused());
+    }
+
+    #[test]
+    fn set_tag() {
+        let info = info(
+            &[100, 100, 100, 100],
+            &[
+                ("walk", 0..2, AsepriteAnimationDirection::Forward),
+                ("run", 2..4, AsepriteAnimationDirection::Forward),
+            ],
+        );
+        let mut anim = AsepriteAnimation::tag("walk").speed(2.);
+        anim.custom_size = Some(Vec2::splat(8.));
+        assert_eq!(play(&mut anim, &info, 1), vec![0]);
+
+        anim.set_tag("run");
+        assert_eq!(play(&mut anim, &info, 1), vec![2]);
+        assert_eq!(anim.current_tag(), Some("run"));
+        assert_eq!(anim.playback_speed(), 2.);
+        assert_eq!(anim.custom_size, Some(Vec2::splat(8.)));
+    }
+
+    #[test]
+    fn goto() {
+        let mut info = info(
+            &[100, 100, 100, 100],
+            &[
+                ("walk", 0..2, AsepriteAnimationDirection::Forward),
+                ("run", 2..4, AsepriteAnimationDirection::Reverse),
+            ],
+        );
+        let mut anim = AsepriteAnimation::tag("walk");
+        anim.custom_size = Some(Vec2::splat(8.));
+        assert_eq!(play(&mut anim, &info, 1), vec![1]);
+
+        assert!(anim.goto(&info, "run", 1));
+        assert_eq!(anim.current_tag(), Some("run"));
+        assert_eq!(anim.current_frame(), 2);
+        assert_eq!(play(&mut anim, &info, 2), vec![3, 2]);
+        assert_eq!(anim.custom_size, Some(Vec2::splat(8.)));
+
+        assert!(!anim.goto(&info, "run", 2));
+        assert!(!anim.goto(&info, "jump", 0));
+        assert_eq!(anim.current_tag(), Some("run"));
+        assert_eq!(anim.current_frame(), 2);
+
+        // The settings of the tag apply right away
+        let user_data = Some(AsepriteUserData {
+            text: Some("speed=2; once=true".to_string()),
+            color: None,
+        });
+        info.tags.get_mut("run").unwrap().user_data = user_data.clone();
+        info.tag_list[1].user_data = user_data;
+        assert!(anim.goto(&info, "run", 0));
+        assert_eq!(anim.tag_settings().speed, Some(2.));
+        assert_eq!(play(&mut anim, &info, 2), vec![2, 2]);
+        assert!(anim.is_paused());
+    }
+
+    #[test]
+    fn loop_count() {
+        let info = walk(&[100, 100, 100], 0..3, AsepriteAnimationDirection::Forward);
+        let mut anim = AsepriteAnimation::tag("walk");
+        anim.update(&info, Duration::ZERO);
+        assert_eq!(anim.remaining_frames(&info), 2);
+        assert_eq!(play(&mut anim, &info, 4), vec![1, 2, 0, 1]);
+        assert_eq!(anim.loop_count(), 1);
+        assert_eq!(anim.remaining_frames(&info), 1);
+
+        let info = walk(&[100, 100, 100], 0..3, AsepriteAnimationDirection::PingPong);
+        let mut anim = AsepriteAnimation::tag("walk");
+        assert_eq!(play(&mut anim, &info, 5), vec![1, 2, 1, 0, 1]);
+        assert_eq!(anim.loop_count(), 1);
+        assert_eq!(anim.remaining_frames(&info), 3);
+    }
+
+    #[test]
+    fn builder() {
+        let info = walk(
+            &[100, 100, 100, 100],
+            0..4,
+            AsepriteAnimationDirection::Forward,
+        );
+        let mut anim = AsepriteAnimation::tag("walk").starting_frame(2).paused();
+        assert_eq!(play(&mut anim, &info, 2), vec![2, 2]);
+
+        anim.play();
+        anim.set_speed(2.);
+        assert_eq!(play(&mut anim, &info, 2), vec![0, 2]);
+        assert_eq!(
+            AsepriteAnimation::tag("walk"),
+            AsepriteAnimation::from("walk")
+        );
+    }
+
+    #[test]
+    fn lod_carries_skipped_time() {
+        let entity = bevy::ecs::entity::Entity::from_raw(4);
+        let mut lod = AnimationLod::every(3);
+        let frame = Duration::from_millis(10);
+
+        // Entity 4 starts one frame into its interval
+        let ticks: Vec<_> = (0..5).map(|_| lod.tick(entity, frame)).collect();
+        assert_eq!(
+            ticks,
+            vec![None, Some(frame * 2), None, None, Some(frame * 3)]
+        );
+
+        lod.interval = 1;
+        assert_eq!(lod.tick(entity, frame), Some(frame));
+    }
 }
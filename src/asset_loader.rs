@@ -0,0 +1,45 @@
+use bevy::prelude::*;
+
+use crate::Aseprite;
+
+/// Moves from one state to the next once every loaded aseprite is ready
+///
+/// `bevy_asset_loader` considers an aseprite loaded as soon as the file is
+/// parsed, but its atlas is only built afterwards. Continue the loading state
+/// to `state` instead of the game state, and this plugin moves on to
+/// `next_state` once the atlases are built.
+///
+/// ```rust,ignore
+/// app.add_loading_state(
+///     LoadingState::new(GameState::Loading)
+///         .continue_to_state(GameState::BuildingAtlases)
+///         .load_collection::<Sprites>(),
+/// )
+/// .add_plugins(AsepriteLoadingPlugin {
+///     state: GameState::BuildingAtlases,
+///     next_state: GameState::Playing,
+/// });
+/// ```
+#[derive(Debug, Clone)]
+pub struct AsepriteLoadingPlugin<S: States> {
+    /// State in which the plugin waits for the aseprites
+    pub state: S,
+    /// State entered once every aseprite is ready
+    pub next_state: S,
+}
+
+impl<S: States> Plugin for AsepriteLoadingPlugin<S> {
+    fn build(&self, app: &mut App) {
+        let next_state = self.next_state.clone();
+        app.add_systems(
+            Update,
+            (move |mut state: ResMut<NextState<S>>| state.set(next_state.clone()))
+                .run_if(in_state(self.state.clone()).and_then(aseprites_ready)),
+        );
+    }
+}
+
+/// Run condition returning `true` once every loaded aseprite is ready to use
+pub fn aseprites_ready(aseprites: Res<Assets<Aseprite>>) -> bool {
+    aseprites.iter().all(|(_, aseprite)| aseprite.is_ready())
+}
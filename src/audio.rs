@@ -0,0 +1,80 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{anim::AsepriteAnimation, Aseprite};
+
+/// Plays sounds when an animation enters a frame with a marker, e.g. to sync
+/// footsteps with the walk cycle
+///
+/// Markers are the texts of the user data of the cels of a frame, set in the
+/// properties of the cels in aseprite. Frames skipped within a single update
+/// don't play their sounds.
+///
+/// ```rust,ignore
+/// commands.spawn((
+///     AsepriteBundle { .. },
+///     AsepriteAudioCues::default().with("step", asset_server.load("step.ogg")),
+/// ));
+/// ```
+#[derive(Debug, Component, Clone)]
+pub struct AsepriteAudioCues {
+    /// Sound played for each marker
+    pub cues: HashMap<String, Handle<AudioSource>>,
+    /// How the sounds are played, despawned once done by default
+    pub settings: PlaybackSettings,
+    // Frame the markers were last checked for
+    last_frame: Option<usize>,
+}
+
+impl Default for AsepriteAudioCues {
+    fn default() -> Self {
+        Self {
+            cues: HashMap::new(),
+            settings: PlaybackSettings::DESPAWN,
+            last_frame: None,
+        }
+    }
+}
+
+impl AsepriteAudioCues {
+    /// Play a sound whenever a frame with the marker is entered
+    pub fn with(mut self, marker: impl Into<String>, sound: Handle<AudioSource>) -> Self {
+        self.cues.insert(marker.into(), sound);
+        self
+    }
+}
+
+pub(crate) fn play_audio_cues(
+    mut commands: Commands,
+    aseprites: Res<Assets<Aseprite>>,
+    mut cues_query: Query<(
+        &Handle<Aseprite>,
+        &AsepriteAnimation,
+        &mut AsepriteAudioCues,
+    )>,
+) {
+    for (handle, animation, mut cues) in cues_query.iter_mut() {
+        let info = match aseprites.get(handle).and_then(Aseprite::info) {
+            Some(info) => info,
+            None => continue,
+        };
+        let frame = animation.current_frame();
+        if cues.last_frame == Some(frame) {
+            continue;
+        }
+        cues.last_frame = Some(frame);
+
+        // Frames passed over during lag spikes still play their sounds
+        let frames = animation.skipped_frames().iter().chain([&frame]);
+        let markers = frames
+            .filter_map(|&frame| info.frame_infos.get(frame))
+            .flat_map(|frame_info| &frame_info.markers);
+        for marker in markers {
+            if let Some(sound) = cues.cues.get(marker) {
+                commands.spawn(AudioBundle {
+                    source: sound.clone(),
+                    settings: cues.settings,
+                });
+            }
+        }
+    }
+}
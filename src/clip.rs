@@ -0,0 +1,173 @@
+use std::time::Duration;
+
+use bevy::{
+    animation::{AnimationClip, EntityPath, Keyframes, VariableCurve},
+    prelude::*,
+    render::mesh::morph::MorphWeights,
+};
+
+use crate::{anim::AsepriteAnimation, flipbook::playback_frames, group::AsepriteGroup, Aseprite};
+
+// Keyframes of a frame end just before the next frame starts, the weights
+// being interpolated in between
+const FRAME_END: f32 = 1e-4;
+
+/// Marks a sprite whose frames are driven by an [`AnimationPlayer`]
+///
+/// Bevy's animation clips can't animate the atlas index of a sprite, so the
+/// frame is stored in the first weight of a [`MorphWeights`] instead and
+/// copied to the sprite once the clips have been applied. Pause the
+/// [`AsepriteAnimation`] of the sprite so it doesn't change frames on its
+/// own.
+#[derive(Debug, Component, Clone, Copy, Default)]
+pub struct AsepriteClipTarget;
+
+/// A bundle making a sprite playable with [`Aseprite::animation_clip`]
+///
+/// ```rust,ignore
+/// let name = Name::new("player");
+/// let clip = aseprite.animation_clip("walk", EntityPath { parts: vec![name.clone()] })?;
+/// let mut player = AnimationPlayer::default();
+/// player.play(clips.add(clip)).repeat();
+/// commands.spawn((
+///     AsepriteBundle {
+///         aseprite: handle,
+///         animation: AsepriteAnimation::default().paused(),
+///         ..Default::default()
+///     },
+///     AsepriteClipBundle { name, player, ..Default::default() },
+/// ));
+/// ```
+#[derive(Bundle)]
+pub struct AsepriteClipBundle {
+    /// Name the clips target the sprite with
+    pub name: Name,
+    pub player: AnimationPlayer,
+    pub weights: MorphWeights,
+    pub target: AsepriteClipTarget,
+}
+
+impl Default for AsepriteClipBundle {
+    fn default() -> Self {
+        Self {
+            name: Name::default(),
+            player: AnimationPlayer::default(),
+            weights: MorphWeights::new(vec![0.], None)
+                .expect("a single weight is within the morph target limit"),
+            target: AsepriteClipTarget,
+        }
+    }
+}
+
+impl Aseprite {
+    /// Get a tag as an [`AnimationClip`], once the file is ready
+    ///
+    /// The clip animates the frame of the [`AsepriteClipTarget`] at `path`,
+    /// so the animation graph of Bevy can drive the sprite. Ping-pong tags
+    /// are unrolled into a single forward and backward pass, play the clip
+    /// repeated to loop it.
+    pub fn animation_clip(&self, tag: &str, path: EntityPath) -> Option<AnimationClip> {
+        let info = self.info.as_ref()?;
+        let tag = match info.tags.get(tag) {
+            Some(tag) => tag,
+            None => {
+                error!("Tag {} wasn't found.", tag);
+                return None;
+            }
+        };
+
+        let frames = playback_frames(tag);
+        let durations: Vec<_> = frames
+            .iter()
+            .map(|&frame| info.frame_infos[frame].duration)
+            .collect();
+        let (keyframe_timestamps, weights) = frame_keyframes(&frames, &durations);
+        let mut clip = AnimationClip::default();
+        clip.add_curve_to_path(
+            path,
+            VariableCurve {
+                keyframe_timestamps,
+                keyframes: Keyframes::Weights(weights),
+            },
+        );
+        Some(clip)
+    }
+}
+
+// Timestamps and frame of the keyframes holding each frame for its duration
+fn frame_keyframes(frames: &[usize], durations: &[Duration]) -> (Vec<f32>, Vec<f32>) {
+    let mut timestamps = vec![];
+    let mut weights = vec![];
+    let mut time = 0.;
+    for (&frame, duration) in frames.iter().zip(durations) {
+        let end = time + duration.as_secs_f32();
+        timestamps.extend([time, (end - FRAME_END).max(time)]);
+        weights.extend([frame as f32; 2]);
+        time = end;
+    }
+    // The clip lasts until the end of the last frame
+    if let Some(last) = timestamps.last_mut() {
+        *last = time;
+    }
+    (timestamps, weights)
+}
+
+pub(crate) fn apply_clip_frames(
+    aseprites: Res<Assets<Aseprite>>,
+    mut clip_query: Query<
+        (
+            &Handle<Aseprite>,
+            &MorphWeights,
+            &mut AsepriteAnimation,
+            Option<&mut TextureAtlasSprite>,
+            Option<&AsepriteGroup>,
+        ),
+        (With<AsepriteClipTarget>, Changed<MorphWeights>),
+    >,
+) {
+    for (handle, weights, mut animation, sprite, group) in clip_query.iter_mut() {
+        let aseprite = match aseprites.get(handle) {
+            Some(aseprite) => aseprite,
+            None => continue,
+        };
+        let frame = match weights.weights().first() {
+            Some(&weight) => weight.round().max(0.) as usize,
+            None => continue,
+        };
+        if animation.current_frame == frame {
+            continue;
+        }
+        animation.current_frame = frame;
+        if let Some(mut sprite) = sprite {
+            match aseprite.frame_index(group, frame) {
+                Some(index) if sprite.index != index => sprite.index = index,
+                Some(_) => {}
+                None => error!("Frame {} is out of bounds", frame),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::{frame_keyframes, FRAME_END};
+
+    #[test]
+    fn frames_are_held_for_their_duration() {
+        let (timestamps, weights) = frame_keyframes(
+            &[2, 3, 2],
+            &[
+                Duration::from_millis(100),
+                Duration::from_millis(200),
+                Duration::from_millis(100),
+            ],
+        );
+        assert_eq!(
+            timestamps,
+            vec![0., 0.1 - FRAME_END, 0.1, 0.3 - FRAME_END, 0.3, 0.4]
+        );
+        assert_eq!(weights, vec![2., 2., 3., 3., 2., 2.]);
+    }
+}
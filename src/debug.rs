@@ -0,0 +1,210 @@
+use std::path::Path;
+
+use bevy::{
+    ecs::schedule::{InternedScheduleLabel, ScheduleLabel},
+    prelude::*,
+    sprite::Anchor,
+};
+
+use crate::{anim::AsepriteAnimation, Aseprite, AsepriteInfo, AsepriteSystems};
+
+/// Draws the slices of animated aseprites and labels them with their frame
+/// and tag
+///
+/// Slices are drawn with gizmos, their pivot as a small circle. The label is
+/// a text child spawned above each sprite. The systems run in the schedule
+/// of the [`AsepritePlugin`](crate::AsepritePlugin).
+pub struct AsepriteDebugPlugin;
+
+impl Plugin for AsepriteDebugPlugin {
+    fn build(&self, _app: &mut App) {}
+
+    // The aseprite plugin may be added after this one
+    fn finish(&self, app: &mut App) {
+        let schedule = app
+            .world
+            .get_resource::<AsepriteSchedule>()
+            .map_or_else(|| Update.intern(), |schedule| schedule.0);
+        app.add_systems(
+            schedule,
+            (draw_slices, label_frames).after(AsepriteSystems::Animate),
+        );
+    }
+}
+
+// The schedule the aseprite plugin was added to
+#[derive(Debug, Clone, Copy, Resource)]
+pub(crate) struct AsepriteSchedule(pub(crate) InternedScheduleLabel);
+
+// Text child showing the frame and tag of an aseprite
+#[derive(Component)]
+struct DebugLabel(Entity);
+
+// Position of a canvas pixel relative to the sprite, aseprite positions
+// pointing down
+fn canvas_to_local(point: Vec2, canvas: Vec2, size: Vec2, sprite: &TextureAtlasSprite) -> Vec2 {
+    let mut point = point;
+    if sprite.flip_x {
+        point.x = canvas.x - point.x;
+    }
+    if sprite.flip_y {
+        point.y = canvas.y - point.y;
+    }
+    (Vec2::new(point.x / canvas.x - 0.5, 0.5 - point.y / canvas.y) - sprite.anchor.as_vec()) * size
+}
+
+// Size of the canvas, and of the sprite drawn from it
+fn sprite_size(info: &AsepriteInfo, sprite: &TextureAtlasSprite) -> (Vec2, Vec2) {
+    let canvas = Vec2::new(info.dimensions.0 as f32, info.dimensions.1 as f32);
+    (canvas, sprite.custom_size.unwrap_or(canvas))
+}
+
+fn draw_slices(
+    mut gizmos: Gizmos,
+    aseprites: Res<Assets<Aseprite>>,
+    query: Query<(
+        &Handle<Aseprite>,
+        &AsepriteAnimation,
+        &TextureAtlasSprite,
+        &GlobalTransform,
+    )>,
+) {
+    for (handle, animation, sprite, transform) in query.iter() {
+        let info = match aseprites.get(handle).and_then(Aseprite::info) {
+            Some(info) => info,
+            None => continue,
+        };
+        let (canvas, size) = sprite_size(info, sprite);
+        let to_world = |point: Vec2| {
+            transform
+                .transform_point(canvas_to_local(point, canvas, size, sprite).extend(0.))
+                .truncate()
+        };
+
+        for name in info.slice_keys.keys() {
+            let slice = match info.slice_at(name, animation.current_frame() as u16) {
+                Some(slice) => slice,
+                None => continue,
+            };
+            let min = Vec2::new(slice.position_x as f32, slice.position_y as f32);
+            let max = min + Vec2::new(slice.width as f32, slice.height as f32);
+            gizmos.linestrip_2d(
+                [
+                    min,
+                    Vec2::new(max.x, min.y),
+                    max,
+                    Vec2::new(min.x, max.y),
+                    min,
+                ]
+                .map(to_world),
+                Color::YELLOW,
+            );
+            if let Some(pivot) = &slice.pivot {
+                let pivot = min + Vec2::new(pivot.x_pivot as f32, pivot.y_pivot as f32);
+                gizmos.circle_2d(to_world(pivot), 1., Color::RED);
+            }
+        }
+    }
+}
+
+fn label_frames(
+    mut commands: Commands,
+    aseprites: Res<Assets<Aseprite>>,
+    query: Query<(
+        Entity,
+        &Handle<Aseprite>,
+        &AsepriteAnimation,
+        &TextureAtlasSprite,
+        Option<&DebugLabel>,
+    )>,
+    mut labels: Query<(&mut Text, &mut Transform)>,
+) {
+    for (entity, handle, animation, sprite, label) in query.iter() {
+        let info = match aseprites.get(handle).and_then(Aseprite::info) {
+            Some(info) => info,
+            None => continue,
+        };
+        let value = match animation.current_tag() {
+            Some(tag) => format!("{} ({})", animation.current_frame(), tag),
+            None => animation.current_frame().to_string(),
+        };
+        // Just above the middle of the top edge
+        let (canvas, size) = sprite_size(info, sprite);
+        let top = canvas_to_local(Vec2::new(canvas.x / 2., 0.), canvas, size, sprite);
+        let translation = (top + Vec2::Y * 4.).extend(1.);
+
+        let label = match label {
+            Some(label) => label.0,
+            None => {
+                let label = commands
+                    .spawn(Text2dBundle {
+                        text: Text::from_section(
+                            value,
+                            TextStyle {
+                                font_size: 12.,
+                                ..Default::default()
+                            },
+                        ),
+                        text_anchor: Anchor::BottomCenter,
+                        transform: Transform::from_translation(translation),
+                        ..Default::default()
+                    })
+                    .id();
+                commands
+                    .entity(entity)
+                    .insert(DebugLabel(label))
+                    .add_child(label);
+                continue;
+            }
+        };
+        let (mut text, mut transform) = match labels.get_mut(label) {
+            Ok(label) => label,
+            Err(_) => continue,
+        };
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+        transform.translation = translation;
+    }
+}
+
+// Name the dumped images start with, the file name without its extension
+fn dump_name(ase: &Aseprite) -> String {
+    ase.path
+        .as_deref()
+        .and_then(Path::file_stem)
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "bytes".to_string())
+}
+
+pub(crate) fn dump_frames(dir: &Path, ase: &Aseprite, frames: &[(Vec<u8>, u32, u32)]) {
+    if let Err(err) = std::fs::create_dir_all(dir) {
+        error!("Could not create {}: {}", dir.display(), err);
+        return;
+    }
+    let name = dump_name(ase);
+    for (index, (data, width, height)) in frames.iter().enumerate() {
+        let path = dir.join(format!("{}_frame_{}.png", name, index));
+        let result = image::save_buffer(&path, data, *width, *height, image::ColorType::Rgba8);
+        if let Err(err) = result {
+            error!("Could not write {}: {}", path.display(), err);
+        }
+    }
+}
+
+pub(crate) fn dump_atlas(dir: &Path, ase: &Aseprite, texture: &Image) {
+    let size = texture.size();
+    // Mipmaps come after the full size image
+    let len = (size.x * size.y * 4) as usize;
+    let atlas = match image::RgbaImage::from_raw(size.x, size.y, texture.data[..len].to_vec()) {
+        Some(atlas) => atlas,
+        None => {
+            error!("The atlas of {} isn't an RGBA image", dump_name(ase));
+            return;
+        }
+    };
+    let path = dir.join(format!("{}_atlas.png", dump_name(ase)));
+    if let Err(err) = atlas.save(&path) {
+        error!("Could not write {}: {}", path.display(), err);
+    }
+}
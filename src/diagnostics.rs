@@ -0,0 +1,255 @@
+use std::{mem::size_of, time::Duration};
+
+use bevy::{
+    diagnostic::{Diagnostic, DiagnosticId, Diagnostics, RegisterDiagnostic},
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+use crate::{reader::AsepriteFrameInfo, Aseprite, AsepriteInfo, AsepriteSlice, AsepriteTag};
+
+/// Timings recorded while loading a single aseprite
+#[derive(Debug, Clone, Default)]
+pub struct AsepriteLoadTimings {
+    /// Time spent parsing the file
+    pub parse: Duration,
+    /// Time spent compositing the layers of every frame
+    pub composite: Duration,
+    /// Time spent packing the frames into the atlas
+    pub atlas_build: Duration,
+    /// Size of the atlas texture, in pixels
+    pub atlas_size: UVec2,
+}
+
+/// Memory used by aseprites, see [`Aseprite::memory_usage`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AsepriteMemoryReport {
+    /// Size of the textures (frame and group atlases, static image, layer
+    /// and effect textures), in bytes
+    pub atlas_bytes: usize,
+    /// Approximate size of the info (tags, slices, frame infos), in bytes
+    pub info_bytes: usize,
+    /// Number of frames
+    pub frames: usize,
+}
+
+/// Load timings of every aseprite processed so far
+///
+/// The latest timings are also reported to Bevy's diagnostics, see
+/// [`AsepriteDiagnostics::PARSE_TIME`] and friends, along with the cost of
+/// animating ([`AsepriteDiagnostics::ANIMATED_ENTITIES`],
+/// [`AsepriteDiagnostics::FRAMES_ADVANCED`]) and the memory used by the
+/// textures ([`AsepriteDiagnostics::ATLAS_MEMORY`]) and infos
+/// ([`AsepriteDiagnostics::INFO_MEMORY`]).
+#[derive(Debug, Default, Resource)]
+pub struct AsepriteDiagnostics {
+    pub timings: HashMap<AssetId<Aseprite>, AsepriteLoadTimings>,
+    /// Memory used by every loaded aseprite, updated each frame
+    ///
+    /// Textures shared by identical files are only counted once.
+    pub memory: AsepriteMemoryReport,
+}
+
+impl AsepriteDiagnostics {
+    /// Time spent parsing the last loaded aseprite, in ms
+    pub const PARSE_TIME: DiagnosticId =
+        DiagnosticId::from_u128(0x6d1b5f0e_4b6c_4e3c_9e3c_2a0f6a3f9e01);
+    /// Time spent compositing the last loaded aseprite, in ms
+    pub const COMPOSITE_TIME: DiagnosticId =
+        DiagnosticId::from_u128(0x6d1b5f0e_4b6c_4e3c_9e3c_2a0f6a3f9e02);
+    /// Time spent building the atlas of the last loaded aseprite, in ms
+    pub const ATLAS_BUILD_TIME: DiagnosticId =
+        DiagnosticId::from_u128(0x6d1b5f0e_4b6c_4e3c_9e3c_2a0f6a3f9e03);
+    /// Number of entities animated each update
+    pub const ANIMATED_ENTITIES: DiagnosticId =
+        DiagnosticId::from_u128(0x6d1b5f0e_4b6c_4e3c_9e3c_2a0f6a3f9e04);
+    /// Number of animations which changed frame each update
+    pub const FRAMES_ADVANCED: DiagnosticId =
+        DiagnosticId::from_u128(0x6d1b5f0e_4b6c_4e3c_9e3c_2a0f6a3f9e05);
+    /// Size of the atlas and static textures of every loaded aseprite, in bytes
+    pub const ATLAS_MEMORY: DiagnosticId =
+        DiagnosticId::from_u128(0x6d1b5f0e_4b6c_4e3c_9e3c_2a0f6a3f9e06);
+    /// Approximate size of the info of every loaded aseprite, in bytes
+    pub const INFO_MEMORY: DiagnosticId =
+        DiagnosticId::from_u128(0x6d1b5f0e_4b6c_4e3c_9e3c_2a0f6a3f9e07);
+
+    /// Get the timings of an aseprite, once its atlas has been built
+    pub fn get(&self, id: impl Into<AssetId<Aseprite>>) -> Option<&AsepriteLoadTimings> {
+        self.timings.get(&id.into())
+    }
+
+    pub(crate) fn record(
+        &mut self,
+        diagnostics: &mut Diagnostics,
+        id: AssetId<Aseprite>,
+        timings: AsepriteLoadTimings,
+    ) {
+        diagnostics.add_measurement(Self::PARSE_TIME, || timings.parse.as_secs_f64() * 1000.);
+        diagnostics.add_measurement(Self::COMPOSITE_TIME, || {
+            timings.composite.as_secs_f64() * 1000.
+        });
+        diagnostics.add_measurement(Self::ATLAS_BUILD_TIME, || {
+            timings.atlas_build.as_secs_f64() * 1000.
+        });
+        self.timings.insert(id, timings);
+    }
+}
+
+pub(crate) fn register(app: &mut App) {
+    app.init_resource::<AsepriteDiagnostics>()
+        .register_diagnostic(
+            Diagnostic::new(AsepriteDiagnostics::PARSE_TIME, "aseprite_parse_time", 20)
+                .with_suffix("ms"),
+        )
+        .register_diagnostic(
+            Diagnostic::new(
+                AsepriteDiagnostics::COMPOSITE_TIME,
+                "aseprite_composite_time",
+                20,
+            )
+            .with_suffix("ms"),
+        )
+        .register_diagnostic(
+            Diagnostic::new(
+                AsepriteDiagnostics::ATLAS_BUILD_TIME,
+                "aseprite_atlas_build_time",
+                20,
+            )
+            .with_suffix("ms"),
+        )
+        .register_diagnostic(Diagnostic::new(
+            AsepriteDiagnostics::ANIMATED_ENTITIES,
+            "aseprite_animated_entities",
+            20,
+        ))
+        .register_diagnostic(Diagnostic::new(
+            AsepriteDiagnostics::FRAMES_ADVANCED,
+            "aseprite_frames_advanced",
+            20,
+        ))
+        .register_diagnostic(
+            Diagnostic::new(
+                AsepriteDiagnostics::ATLAS_MEMORY,
+                "aseprite_atlas_memory",
+                20,
+            )
+            .with_suffix("B"),
+        )
+        .register_diagnostic(
+            Diagnostic::new(AsepriteDiagnostics::INFO_MEMORY, "aseprite_info_memory", 20)
+                .with_suffix("B"),
+        );
+}
+
+impl Aseprite {
+    /// Get the memory used by the textures and info of the file
+    ///
+    /// Textures shared with identical files are counted for each of them,
+    /// [`AsepriteDiagnostics::memory`] sums every file counting them once.
+    pub fn memory_usage(
+        &self,
+        atlases: &Assets<TextureAtlas>,
+        images: &Assets<Image>,
+    ) -> AsepriteMemoryReport {
+        let textures: HashSet<_> = self.textures(atlases).collect();
+        AsepriteMemoryReport {
+            atlas_bytes: texture_bytes(textures, images),
+            info_bytes: self.info.as_ref().map_or(0, info_bytes),
+            frames: self.info.as_ref().map_or(0, |info| info.frame_count),
+        }
+    }
+
+    // Every texture built for the file, the slice atlas shares the frame one
+    fn textures<'a>(
+        &'a self,
+        atlases: &'a Assets<TextureAtlas>,
+    ) -> impl Iterator<Item = AssetId<Image>> + 'a {
+        let atlases = self
+            .atlas
+            .iter()
+            .chain(self.group_atlases.values().map(|group| &group.atlas))
+            .filter_map(|atlas| atlases.get(atlas))
+            .map(|atlas| atlas.texture.id());
+        let images = self
+            .image
+            .iter()
+            .chain(&self.effect_textures)
+            .chain(&self.normal_texture)
+            .chain(&self.emissive_texture)
+            .map(Handle::id);
+        atlases.chain(images)
+    }
+}
+
+fn texture_bytes(textures: HashSet<AssetId<Image>>, images: &Assets<Image>) -> usize {
+    textures
+        .into_iter()
+        .filter_map(|texture| images.get(texture))
+        .map(|image| image.data.len())
+        .sum()
+}
+
+// Heap data of the names, keys and markers is counted, not the spare
+// capacity of the maps
+fn info_bytes(info: &AsepriteInfo) -> usize {
+    let tag_size = |tag: &AsepriteTag| size_of::<AsepriteTag>() + tag.name.len();
+    let slice_size = |slice: &AsepriteSlice| size_of::<AsepriteSlice>() + slice.name.len();
+    let tags: usize = info
+        .tags
+        .iter()
+        .map(|(name, tag)| name.len() + tag_size(tag))
+        .chain(info.tag_list.iter().map(tag_size))
+        .sum();
+    let slices: usize = info
+        .slices
+        .iter()
+        .map(|(name, slice)| name.len() + slice_size(slice))
+        .chain(
+            info.slice_keys
+                .iter()
+                .map(|(name, keys)| name.len() + keys.iter().map(slice_size).sum::<usize>()),
+        )
+        .sum();
+    let frames: usize = info
+        .frame_infos
+        .iter()
+        .flat_map(|frame_info| &frame_info.markers)
+        .map(|marker| size_of::<String>() + marker.len())
+        .sum::<usize>()
+        + info.frame_infos.len() * size_of::<AsepriteFrameInfo>();
+    let palette = info
+        .palette
+        .as_ref()
+        .map_or(0, |palette| palette.entries.len() * 4);
+    size_of::<AsepriteInfo>() + tags + slices + frames + palette
+}
+
+// Textures shared by identical files are only counted once
+pub(crate) fn measure_memory(
+    mut diagnostics: Diagnostics,
+    mut memory_diagnostics: ResMut<AsepriteDiagnostics>,
+    aseprites: Res<Assets<Aseprite>>,
+    atlases: Res<Assets<TextureAtlas>>,
+    images: Res<Assets<Image>>,
+) {
+    let mut textures = HashSet::new();
+    let mut memory = AsepriteMemoryReport::default();
+    for (_, aseprite) in aseprites.iter() {
+        textures.extend(aseprite.textures(&atlases));
+        if let Some(info) = &aseprite.info {
+            memory.info_bytes += info_bytes(info);
+            memory.frames += info.frame_count;
+        }
+    }
+    memory.atlas_bytes = texture_bytes(textures, &images);
+
+    diagnostics.add_measurement(AsepriteDiagnostics::ATLAS_MEMORY, || {
+        memory.atlas_bytes as f64
+    });
+    diagnostics.add_measurement(AsepriteDiagnostics::INFO_MEMORY, || {
+        memory.info_bytes as f64
+    });
+    if memory_diagnostics.memory != memory {
+        memory_diagnostics.memory = memory;
+    }
+}
@@ -0,0 +1,243 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{group::AsepriteGroup, Aseprite, AtlasEffect};
+
+/// Draws the frames of an entity from another image, e.g. a white silhouette
+/// flashing when hit or an outline around a selected unit
+///
+/// The image needs the same layout as the atlas of the entity, the frames are
+/// cut from the same rects. Removing the component draws the frames from the
+/// atlas again.
+#[derive(Debug, Component, Clone, PartialEq, Eq)]
+pub struct AsepriteMaterialOverride {
+    /// Image the frames are drawn from
+    pub texture: Handle<Image>,
+}
+
+impl AsepriteMaterialOverride {
+    /// Draw the frames from an image laid out like the atlas
+    pub fn new(texture: Handle<Image>) -> Self {
+        Self { texture }
+    }
+}
+
+// The atlas an entity is drawn from without any override
+fn base_atlas<'a>(
+    aseprite: &'a Aseprite,
+    group: Option<&AsepriteGroup>,
+) -> Option<&'a Handle<TextureAtlas>> {
+    match group {
+        Some(group) => aseprite.group_atlas(&group.name),
+        None => aseprite.atlas.as_ref(),
+    }
+}
+
+pub(crate) fn apply_material_overrides(
+    aseprites: Res<Assets<Aseprite>>,
+    mut atlases: ResMut<Assets<TextureAtlas>>,
+    // Atlases sharing the rects of a base atlas, by base atlas and texture
+    mut overridden: Local<HashMap<(AssetId<TextureAtlas>, AssetId<Image>), Handle<TextureAtlas>>>,
+    mut removed: RemovedComponents<AsepriteMaterialOverride>,
+    mut sprites: ParamSet<(
+        Query<(
+            &Handle<Aseprite>,
+            Option<&AsepriteGroup>,
+            &mut Handle<TextureAtlas>,
+        )>,
+        Query<
+            (
+                &Handle<Aseprite>,
+                Option<&AsepriteGroup>,
+                &AsepriteMaterialOverride,
+                &mut Handle<TextureAtlas>,
+            ),
+            Or<(
+                Changed<AsepriteMaterialOverride>,
+                Changed<Handle<TextureAtlas>>,
+            )>,
+        >,
+    )>,
+) {
+    // Atlases of files which are gone aren't used anymore
+    overridden.retain(|(base, _), _| atlases.contains(*base));
+
+    let mut restored = sprites.p0();
+    for entity in removed.read() {
+        let (handle, group, mut atlas) = match restored.get_mut(entity) {
+            Ok(sprite) => sprite,
+            Err(_) => continue,
+        };
+        let base = match aseprites.get(handle).and_then(|ase| base_atlas(ase, group)) {
+            Some(base) => base,
+            None => continue,
+        };
+        if *atlas != *base {
+            *atlas = base.clone();
+        }
+    }
+
+    for (handle, group, material, mut atlas) in sprites.p1().iter_mut() {
+        let base = match aseprites.get(handle).and_then(|ase| base_atlas(ase, group)) {
+            Some(base) => base,
+            None => {
+                debug!("Aseprite atlas not ready");
+                continue;
+            }
+        };
+        let key = (base.id(), material.texture.id());
+        let target = match overridden.get(&key) {
+            Some(target) => target.clone(),
+            None => {
+                let mut target = match atlases.get(base) {
+                    Some(base) => base.clone(),
+                    None => continue,
+                };
+                target.texture = material.texture.clone();
+                let target = atlases.add(target);
+                overridden.insert(key, target.clone());
+                target
+            }
+        };
+        if *atlas != target {
+            *atlas = target;
+        }
+    }
+}
+
+impl Aseprite {
+    /// The image generated for an effect of the
+    /// [`effects`](crate::AsepriteLoaderSettings::effects) setting, once the
+    /// atlas has been built
+    ///
+    /// It is laid out like the frame atlas, ready for an
+    /// [`AsepriteMaterialOverride`].
+    pub fn effect_texture(&self, effect: &AtlasEffect) -> Option<&Handle<Image>> {
+        let index = self.settings.effects.iter().position(|e| e == effect)?;
+        self.effect_textures.get(index)
+    }
+}
+
+// Color of the pixels added by an effect, in the alpha mode of the atlas
+fn effect_color(color: Color, premultiply_alpha: bool) -> [u8; 4] {
+    let [red, green, blue, alpha] = color.as_rgba_u8();
+    if !premultiply_alpha {
+        return [red, green, blue, alpha];
+    }
+    let premultiply = |value: u8| (value as u32 * alpha as u32 / 255) as u8;
+    [
+        premultiply(red),
+        premultiply(green),
+        premultiply(blue),
+        alpha,
+    ]
+}
+
+// Applies an effect to the frames of an RGBA atlas buffer, each frame only
+// reading and writing the pixels of its own rect
+pub(crate) fn apply_effect(
+    effect: &AtlasEffect,
+    data: &[u8],
+    width: u32,
+    rects: &[URect],
+    premultiply_alpha: bool,
+) -> Vec<u8> {
+    let mut output = data.to_vec();
+    let index = |x: u32, y: u32| ((y * width + x) * 4) as usize;
+    for rect in rects {
+        // Whether the pixel at an offset from a point of the frame is visible
+        let visible = |x: u32, y: u32, dx: i32, dy: i32| {
+            let (x, y) = (x as i32 + dx, y as i32 + dy);
+            x >= rect.min.x as i32
+                && y >= rect.min.y as i32
+                && x < rect.max.x as i32
+                && y < rect.max.y as i32
+                && data[index(x as u32, y as u32) + 3] != 0
+        };
+        for y in rect.min.y..rect.max.y {
+            for x in rect.min.x..rect.max.x {
+                let pixel = &mut output[index(x, y)..index(x, y) + 4];
+                match effect {
+                    AtlasEffect::Silhouette => {
+                        let alpha = pixel[3];
+                        let white = if premultiply_alpha { alpha } else { 255 };
+                        pixel.copy_from_slice(&[white, white, white, alpha]);
+                    }
+                    AtlasEffect::Outline { width, color } => {
+                        let width = *width as i32;
+                        if pixel[3] == 0
+                            && (-width..=width)
+                                .any(|dy| (-width..=width).any(|dx| visible(x, y, dx, dy)))
+                        {
+                            pixel.copy_from_slice(&effect_color(*color, premultiply_alpha));
+                        }
+                    }
+                    AtlasEffect::DropShadow { offset, color } => {
+                        if pixel[3] == 0 && visible(x, y, -offset.0, -offset.1) {
+                            pixel.copy_from_slice(&effect_color(*color, premultiply_alpha));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::prelude::*;
+
+    use super::apply_effect;
+    use crate::AtlasEffect;
+
+    // A 4x3 atlas holding two frames of 2x3, a single pixel visible in the
+    // first one
+    fn atlas() -> (Vec<u8>, Vec<URect>) {
+        let mut data = vec![0; 4 * 3 * 4];
+        data[(4 + 1) * 4..(4 + 1) * 4 + 4].copy_from_slice(&[10, 20, 30, 128]);
+        (data, vec![URect::new(0, 0, 2, 3), URect::new(2, 0, 4, 3)])
+    }
+
+    fn alpha(data: &[u8]) -> Vec<u8> {
+        data.chunks(4).map(|pixel| pixel[3]).collect()
+    }
+
+    #[test]
+    fn silhouette_keeps_alpha() {
+        let (data, rects) = atlas();
+        let silhouette = apply_effect(&AtlasEffect::Silhouette, &data, 4, &rects, false);
+        assert_eq!(
+            &silhouette[(4 + 1) * 4..(4 + 1) * 4 + 4],
+            &[255, 255, 255, 128]
+        );
+        assert_eq!(alpha(&silhouette), alpha(&data));
+    }
+
+    #[test]
+    fn effects_stay_in_their_frame() {
+        let (data, rects) = atlas();
+        let outline = AtlasEffect::Outline {
+            width: 1,
+            color: Color::BLACK,
+        };
+        let outlined = apply_effect(&outline, &data, 4, &rects, false);
+        #[rustfmt::skip]
+        assert_eq!(alpha(&outlined), vec![
+            255, 255, 0, 0,
+            255, 128, 0, 0,
+            255, 255, 0, 0,
+        ]);
+
+        let shadow = AtlasEffect::DropShadow {
+            offset: (0, 1),
+            color: Color::BLACK,
+        };
+        let shadowed = apply_effect(&shadow, &data, 4, &rects, false);
+        #[rustfmt::skip]
+        assert_eq!(alpha(&shadowed), vec![
+            0, 0, 0, 0,
+            0, 128, 0, 0,
+            0, 255, 0, 0,
+        ]);
+    }
+}
@@ -1,34 +1,87 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
 use bevy_aseprite_reader as reader;
 
-#[derive(Debug)]
-pub enum AsepriteLoaderError {
-    Aseprite(reader::error::AsepriteError),
-    Anyhow(anyhow::Error),
-    Io(std::io::Error),
+/// The stage of loading an aseprite which failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsepriteLoadStage {
+    /// Reading the bytes of the file
+    Read,
+    /// Parsing the file
+    Parse,
+    /// Compositing the layers of its frames
+    Composite,
+    /// Packing its frames into the atlas
+    Atlas,
 }
 
-impl From<reader::error::AsepriteError> for AsepriteLoaderError {
-    fn from(value: reader::error::AsepriteError) -> Self {
-        Self::Aseprite(value)
+impl fmt::Display for AsepriteLoadStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Read => "read",
+            Self::Parse => "parse",
+            Self::Composite => "composite",
+            Self::Atlas => "build the atlas of",
+        })
     }
 }
 
-impl From<anyhow::Error> for AsepriteLoaderError {
-    fn from(value: anyhow::Error) -> Self {
-        Self::Anyhow(value)
-    }
+/// Errors that can occur while loading an aseprite
+///
+/// Files created with [`Aseprite::from_bytes`](crate::Aseprite::from_bytes)
+/// have no path, `<bytes>` is shown instead.
+#[derive(Debug, thiserror::Error)]
+pub enum AsepriteLoaderError {
+    /// The file couldn't be read
+    #[error("Could not read {}: {source}", .path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    /// The file is invalid, or its frames couldn't be composited
+    #[error("Could not {stage} {}: {source}", .path.display())]
+    Aseprite {
+        path: PathBuf,
+        stage: AsepriteLoadStage,
+        #[source]
+        source: reader::error::AsepriteError,
+    },
+    /// The frames couldn't be packed into an atlas
+    #[error("Could not {} {}: {message}", AsepriteLoadStage::Atlas, .path.display())]
+    Atlas { path: PathBuf, message: String },
+    /// The file comes from an asset source the loader isn't registered for
+    #[error("Could not load {}, the loader isn't registered for the {asset_source} asset source", .path.display())]
+    UnsupportedSource { path: PathBuf, asset_source: String },
+    /// The manifest of an [`AsepriteSet`](crate::set::AsepriteSet) is invalid,
+    /// or one of its files couldn't be read
+    #[error("Could not load the set {}: {message}", .path.display())]
+    Set { path: PathBuf, message: String },
 }
 
-impl From<std::io::Error> for AsepriteLoaderError {
-    fn from(value: std::io::Error) -> Self {
-        Self::Io(value)
+impl AsepriteLoaderError {
+    /// The path of the file which failed to load
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Io { path, .. }
+            | Self::Aseprite { path, .. }
+            | Self::Atlas { path, .. }
+            | Self::UnsupportedSource { path, .. }
+            | Self::Set { path, .. } => path,
+        }
     }
-}
 
-impl std::fmt::Display for AsepriteLoaderError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{self:?}")
+    /// The stage of loading which failed
+    pub fn stage(&self) -> AsepriteLoadStage {
+        match self {
+            Self::Io { .. } | Self::UnsupportedSource { .. } | Self::Set { .. } => {
+                AsepriteLoadStage::Read
+            }
+            Self::Aseprite { stage, .. } => *stage,
+            Self::Atlas { .. } => AsepriteLoadStage::Atlas,
+        }
     }
 }
-
-impl std::error::Error for AsepriteLoaderError {}
@@ -0,0 +1,164 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::{
+    reader::{raw::AsepriteAnimationDirection, AsepriteTag},
+    Aseprite,
+};
+
+/// The frames of a tag as rects of the atlas texture, in playback order
+///
+/// Meant for code drawing the frames without sprites, such as GPU particles
+/// (e.g. `bevy_hanabi` flipbooks) playing an explosion authored in aseprite.
+/// Ping-pong tags are unrolled into a single forward and backward pass.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Flipbook {
+    /// The atlas texture containing every frame
+    pub texture: Handle<Image>,
+    /// Size of the atlas texture, in pixels
+    pub size: Vec2,
+    /// Rect of each frame inside the texture, in pixels
+    pub rects: Vec<Rect>,
+    /// How long each frame is shown
+    pub durations: Vec<Duration>,
+}
+
+impl Flipbook {
+    /// Get the duration of a single pass through the frames
+    pub fn duration(&self) -> Duration {
+        self.durations.iter().sum()
+    }
+
+    /// Get the frame shown at a time, looping over the frames
+    pub fn frame_at(&self, time: Duration) -> usize {
+        let duration = self.duration();
+        if duration.is_zero() {
+            return 0;
+        }
+        let mut remaining = Duration::from_nanos((time.as_nanos() % duration.as_nanos()) as u64);
+        for (frame, &frame_duration) in self.durations.iter().enumerate() {
+            if remaining < frame_duration {
+                return frame;
+            }
+            remaining -= frame_duration;
+        }
+        self.durations.len() - 1
+    }
+
+    /// Get the rect of each frame in UV coordinates, from 0 to 1
+    pub fn uv_rects(&self) -> Vec<Rect> {
+        self.rects
+            .iter()
+            .map(|rect| Rect {
+                min: rect.min / self.size,
+                max: rect.max / self.size,
+            })
+            .collect()
+    }
+}
+
+impl Aseprite {
+    /// Get the frames of a tag as a [`Flipbook`], once the atlas is built
+    pub fn flipbook(&self, tag: &str, atlases: &Assets<TextureAtlas>) -> Option<Flipbook> {
+        let info = self.info.as_ref()?;
+        let atlas = atlases.get(self.atlas.as_ref()?)?;
+        let tag = match info.tags.get(tag) {
+            Some(tag) => tag,
+            None => {
+                error!("Tag {} wasn't found.", tag);
+                return None;
+            }
+        };
+
+        let frames = playback_frames(tag);
+        let mut flipbook = Flipbook {
+            texture: atlas.texture.clone(),
+            size: atlas.size,
+            rects: Vec::with_capacity(frames.len()),
+            durations: Vec::with_capacity(frames.len()),
+        };
+        for frame in frames {
+            let index = *self.frame_to_idx.get(frame)?;
+            flipbook.rects.push(atlas.textures[index]);
+            flipbook.durations.push(info.frame_infos[frame].duration);
+        }
+        Some(flipbook)
+    }
+}
+
+// The frames of a tag in the order they are shown in a single loop, ping-pong
+// tags going forward then back
+pub(crate) fn playback_frames(tag: &AsepriteTag) -> Vec<usize> {
+    let range = tag.frames.as_range();
+    // The end frames are only shown once per loop
+    let back_len = range.len().saturating_sub(2);
+    match tag.animation_direction {
+        AsepriteAnimationDirection::Forward => range.collect(),
+        AsepriteAnimationDirection::Reverse => range.rev().collect(),
+        AsepriteAnimationDirection::PingPong => {
+            let back = range.clone().rev().skip(1);
+            range.chain(back.take(back_len)).collect()
+        }
+        AsepriteAnimationDirection::PingPongReverse => {
+            let back = range.clone().skip(1);
+            range.rev().chain(back.take(back_len)).collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use bevy::prelude::*;
+
+    use super::{playback_frames, Flipbook};
+    use crate::reader::{
+        raw::{AsepriteAnimationDirection, AsepriteColor},
+        AsepriteTag, FrameSpan,
+    };
+
+    #[test]
+    fn frame_at_loops() {
+        let flipbook = Flipbook {
+            texture: Handle::default(),
+            size: Vec2::new(4., 2.),
+            rects: vec![Rect::new(0., 0., 2., 2.), Rect::new(2., 0., 4., 2.)],
+            durations: vec![Duration::from_millis(100), Duration::from_millis(300)],
+        };
+
+        assert_eq!(flipbook.duration(), Duration::from_millis(400));
+        assert_eq!(flipbook.frame_at(Duration::from_millis(50)), 0);
+        assert_eq!(flipbook.frame_at(Duration::from_millis(399)), 1);
+        assert_eq!(flipbook.frame_at(Duration::from_millis(450)), 0);
+        assert_eq!(flipbook.uv_rects()[1], Rect::new(0.5, 0., 1., 1.));
+    }
+
+    #[test]
+    fn playback_frames_follow_the_direction() {
+        let frames = |from, to, animation_direction| {
+            playback_frames(&AsepriteTag {
+                frames: FrameSpan::new(from, to),
+                animation_direction,
+                color: AsepriteColor {
+                    red: 0,
+                    green: 0,
+                    blue: 0,
+                    alpha: 255,
+                },
+                name: "walk".to_string(),
+                user_data: None,
+            })
+        };
+
+        use AsepriteAnimationDirection::*;
+        assert_eq!(frames(2, 5, Forward), vec![2, 3, 4, 5]);
+        assert_eq!(frames(2, 5, Reverse), vec![5, 4, 3, 2]);
+        assert_eq!(frames(2, 5, PingPong), vec![2, 3, 4, 5, 4, 3]);
+        assert_eq!(frames(2, 5, PingPongReverse), vec![5, 4, 3, 2, 3, 4]);
+        assert_eq!(frames(1, 1, PingPong), vec![1]);
+        // The last frame a file can have doesn't overflow
+        assert_eq!(frames(u16::MAX, u16::MAX, Forward), vec![u16::MAX as usize]);
+    }
+}
@@ -0,0 +1,75 @@
+use bevy::prelude::*;
+
+use crate::{anim::AsepriteAnimation, Aseprite};
+
+/// A layer group of an aseprite, drawn on its own
+///
+/// Only the layers nested in the group are drawn, so the group can be moved
+/// or rotated independently of the rest of the sprite, e.g. to turn the head
+/// of a character. The file needs to be loaded with
+/// [`group_atlases`](crate::AsepriteLoaderSettings::group_atlases) set.
+#[derive(Debug, Component, Clone, Default, PartialEq, Eq)]
+pub struct AsepriteGroup {
+    /// Name of the layer group
+    pub name: String,
+}
+
+impl AsepriteGroup {
+    /// Draw a layer group
+    pub fn new(name: impl Into<String>) -> Self {
+        Self { name: name.into() }
+    }
+}
+
+/// A bundle defining a layer group of an aseprite, drawn on its own
+///
+/// Spawn it as a child of an [`AsepriteBundle`](crate::AsepriteBundle) and
+/// give it the same animation to keep their frames in sync:
+///
+/// ```rust,ignore
+/// commands
+///     .spawn(AsepriteBundle {
+///         aseprite: player.clone(),
+///         animation: AsepriteAnimation::from("walk"),
+///         ..Default::default()
+///     })
+///     .with_children(|parent| {
+///         parent.spawn(AsepriteGroupBundle {
+///             group: AsepriteGroup::new("Head"),
+///             aseprite: player.clone(),
+///             animation: AsepriteAnimation::from("walk"),
+///             transform: Transform::from_xyz(0., 0., 0.1),
+///             ..Default::default()
+///         });
+///     });
+/// ```
+#[derive(Debug, Bundle, Default)]
+pub struct AsepriteGroupBundle {
+    pub group: AsepriteGroup,
+    pub transform: Transform,
+    pub global_transform: GlobalTransform,
+    pub visibility: Visibility,
+    pub inherited_visibility: InheritedVisibility,
+    pub view_visibility: ViewVisibility,
+    pub animation: AsepriteAnimation,
+    pub aseprite: Handle<Aseprite>,
+}
+
+impl Aseprite {
+    /// The atlas of a layer group, once it has been built
+    ///
+    /// Only built with
+    /// [`group_atlases`](crate::AsepriteLoaderSettings::group_atlases) set.
+    pub fn group_atlas(&self, group: &str) -> Option<&Handle<TextureAtlas>> {
+        self.group_atlases.get(group).map(|group| &group.atlas)
+    }
+
+    // Index of a frame in the frame atlas, or in the atlas of a group
+    pub(crate) fn frame_index(&self, group: Option<&AsepriteGroup>, frame: usize) -> Option<usize> {
+        let frame_to_idx = match group {
+            Some(group) => &self.group_atlases.get(&group.name)?.frame_to_idx,
+            None => &self.frame_to_idx,
+        };
+        frame_to_idx.get(frame).copied()
+    }
+}
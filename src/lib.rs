@@ -1,71 +1,552 @@
-#![allow(clippy::type_complexity)]
+#![allow(clippy::type_complexity, clippy::too_many_arguments)]
 #![doc = include_str!("../README.MD")]
 
 pub mod anim;
+pub mod asset_loader;
+#[cfg(feature = "audio")]
+pub mod audio;
+#[cfg(feature = "animation")]
+pub mod clip;
+#[cfg(feature = "debug")]
+pub mod debug;
+pub mod diagnostics;
+pub mod effects;
 mod error;
+pub mod flipbook;
+pub mod group;
 mod loader;
+pub mod nine_patch;
+pub mod set;
+mod stream;
+pub mod text;
+#[cfg(feature = "ui")]
+pub mod ui;
 
-use anim::AsepriteAnimation;
+use std::{path::PathBuf, time::Duration};
+
+use anim::{AsepriteAnimation, MissingAsepritePolicy};
 use bevy::{
     app::{Plugin, Update},
-    asset::{Asset, AssetApp, Handle},
+    asset::{io::AssetSourceId, Asset, AssetApp, AssetId, Handle},
     ecs::{
         bundle::Bundle,
-        schedule::{IntoSystemConfigs, SystemSet},
+        component::Component,
+        event::Event,
+        schedule::{
+            InternedScheduleLabel, IntoSystemConfigs, IntoSystemSetConfigs, ScheduleLabel,
+            SystemSet,
+        },
     },
+    math::{Rect, URect, Vec2},
     reflect::{TypePath, TypeUuid},
+    render::{
+        texture::Image,
+        view::{InheritedVisibility, ViewVisibility, Visibility},
+    },
     sprite::TextureAtlas,
     transform::components::{GlobalTransform, Transform},
+    utils::HashMap,
 };
 
-use bevy_aseprite_reader as reader;
-
 pub use bevy::sprite::TextureAtlasBuilder;
+#[cfg(feature = "derive")]
 pub use bevy_aseprite_derive::aseprite;
-use reader::AsepriteInfo;
+/// The parser of the files, for the types which aren't re-exported here
+pub use bevy_aseprite_reader as reader;
+pub use error::{AsepriteLoadStage, AsepriteLoaderError};
+pub use loader::{
+    AsepriteLoaderSettings, AtlasEffect, AtlasPacking, BackgroundMode, MissingCelPolicy, ParseMode,
+    TagKeys,
+};
+pub use reader::{raw::AsepriteAnimationDirection, AsepriteInfo, AsepriteSlice, AsepriteTag};
 
-pub struct AsepritePlugin;
+/// Plugin loading and animating aseprite files
+#[derive(Debug)]
+pub struct AsepritePlugin {
+    /// Modified files are only rebuilt once they haven't changed for this long
+    ///
+    /// Saving from Aseprite can fire several modifications in a row, this
+    /// avoids rebuilding the atlas for each of them.
+    pub rebuild_debounce: Duration,
+    /// Only update the frames which changed when a file is modified
+    ///
+    /// The atlas texture is updated in place and sprites keep their atlas,
+    /// as long as the canvas size, frame count and loader settings stay the
+    /// same. Otherwise the whole atlas is rebuilt. This keeps the file data
+    /// around to compare it with the next version.
+    pub incremental_reload: bool,
+    /// Keep the bytes of the file for incremental reloads instead of its
+    /// decoded data, parsing them again when the file changes
+    ///
+    /// The cels stay compressed as in the file, so this uses a fraction of
+    /// the memory at the cost of parsing the previous version on each reload.
+    /// Files loaded from sets keep their decoded data.
+    pub compact_reload_data: bool,
+    /// The schedule the systems of the plugin are added to
+    pub schedule: InternedScheduleLabel,
+    /// Build textures and insert sprites for the loaded files
+    ///
+    /// Headless apps (e.g. a server simulating the same animations as its
+    /// clients) can disable this, the plugin then doesn't need any of the
+    /// render resources.
+    pub load_textures: bool,
+    /// Asset sources the loader handles, every source if `None`
+    ///
+    /// Projects serving assets from an embedded VFS or an archive can
+    /// restrict aseprite files to it.
+    pub sources: Option<Vec<AssetSourceId<'static>>>,
+    /// Advance the animations and update the frame of their sprites
+    ///
+    /// Apps only drawing static sprites or slices, or setting the frames
+    /// themselves, can disable this so the animation systems don't run.
+    pub animate: bool,
+    /// Update the entities drawn from slices (text, nine-patches, UI buttons)
+    pub draw_slices: bool,
+    /// What happens to animated entities whose file isn't loaded anymore
+    pub missing_aseprite: MissingAsepritePolicy,
+}
 
-#[derive(Debug, SystemSet, Clone, Hash, PartialEq, Eq)]
-enum AsepriteSystems {
+impl Default for AsepritePlugin {
+    fn default() -> Self {
+        Self {
+            rebuild_debounce: Duration::from_millis(200),
+            incremental_reload: false,
+            compact_reload_data: false,
+            schedule: Update.intern(),
+            load_textures: true,
+            sources: None,
+            animate: true,
+            draw_slices: true,
+            missing_aseprite: MissingAsepritePolicy::Hide,
+        }
+    }
+}
+
+impl AsepritePlugin {
+    /// Add the systems of the plugin to another schedule than [`Update`]
+    pub fn in_schedule(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.schedule = schedule.intern();
+        self
+    }
+
+    /// Only load files from the given asset sources
+    ///
+    /// See [`AsepritePlugin::sources`]
+    pub fn only_from_sources(
+        mut self,
+        sources: impl IntoIterator<Item = impl Into<AssetSourceId<'static>>>,
+    ) -> Self {
+        self.sources = Some(sources.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Only load the file metadata and animate, without building any textures
+    ///
+    /// See [`AsepritePlugin::load_textures`]
+    pub fn without_textures(mut self) -> Self {
+        self.load_textures = false;
+        self
+    }
+
+    /// Don't advance any animation, the frame of the sprites is left alone
+    ///
+    /// See [`AsepritePlugin::animate`]
+    pub fn without_animation(mut self) -> Self {
+        self.animate = false;
+        self
+    }
+
+    /// Don't update the entities drawn from slices
+    ///
+    /// See [`AsepritePlugin::draw_slices`]
+    pub fn without_slices(mut self) -> Self {
+        self.draw_slices = false;
+        self
+    }
+
+    /// Set what happens to animated entities whose file isn't loaded anymore
+    ///
+    /// See [`MissingAsepritePolicy`]
+    pub fn on_missing_aseprite(mut self, policy: MissingAsepritePolicy) -> Self {
+        self.missing_aseprite = policy;
+        self
+    }
+}
+
+/// System sets of the plugin, each running after the previous one
+///
+/// They run in the schedule of the plugin, [`Update`] by default, so
+/// gameplay systems can e.g. sync hitboxes after [`Animate`] and before
+/// transforms are propagated.
+///
+/// [`Animate`]: AsepriteSystems::Animate
+#[derive(Debug, SystemSet, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum AsepriteSystems {
+    /// Builds the atlases of loaded and modified files
+    Load,
+    /// Inserts the sprites of new aseprite entities
     InsertSpriteSheet,
+    /// Updates the entities drawn from slices (text, nine-patches, UI buttons)
+    InsertSlices,
+    /// Advances the animations and updates the frame of their sprites
+    Animate,
 }
 
 impl Plugin for AsepritePlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
+        diagnostics::register(app);
         app.init_asset::<Aseprite>()
-            .register_asset_loader(loader::AsepriteLoader)
-            .add_systems(Update, loader::process_load)
-            .add_systems(
-                Update,
-                loader::insert_sprite_sheet.in_set(AsepriteSystems::InsertSpriteSheet),
-            )
-            .add_systems(
-                Update,
-                anim::update_animations.after(AsepriteSystems::InsertSpriteSheet),
+            .add_event::<AsepriteReady>()
+            .init_asset::<AsepriteSliceAsset>()
+            .init_asset::<AsepriteTagAsset>()
+            .register_asset_loader(loader::AsepriteLoader {
+                sources: self.sources.clone(),
+                load_textures: self.load_textures,
+                keep_source: self.incremental_reload && self.compact_reload_data,
+            })
+            .register_asset_loader(set::AsepriteSetLoader {
+                sources: self.sources.clone(),
+                load_textures: self.load_textures,
+            })
+            .insert_resource(loader::ReloadSettings {
+                debounce: self.rebuild_debounce,
+                incremental: self.incremental_reload,
+            })
+            .insert_resource(anim::AnimationSettings {
+                missing: self.missing_aseprite,
+            })
+            .configure_sets(
+                self.schedule,
+                (
+                    AsepriteSystems::Load,
+                    AsepriteSystems::InsertSpriteSheet,
+                    AsepriteSystems::InsertSlices,
+                    AsepriteSystems::Animate,
+                )
+                    .chain(),
+            );
+
+        #[cfg(feature = "debug")]
+        app.insert_resource(debug::AsepriteSchedule(self.schedule));
+
+        #[cfg(feature = "animation")]
+        app.add_systems(
+            bevy::app::PostUpdate,
+            clip::apply_clip_frames.after(bevy::animation::animation_player),
+        );
+
+        if !self.load_textures {
+            app.add_systems(
+                self.schedule,
+                loader::process_info.in_set(AsepriteSystems::Load),
             );
+            if self.animate {
+                app.add_systems(
+                    self.schedule,
+                    anim::update_animations.in_set(AsepriteSystems::Animate),
+                );
+            }
+            return;
+        }
+
+        app.add_systems(
+            self.schedule,
+            (
+                (loader::process_load, diagnostics::measure_memory)
+                    .chain()
+                    .in_set(AsepriteSystems::Load),
+                (loader::insert_sprite_sheet, loader::insert_static_sprite)
+                    .in_set(AsepriteSystems::InsertSpriteSheet),
+                effects::apply_material_overrides
+                    .after(loader::insert_sprite_sheet)
+                    .in_set(AsepriteSystems::InsertSpriteSheet),
+                // The sprites are inserted with commands, slices and
+                // animations see them on the same update
+                bevy::ecs::schedule::apply_deferred
+                    .after(AsepriteSystems::InsertSpriteSheet)
+                    .before(AsepriteSystems::InsertSlices),
+            ),
+        );
+
+        if self.draw_slices {
+            app.add_systems(
+                self.schedule,
+                (text::layout_text, nine_patch::layout_nine_patches)
+                    .in_set(AsepriteSystems::InsertSlices),
+            );
+            #[cfg(feature = "ui")]
+            app.add_systems(
+                self.schedule,
+                ui::update_buttons.in_set(AsepriteSystems::InsertSlices),
+            );
+        }
+
+        if self.animate {
+            app.add_systems(
+                self.schedule,
+                (
+                    anim::update_animations,
+                    stream::stream_frames,
+                    anim::smooth_motion,
+                    anim::update_aabbs,
+                )
+                    .chain()
+                    .in_set(AsepriteSystems::Animate),
+            );
+            #[cfg(feature = "audio")]
+            app.add_systems(
+                self.schedule,
+                audio::play_audio_cues
+                    .after(anim::update_animations)
+                    .in_set(AsepriteSystems::Animate),
+            );
+        }
     }
 }
 
 #[derive(Debug, Clone, TypePath, TypeUuid, Asset)]
 #[uuid = "b29abc81-6179-42e4-b696-3a5a52f44f73"]
 pub struct Aseprite {
+    // Path the file was loaded from, shown in errors
+    path: Option<PathBuf>,
     // Data is dropped after the atlas is built
     data: Option<reader::Aseprite>,
+    // Bytes of the file, kept until the atlas is built for compact
+    // incremental reloads
+    source: Option<Vec<u8>>,
+    // Settings the file was loaded with, applied when building the atlas
+    settings: AsepriteLoaderSettings,
+    // Hash of the file and its settings, identical files share their atlas
+    content_hash: u64,
+    // Time it took to parse the file, reported once the atlas is built
+    parse_time: Duration,
     // Info stores data such as tags and slices
     info: Option<AsepriteInfo>,
     // TextureAtlasBuilder might shift the index order when building so
     // we keep a mapping of frame# -> atlas index here
     frame_to_idx: Vec<usize>,
+    // Part of the canvas each frame was packed from
+    frame_rects: Vec<URect>,
     // Atlas that gets built from the frame info of the aseprite file
     atlas: Option<Handle<TextureAtlas>>,
+    // Single frame files also keep their image around so they can be
+    // drawn as a plain sprite without going through the atlas
+    image: Option<Handle<Image>>,
+    // Slices are cut out of the frame atlas texture, this maps
+    // slice name -> index in the slice atlas
+    slice_to_idx: HashMap<String, usize>,
+    // Atlas containing every slice, sharing the frame atlas texture
+    slice_atlas: Option<Handle<TextureAtlas>>,
+    // Tag sub-assets, their atlas is set once the frame atlas is built
+    tag_assets: Vec<Handle<AsepriteTagAsset>>,
+    // Atlases of the layer groups, by group name, if the settings ask for them
+    group_atlases: HashMap<String, loader::GroupAtlas>,
+    // Images generated from the frame atlas, in the order of the effects
+    // in the settings
+    effect_textures: Vec<Handle<Image>>,
+    // The normal map layer laid out like the frame atlas, if the file has one
+    normal_texture: Option<Handle<Image>>,
+    // The emissive layer laid out like the frame atlas, if the file has one
+    emissive_texture: Option<Handle<Image>>,
+    // Composited frames of files streamed into the slots of their atlas
+    streamed: Option<stream::StreamedFrames>,
+}
+
+impl Aseprite {
+    /// Create an aseprite from the bytes of a file, e.g. one embedded in the
+    /// binary or downloaded at runtime
+    ///
+    /// Add it to the `Assets<Aseprite>` to have its atlas built, it is then
+    /// animated like files loaded through the asset server. See also the
+    /// `embed` option of the `aseprite!` macro.
+    ///
+    /// ```rust,ignore
+    /// let aseprite = Aseprite::from_bytes(&downloaded, AsepriteLoaderSettings::default())?;
+    /// commands.spawn(AsepriteBundle {
+    ///     aseprite: aseprites.add(aseprite),
+    ///     ..Default::default()
+    /// });
+    /// ```
+    pub fn from_bytes(
+        bytes: &[u8],
+        settings: AsepriteLoaderSettings,
+    ) -> Result<Self, reader::error::AsepriteError> {
+        loader::parse(bytes.to_vec(), &settings)
+    }
+
+    /// Returns `true` once the atlas of the file is built and it can be drawn
+    ///
+    /// The asset server reports the file as loaded as soon as it is parsed,
+    /// which happens before the atlas is built. Without textures (see
+    /// [`AsepritePlugin::without_textures`]) this only waits for the info.
+    pub fn is_ready(&self) -> bool {
+        self.info.is_some()
+    }
+
+    /// The info of the file (tags, slices, frame durations), once it is ready
+    pub fn info(&self) -> Option<&AsepriteInfo> {
+        self.info.as_ref()
+    }
+
+    /// Returns `true` if the file only contains a single frame
+    ///
+    /// Static files can be spawned with an [`AsepriteStaticBundle`]
+    pub fn is_static(&self) -> bool {
+        self.info
+            .as_ref()
+            .map(|info| info.frame_count == 1)
+            .unwrap_or(false)
+    }
+
+    /// The image of a static (single frame) file, once it has been built
+    pub fn image(&self) -> Option<&Handle<Image>> {
+        self.image.as_ref()
+    }
+
+    /// The normal map of the frames, laid out like the frame atlas, once it
+    /// has been built
+    ///
+    /// `None` if the file has no layer named as set in
+    /// [`normal_layer`](AsepriteLoaderSettings::normal_layer). The texture is
+    /// linear, empty pixels are fully transparent.
+    pub fn normal_texture(&self) -> Option<&Handle<Image>> {
+        self.normal_texture.as_ref()
+    }
+
+    /// The emissive parts of the frames, laid out like the frame atlas, once
+    /// it has been built
+    ///
+    /// `None` if the file has no layer named as set in
+    /// [`emissive_layer`](AsepriteLoaderSettings::emissive_layer). Draw it
+    /// over the sprite with an
+    /// [`AsepriteMaterialOverride`](effects::AsepriteMaterialOverride) and a
+    /// bright color to have it picked up by bloom.
+    pub fn emissive_texture(&self) -> Option<&Handle<Image>> {
+        self.emissive_texture.as_ref()
+    }
+
+    /// The atlas containing every slice of the file, once it has been built
+    pub fn slice_atlas(&self) -> Option<&Handle<TextureAtlas>> {
+        self.slice_atlas.as_ref()
+    }
+
+    /// Part of the canvas a frame was packed from, in pixels from its top
+    /// left corner, once the atlas has been built
+    ///
+    /// The whole canvas, unless the file was loaded with
+    /// [`trim_frames`](AsepriteLoaderSettings::trim_frames) set.
+    pub fn frame_rect(&self, frame: usize) -> Option<URect> {
+        self.frame_rects.get(frame).copied()
+    }
+
+    /// Get the index of a slice inside the [`slice_atlas`](Self::slice_atlas)
+    pub fn slice_index(&self, name: &str) -> Option<usize> {
+        self.slice_to_idx.get(name).copied()
+    }
+}
+
+/// A slice of an aseprite, loaded as a labeled sub-asset
+///
+/// ```rust,ignore
+/// let ghost: Handle<AsepriteSliceAsset> = asset_server.load("ui.aseprite#slice/ghost_blue");
+/// // Or directly its image
+/// let ghost: Handle<Image> = asset_server.load("ui.aseprite#slice/ghost_blue/image");
+/// ```
+///
+/// Only added when the plugin loads textures.
+#[derive(Debug, Clone, TypePath, Asset)]
+pub struct AsepriteSliceAsset {
+    /// Name of the slice
+    pub name: String,
+    /// Bounds of the slice on the canvas, in pixels
+    ///
+    /// Parts of the slice outside of the canvas are clamped off, its image
+    /// keeps them transparent.
+    pub rect: Rect,
+    /// Center of a nine-patch slice, relative to the slice
+    pub nine_patch: Option<Rect>,
+    /// Pivot of the slice, relative to the slice
+    pub pivot: Option<Vec2>,
+    /// Text of the slice's user data, e.g. a localization key
+    pub text: Option<String>,
+    /// Image of the slice, in the frame it is valid from
+    pub image: Handle<Image>,
+}
+
+/// Text of the user data of the slice an entity is drawn from
+///
+/// Inserted on nine-patches and UI buttons whose slice has text in its
+/// properties, so placeholders authored as slices can carry e.g. the
+/// localization key of their text.
+#[derive(Debug, Component, Clone, PartialEq, Eq)]
+pub struct SliceMeta(pub String);
+
+/// A tag of an aseprite, loaded as a labeled sub-asset
+///
+/// ```rust,ignore
+/// let walk: Handle<AsepriteTagAsset> = asset_server.load("player.aseprite#tag/walk");
+/// ```
+///
+/// Its atlas only contains the frames of the tag in playback order (ping-pong
+/// tags going forward then back), so crates animating texture atlases can
+/// play it without knowing about [`AsepriteAnimation`]. The atlas is set once
+/// the file is [ready](Aseprite::is_ready).
+#[derive(Debug, Clone, TypePath, Asset)]
+pub struct AsepriteTagAsset {
+    /// Name of the tag
+    pub name: String,
+    /// Frames of the file, in playback order
+    pub frames: Vec<usize>,
+    /// How long each frame is shown
+    pub durations: Vec<Duration>,
+    /// Atlas with one texture per entry of `frames`
+    pub atlas: Option<Handle<TextureAtlas>>,
+}
+
+/// Sent once an aseprite is [ready](Aseprite::is_ready)
+///
+/// Sent again whenever a modified file has been rebuilt. Entities depending
+/// on the atlas can be spawned from it instead of polling the assets:
+///
+/// ```rust,ignore
+/// fn spawn_player(mut ready: EventReader<AsepriteReady>, sprites: Res<Sprites>, ...) {
+///     for event in ready.read() {
+///         if event.id == sprites.player.id() {
+///             commands.spawn(AsepriteBundle { .. });
+///         }
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Event)]
+pub struct AsepriteReady {
+    pub id: AssetId<Aseprite>,
 }
 
 /// A bundle defining a drawn aseprite
+///
+/// The sprite components are inserted once the atlas is built, the
+/// visibility ones are part of the bundle so it can be parented right away.
 #[derive(Debug, Bundle, Default)]
 pub struct AsepriteBundle {
     pub transform: Transform,
     pub global_transform: GlobalTransform,
+    pub visibility: Visibility,
+    pub inherited_visibility: InheritedVisibility,
+    pub view_visibility: ViewVisibility,
     pub animation: AsepriteAnimation,
     pub aseprite: Handle<Aseprite>,
 }
+
+/// A bundle defining a static aseprite, drawn as a plain sprite
+///
+/// Meant for single frame files such as icons and props, it skips the
+/// animation state entirely.
+#[derive(Debug, Bundle, Default)]
+pub struct AsepriteStaticBundle {
+    pub transform: Transform,
+    pub global_transform: GlobalTransform,
+    pub visibility: Visibility,
+    pub inherited_visibility: InheritedVisibility,
+    pub view_visibility: ViewVisibility,
+    pub aseprite: Handle<Aseprite>,
+}
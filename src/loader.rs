@@ -1,39 +1,352 @@
-use crate::{anim::AsepriteAnimation, Aseprite, error};
+use crate::{
+    anim::AsepriteAnimation,
+    diagnostics::{AsepriteDiagnostics, AsepriteLoadTimings},
+    effects,
+    error::{AsepriteLoadStage, AsepriteLoaderError},
+    flipbook,
+    group::AsepriteGroup,
+    stream,
+    text::AsepriteText,
+    Aseprite, AsepriteInfo, AsepriteReady, AsepriteSliceAsset, AsepriteTagAsset,
+};
 use bevy::{
-    asset::{AssetLoader, AsyncReadExt},
+    asset::{io::AssetSourceId, AssetLoader, AsyncReadExt, LoadContext},
+    diagnostic::Diagnostics,
+    ecs::{query::Has, system::EntityCommands},
     prelude::*,
-    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+    render::{
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        texture::ImageSampler,
+    },
+    sprite::TextureAtlasBuilderError,
+    utils::{HashMap, Instant},
 };
 use bevy_aseprite_reader as reader;
+use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    hash::{Hash, Hasher},
+    path::PathBuf,
+    time::Duration,
+};
+
+/// Settings used when loading an aseprite file
+///
+/// ```rust,ignore
+/// asset_server.load_with_settings("player.ase", |settings: &mut AsepriteLoaderSettings| {
+///     settings.sampler = ImageSampler::nearest();
+/// });
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AsepriteLoaderSettings {
+    /// Sampler of the generated textures
+    ///
+    /// [`ImageSampler::Default`] uses the sampler set in the `ImagePlugin`
+    pub sampler: ImageSampler,
+    /// Generate mipmaps for the atlas texture
+    ///
+    /// Useful for sprites which are drawn scaled down, the sampler needs a
+    /// `mipmap_filter` for them to be used. Consider setting
+    /// [`extrude`](Self::extrude) too, to avoid frames bleeding into each other.
+    pub generate_mipmaps: bool,
+    /// Amount of pixels the edges of each frame are repeated by in the atlas
+    pub extrude: u32,
+    /// How the empty pixels of each frame are filled
+    pub background: BackgroundMode,
+    /// What is drawn for layers without a cel in a frame
+    pub missing_cel: MissingCelPolicy,
+    /// How strictly the file is parsed, useful with files from other
+    /// exporters
+    pub parse_mode: ParseMode,
+    /// How the frames are laid out in the atlas
+    pub packing: AtlasPacking,
+    /// Store premultiplied alpha in the textures instead of straight alpha
+    ///
+    /// Only useful with a material or blend state expecting premultiplied
+    /// colors (e.g. additive particles), the default sprite pipeline expects
+    /// straight alpha.
+    pub premultiply_alpha: bool,
+    /// Write the composited frames and the atlas of the file to this folder
+    ///
+    /// The images are named after the file, `<name>_frame_<index>.png` and
+    /// `<name>_atlas.png`, so they can be compared between runs. Needs the
+    /// `debug` feature.
+    pub dump_frames_to: Option<PathBuf>,
+    /// How the tags are keyed in [`AsepriteInfo::tags`]
+    ///
+    /// Also used for the labels of the tag sub-assets, `tag/<key>`.
+    pub tag_keys: TagKeys,
+    /// Build an atlas of the frames of every layer group
+    ///
+    /// Needed to draw groups on their own with an
+    /// [`AsepriteGroupBundle`](crate::group::AsepriteGroupBundle). Each atlas
+    /// takes as much memory as the frame atlas.
+    pub group_atlases: bool,
+    /// Trim the transparent edges of each frame before packing it
+    ///
+    /// Saves atlas space when most frames only cover part of the canvas.
+    /// Animated sprites are still drawn with each frame at its place on the
+    /// canvas, through the size and anchor of the sprite (see
+    /// [`Aseprite::frame_rect`](crate::Aseprite::frame_rect)). Slices are cut
+    /// from the trimmed frames, the tag sub-assets and layer group atlases
+    /// don't know where their frames were on the canvas.
+    pub trim_frames: bool,
+    /// Images to generate from the frame atlas, laid out like it
+    ///
+    /// See [`Aseprite::effect_texture`](crate::Aseprite::effect_texture).
+    /// Effects are drawn inside the rect of each frame, leave some empty
+    /// pixels around the sprites for outlines and shadows.
+    pub effects: Vec<AtlasEffect>,
+    /// Name of the layer holding the normal map of the sprite, `normal` if
+    /// `None`
+    ///
+    /// The layer is left out of the frame atlas and drawn on its own into an
+    /// image laid out like it, see
+    /// [`Aseprite::normal_texture`](crate::Aseprite::normal_texture). It can
+    /// be hidden in aseprite.
+    pub normal_layer: Option<String>,
+    /// Name of the layer holding the emissive parts of the sprite, `emissive`
+    /// if `None`
+    ///
+    /// Like the [`normal_layer`](Self::normal_layer), it is left out of the
+    /// frame atlas and drawn into
+    /// [`Aseprite::emissive_texture`](crate::Aseprite::emissive_texture).
+    pub emissive_layer: Option<String>,
+    /// Keep the composited frames in memory and only upload this many of
+    /// them at once, instead of packing every frame in an atlas
+    ///
+    /// For very long animations on targets short of video memory. The atlas
+    /// of the file has this many slots, the frames shown by its sprites (and
+    /// the ones shown next) are copied into them as they are needed, so it
+    /// needs at least as many slots as frames are shown at once. Slices,
+    /// layer groups, effects, layer textures and trimming aren't available
+    /// for streamed files.
+    pub stream_frames: Option<usize>,
+    /// Pixels of this color are made transparent while compositing
+    ///
+    /// For files converted from formats using a color key (e.g. magenta)
+    /// instead of alpha. The alpha of the color is ignored.
+    pub transparent_color: Option<Color>,
+}
+
+impl AsepriteLoaderSettings {
+    pub(crate) fn composite_options(&self) -> reader::AsepriteCompositeOptions {
+        let (skip_background_layer, background_color) = match self.background {
+            BackgroundMode::FromBackgroundLayer => (false, None),
+            BackgroundMode::Transparent => (true, None),
+            BackgroundMode::Color(color) => {
+                let [red, green, blue, alpha] = color.as_rgba_u8();
+                (
+                    true,
+                    Some(reader::raw::AsepriteColor {
+                        red,
+                        green,
+                        blue,
+                        alpha,
+                    }),
+                )
+            }
+        };
+        reader::AsepriteCompositeOptions {
+            skip_background_layer,
+            background_color,
+            layer_group: None,
+            missing_cel: match self.missing_cel {
+                MissingCelPolicy::Transparent => reader::AsepriteMissingCel::Transparent,
+                MissingCelPolicy::PreviousFrameHold => {
+                    reader::AsepriteMissingCel::PreviousFrameHold
+                }
+                MissingCelPolicy::Error => reader::AsepriteMissingCel::Error,
+            },
+            premultiply_alpha: self.premultiply_alpha,
+            layer: None,
+            skip_layers: self.texture_layers().map(str::to_string).into(),
+            transparent_color: self.transparent_color.map(|color| {
+                let [red, green, blue, alpha] = color.as_rgba_u8();
+                reader::raw::AsepriteColor {
+                    red,
+                    green,
+                    blue,
+                    alpha,
+                }
+            }),
+        }
+    }
 
+    // Name of the layer drawn into the normal map
+    pub(crate) fn normal_layer(&self) -> &str {
+        self.normal_layer.as_deref().unwrap_or("normal")
+    }
+
+    // Name of the layer drawn into the emissive texture
+    pub(crate) fn emissive_layer(&self) -> &str {
+        self.emissive_layer.as_deref().unwrap_or("emissive")
+    }
+
+    // Layers drawn into textures of their own instead of the frame atlas
+    pub(crate) fn texture_layers(&self) -> [&str; 2] {
+        [self.normal_layer(), self.emissive_layer()]
+    }
+
+    // The info of a parsed file, with its tags keyed as set
+    pub(crate) fn info(&self, data: reader::Aseprite) -> AsepriteInfo {
+        let mut info: AsepriteInfo = data.into();
+        if self.tag_keys != TagKeys::Name {
+            info.rekey_tags(|index, tag| self.tag_keys.key(index, &tag.name));
+        }
+        info
+    }
+}
+
+// Atlas of the frames of a layer group, only containing its layers
+#[derive(Debug, Clone)]
+pub(crate) struct GroupAtlas {
+    pub(crate) atlas: Handle<TextureAtlas>,
+    pub(crate) frame_to_idx: Vec<usize>,
+}
+
+/// How the tags of a file are keyed in [`AsepriteInfo::tags`]
+///
+/// Aseprite allows several tags to share a name, e.g. when merging
+/// animations from other files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TagKeys {
+    /// Key the tags by their name, only the last of tags sharing a name is
+    /// kept
+    ///
+    /// The others are still listed in [`AsepriteInfo::tag_list`].
+    #[default]
+    Name,
+    /// Prefix the name with the index of the tag in the timeline, e.g.
+    /// `"2:walk"` for the third tag
+    ///
+    /// Every tag is kept. Play them by name with [`TagMatch::FirstMatch`](crate::anim::TagMatch::FirstMatch).
+    IndexPrefixed,
+}
+
+impl TagKeys {
+    /// Get the key of a tag from its index in the timeline and its name
+    pub fn key(&self, index: usize, name: &str) -> String {
+        match self {
+            TagKeys::Name => name.to_string(),
+            TagKeys::IndexPrefixed => format!("{}:{}", index, name),
+        }
+    }
+}
+
+/// How the frames of a file are laid out in its atlas
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AtlasPacking {
+    /// Pack the frames tightly, the layout can differ between runs and platforms
+    #[default]
+    Compact,
+    /// Place the frames on a grid row by row, in frame order
+    ///
+    /// The same file always produces the same layout and frame indices,
+    /// e.g. for golden-image tests or deterministic replays.
+    Grid,
+}
+
+/// How the empty pixels of composited frames are filled
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum BackgroundMode {
+    /// Keep the background layer as authored, if there is one
+    #[default]
+    FromBackgroundLayer,
+    /// Drop the background layer, leaving empty pixels transparent
+    Transparent,
+    /// Drop the background layer and fill empty pixels with a color
+    Color(Color),
+}
+
+/// What is drawn for a layer without a cel in a frame
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MissingCelPolicy {
+    /// Nothing, like in aseprite
+    #[default]
+    Transparent,
+    /// The last cel of the layer in an earlier frame, like some exporters do
+    PreviousFrameHold,
+    /// Fail to build the atlas when a visible layer has no cel in a frame
+    Error,
+}
+
+/// An image generated from the frame atlas, e.g. to draw a sprite with an
+/// [`AsepriteMaterialOverride`](crate::effects::AsepriteMaterialOverride)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum AtlasEffect {
+    /// Every visible pixel in white, keeping its alpha
+    Silhouette,
+    /// The frames surrounded by a line of `width` pixels
+    Outline { width: u32, color: Color },
+    /// The frames casting a shadow, offset by some pixels (y pointing down)
+    DropShadow { offset: (i32, i32), color: Color },
+}
+
+/// How strictly files are parsed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ParseMode {
+    /// Only parse the content of the file
+    #[default]
+    Loose,
+    /// Check that the sizes stored in the file match its data first
+    ///
+    /// Reports the byte offset of the first mismatch instead of a parsing
+    /// error.
+    Strict,
+    /// Tolerate the quirks of files from LibreSprite and old Aseprite
+    /// versions, skipping the chunks which can't be parsed
+    Compatible,
+}
 
 #[derive(Debug, Default)]
-pub struct AsepriteLoader;
+pub struct AsepriteLoader {
+    // Only files from these sources are loaded, if set
+    pub(crate) sources: Option<Vec<AssetSourceId<'static>>>,
+    // Slice sub-assets come with an image, only added when textures are
+    pub(crate) load_textures: bool,
+    // Keep the bytes of the file to parse them again on reload
+    pub(crate) keep_source: bool,
+}
 
 impl AssetLoader for AsepriteLoader {
     type Asset = Aseprite;
-    type Settings = ();
-    type Error = error::AsepriteLoaderError;
+    type Settings = AsepriteLoaderSettings;
+    type Error = AsepriteLoaderError;
 
     fn load<'a>(
         &'a self,
         reader: &'a mut bevy::asset::io::Reader,
-        _settings: &'a Self::Settings,
+        settings: &'a Self::Settings,
         load_context: &'a mut bevy::asset::LoadContext,
     ) -> bevy::utils::BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
         Box::pin(async move {
             debug!("Loading aseprite at {:?}", load_context.path());
 
+            let path = load_context.path().to_path_buf();
+            check_source(&self.sources, load_context)?;
+
             let mut buffer = vec![];
-            let _ = reader.read_to_end(&mut buffer).await?;
-            let data = Some(reader::Aseprite::from_bytes(buffer)?);
-
-            Ok(Aseprite {
-                data,
-                info: None,
-                frame_to_idx: vec![],
-                atlas: None,
-            })
+            if let Err(source) = reader.read_to_end(&mut buffer).await {
+                return Err(AsepriteLoaderError::Io { path, source });
+            }
+            let source = self.keep_source.then(|| buffer.clone());
+            let mut aseprite = match parse(buffer, settings) {
+                Ok(aseprite) => aseprite,
+                Err(source) => {
+                    return Err(AsepriteLoaderError::Aseprite {
+                        path,
+                        stage: AsepriteLoadStage::Parse,
+                        source,
+                    })
+                }
+            };
+            aseprite.path = Some(path);
+            aseprite.source = source;
+            if self.load_textures {
+                add_sub_assets(load_context, &mut aseprite, settings);
+            }
+            Ok(aseprite)
         })
     }
 
@@ -42,100 +355,1003 @@ impl AssetLoader for AsepriteLoader {
     }
 }
 
+// Files are only loaded from the sources the loader is registered for
+pub(crate) fn check_source(
+    sources: &Option<Vec<AssetSourceId<'static>>>,
+    load_context: &LoadContext,
+) -> Result<(), AsepriteLoaderError> {
+    let source = load_context.asset_path().source();
+    match sources {
+        Some(sources) if !sources.contains(source) => Err(AsepriteLoaderError::UnsupportedSource {
+            path: load_context.path().to_path_buf(),
+            asset_source: format!("{:?}", source),
+        }),
+        _ => Ok(()),
+    }
+}
+
+// The slices and tags of a parsed file as labeled sub-assets
+pub(crate) fn add_sub_assets(
+    load_context: &mut LoadContext,
+    aseprite: &mut Aseprite,
+    settings: &AsepriteLoaderSettings,
+) {
+    if let Some(data) = &aseprite.data {
+        add_slice_assets(load_context, data, settings);
+        aseprite.tag_assets = add_tag_assets(load_context, data, settings);
+    }
+}
+
+// Slices are also available as labeled sub-assets, `ui.aseprite#slice/<name>`
+fn add_slice_assets(
+    load_context: &mut LoadContext,
+    data: &reader::Aseprite,
+    settings: &AsepriteLoaderSettings,
+) {
+    let slices = data.slices();
+    // Hidden slices have no size, and nothing to draw
+    let visible = || {
+        slices
+            .get_all()
+            .filter(|slice| slice.width > 0 && slice.height > 0)
+    };
+    let images = match slices.get_images(visible()) {
+        Ok(images) => images,
+        Err(err) => {
+            error!("{:?}", err);
+            return;
+        }
+    };
+
+    let canvas = data.canvas_rect();
+    let canvas = Vec2::new(canvas.width as f32, canvas.height as f32);
+    for (slice, slice_image) in visible().zip(images) {
+        let (width, height) = (slice_image.image.width(), slice_image.image.height());
+        let image = load_context.add_labeled_asset(
+            format!("slice/{}/image", slice.name),
+            frame_image(slice_image.image.into_raw(), width, height, settings),
+        );
+        load_context.add_labeled_asset(
+            format!("slice/{}", slice.name),
+            AsepriteSliceAsset {
+                name: slice.name.clone(),
+                rect: slice_rect(slice, canvas),
+                nine_patch: slice.nine_patch_info.as_ref().map(|info| {
+                    let min = Vec2::new(info.x_center as f32, info.y_center as f32);
+                    Rect::from_corners(min, min + Vec2::new(info.width as f32, info.height as f32))
+                }),
+                pivot: slice
+                    .pivot
+                    .map(|pivot| Vec2::new(pivot.x_pivot as f32, pivot.y_pivot as f32)),
+                text: slice.text().map(str::to_string),
+                image,
+            },
+        );
+    }
+}
+
+// Bounds of a slice, clamped to the canvas
+fn slice_rect(slice: &reader::AsepriteSlice, canvas: Vec2) -> Rect {
+    let min = Vec2::new(slice.position_x as f32, slice.position_y as f32);
+    let max = min + Vec2::new(slice.width as f32, slice.height as f32);
+    Rect::from_corners(min.clamp(Vec2::ZERO, canvas), max.clamp(Vec2::ZERO, canvas))
+}
+
+// Tags are also available as labeled sub-assets, `player.aseprite#tag/<name>`
+fn add_tag_assets(
+    load_context: &mut LoadContext,
+    data: &reader::Aseprite,
+    settings: &AsepriteLoaderSettings,
+) -> Vec<Handle<AsepriteTagAsset>> {
+    let tags = data.tags().in_order();
+    let labels: Vec<_> = tags
+        .iter()
+        .enumerate()
+        .map(|(index, tag)| format!("tag/{}", settings.tag_keys.key(index, &tag.name)))
+        .collect();
+    labels
+        .iter()
+        .zip(tags)
+        .enumerate()
+        // Tags sharing a key are only added once, the last one like in the info
+        .filter(|(index, (label, _))| !labels[index + 1..].contains(label))
+        .map(|(_, (label, tag))| {
+            let frames = flipbook::playback_frames(tag);
+            let durations = frames
+                .iter()
+                .map(|&frame| data.frame_infos()[frame].duration)
+                .collect();
+            load_context.add_labeled_asset(
+                label.clone(),
+                AsepriteTagAsset {
+                    name: tag.name.clone(),
+                    frames,
+                    durations,
+                    atlas: None,
+                },
+            )
+        })
+        .collect()
+}
+
+// Tag sub-assets get an atlas of their own frames, sharing the texture of the
+// frame atlas
+fn build_tag_atlases(
+    ase: &Aseprite,
+    atlases: &mut Assets<TextureAtlas>,
+    tag_assets: &mut Assets<AsepriteTagAsset>,
+) {
+    let (texture, size, rects) = match ase.atlas.as_ref().and_then(|atlas| atlases.get(atlas)) {
+        Some(atlas) => (atlas.texture.clone(), atlas.size, atlas.textures.clone()),
+        None => return,
+    };
+    for handle in &ase.tag_assets {
+        let tag = match tag_assets.get_mut(handle) {
+            Some(tag) => tag,
+            None => continue,
+        };
+        let mut tag_atlas = TextureAtlas::new_empty(texture.clone(), size);
+        for &frame in &tag.frames {
+            match ase.frame_to_idx.get(frame) {
+                Some(&idx) => {
+                    tag_atlas.add_texture(rects[idx]);
+                }
+                None => error!("Tag {} references an invalid frame", tag.name),
+            }
+        }
+        tag.atlas = Some(atlases.add(tag_atlas));
+    }
+}
+
+// Parses a file, its atlas is built later on by `process_load`
+pub(crate) fn parse(
+    buffer: Vec<u8>,
+    settings: &AsepriteLoaderSettings,
+) -> Result<Aseprite, reader::error::AsepriteError> {
+    let content_hash = content_hash(&buffer, settings);
+    let start = Instant::now();
+    let data = parse_data(buffer, settings)?;
+    Ok(new_aseprite(data, content_hash, start.elapsed(), settings))
+}
+
+// Parses the bytes of a file in the mode set
+pub(crate) fn parse_data(
+    buffer: Vec<u8>,
+    settings: &AsepriteLoaderSettings,
+) -> Result<reader::Aseprite, reader::error::AsepriteError> {
+    let mode = match settings.parse_mode {
+        ParseMode::Loose => reader::raw::AsepriteParseMode::Loose,
+        ParseMode::Strict => reader::raw::AsepriteParseMode::Strict,
+        ParseMode::Compatible => reader::raw::AsepriteParseMode::Compatible,
+    };
+    reader::Aseprite::from_bytes_with(buffer, mode)
+}
+
+// An aseprite waiting for its atlas to be built from the parsed data
+pub(crate) fn new_aseprite(
+    data: reader::Aseprite,
+    content_hash: u64,
+    parse_time: Duration,
+    settings: &AsepriteLoaderSettings,
+) -> Aseprite {
+    Aseprite {
+        path: None,
+        data: Some(data),
+        source: None,
+        settings: settings.clone(),
+        content_hash,
+        parse_time,
+        info: None,
+        frame_to_idx: vec![],
+        frame_rects: vec![],
+        atlas: None,
+        image: None,
+        slice_to_idx: HashMap::new(),
+        slice_atlas: None,
+        tag_assets: vec![],
+        group_atlases: HashMap::new(),
+        effect_textures: vec![],
+        normal_texture: None,
+        emissive_texture: None,
+        streamed: None,
+    }
+}
+
+// Path shown in errors, files created from bytes don't have one
+fn error_path(ase: &Aseprite) -> PathBuf {
+    ase.path.clone().unwrap_or_else(|| "<bytes>".into())
+}
+
+// Files with the same content and settings end up with the same atlas, so
+// the hash covers both
+pub(crate) fn content_hash(bytes: &[u8], settings: &AsepriteLoaderSettings) -> u64 {
+    let mut hasher = bevy::utils::AHasher::default();
+    bytes.hash(&mut hasher);
+    // The sampler isn't `Hash`, its debug output describes it fully
+    format!("{:?}", settings).hash(&mut hasher);
+    hasher.finish()
+}
+
+// Textures built for a file, reused by other files with the same content hash
+//
+// Cloned from the file which built them, the cache only keeps the id of that
+// file so the textures are still freed once no file uses them
+#[derive(Debug, Clone)]
+struct SharedAtlas {
+    atlas: Handle<TextureAtlas>,
+    slice_atlas: Option<Handle<TextureAtlas>>,
+    image: Option<Handle<Image>>,
+    frame_to_idx: Vec<usize>,
+    frame_rects: Vec<URect>,
+    slice_to_idx: HashMap<String, usize>,
+    group_atlases: HashMap<String, GroupAtlas>,
+    effect_textures: Vec<Handle<Image>>,
+    normal_texture: Option<Handle<Image>>,
+    emissive_texture: Option<Handle<Image>>,
+}
+
+impl SharedAtlas {
+    fn of(ase: &Aseprite) -> Option<Self> {
+        Some(Self {
+            atlas: ase.atlas.clone()?,
+            slice_atlas: ase.slice_atlas.clone(),
+            image: ase.image.clone(),
+            frame_to_idx: ase.frame_to_idx.clone(),
+            frame_rects: ase.frame_rects.clone(),
+            slice_to_idx: ase.slice_to_idx.clone(),
+            group_atlases: ase.group_atlases.clone(),
+            effect_textures: ase.effect_textures.clone(),
+            normal_texture: ase.normal_texture.clone(),
+            emissive_texture: ase.emissive_texture.clone(),
+        })
+    }
+
+    fn share_with(self, ase: &mut Aseprite) {
+        ase.atlas = Some(self.atlas);
+        ase.slice_atlas = self.slice_atlas;
+        ase.image = self.image;
+        ase.frame_to_idx = self.frame_to_idx;
+        ase.frame_rects = self.frame_rects;
+        ase.slice_to_idx = self.slice_to_idx;
+        ase.group_atlases = self.group_atlases;
+        ase.effect_textures = self.effect_textures;
+        ase.normal_texture = self.normal_texture;
+        ase.emissive_texture = self.emissive_texture;
+    }
+}
+
+// What a file was last built from, so a reload can only update the frames
+// which changed
+#[derive(Debug)]
+pub(crate) struct PreviousBuild {
+    data: PreviousData,
+    // Debug output of the settings, the sampler isn't `PartialEq`
+    settings: String,
+    atlas: Handle<TextureAtlas>,
+    image: Option<Handle<Image>>,
+    frame_to_idx: Vec<usize>,
+    frame_rects: Vec<URect>,
+}
+
+// The file a build was made from, either decoded or as the bytes it was read
+// from, parsed again when needed
+#[derive(Debug)]
+enum PreviousData {
+    Decoded(Box<reader::Aseprite>),
+    Compressed(Vec<u8>),
+}
+
+impl PreviousData {
+    fn decode(&self, settings: &AsepriteLoaderSettings) -> Option<Cow<'_, reader::Aseprite>> {
+        match self {
+            PreviousData::Decoded(data) => Some(Cow::Borrowed(data)),
+            PreviousData::Compressed(bytes) => match parse_data(bytes.clone(), settings) {
+                Ok(data) => Some(Cow::Owned(data)),
+                Err(err) => {
+                    warn!(
+                        "Could not parse the previous version of an aseprite: {}",
+                        err
+                    );
+                    None
+                }
+            },
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct AtlasCache {
+    // The file holding the textures of each content hash
+    shared: HashMap<u64, AssetId<Aseprite>>,
+    previous: HashMap<AssetId<Aseprite>, PreviousBuild>,
+}
+
+/// How modified files are rebuilt
+#[derive(Debug, Clone, Resource)]
+pub(crate) struct ReloadSettings {
+    // Files modified within this duration of each other only get rebuilt once
+    pub(crate) debounce: Duration,
+    // Only update the frames which changed when possible
+    pub(crate) incremental: bool,
+}
+
+// Builds the textures of the aseprites added or modified since the last run
+//
+// The pipeline of a file is:
+// - `AsepriteLoader` parses it, the asset holds the parsed data
+// - `Added` builds its atlas on the next run, `Modified` after the debounce
+//   (updating only the changed frames with incremental reloads)
+// - building takes the data out of the asset and keeps its info instead,
+//   then `AsepriteReady` is sent
+// - `Removed` forgets any pending build, the textures are freed along with
+//   the handles the asset held
 pub(crate) fn process_load(
+    time: Res<Time>,
+    reload: Res<ReloadSettings>,
+    mut pending: Local<HashMap<AssetId<Aseprite>, Duration>>,
+    mut cache: Local<AtlasCache>,
     mut asset_events: EventReader<AssetEvent<Aseprite>>,
     mut aseprites: ResMut<Assets<Aseprite>>,
     mut images: ResMut<Assets<Image>>,
     mut atlases: ResMut<Assets<TextureAtlas>>,
+    mut tag_assets: ResMut<Assets<AsepriteTagAsset>>,
+    mut diagnostics: Diagnostics,
+    mut load_diagnostics: ResMut<AsepriteDiagnostics>,
+    mut ready_events: EventWriter<AsepriteReady>,
 ) {
-    asset_events.read().for_each(|event| {
-        if let AssetEvent::Added { id } | AssetEvent::Modified { id } = event {
-            // Get the created/modified aseprite
-            match aseprites.get(*id) {
-                Some(aseprite) => match aseprite.atlas.is_some() {
-                    true => return,
-                    false => {}
-                },
-                None => {
-                    error!("Aseprite handle doesn't hold anything?");
-                    return;
-                }
+    // Keep track of when each asset should be (re)built
+    let now = time.elapsed();
+    for event in asset_events.read() {
+        match event {
+            AssetEvent::Added { id } => {
+                pending.insert(*id, now);
             }
+            AssetEvent::Modified { id } => {
+                pending.insert(*id, now + reload.debounce);
+            }
+            AssetEvent::Removed { id } => {
+                pending.remove(id);
+                cache.previous.remove(id);
+                cache.shared.retain(|_, owner| owner != id);
+                load_diagnostics.timings.remove(id);
+            }
+            _ => {}
+        }
+    }
+
+    pending.retain(|&id, &mut build_at| {
+        if build_at > now {
+            return true;
+        }
+        // Keep the data around to compare it with the next version
+        let data = match reload.incremental {
+            true => aseprites.get(id).and_then(|ase| match &ase.source {
+                Some(source) => Some(PreviousData::Compressed(source.clone())),
+                None => ase.data.clone().map(Box::new).map(PreviousData::Decoded),
+            }),
+            false => None,
+        };
+
+        let updated = match cache.previous.get(&id) {
+            Some(previous) if reload.incremental => {
+                update_atlas(id, previous, &mut aseprites, &mut images, &mut atlases)
+            }
+            _ => false,
+        };
+        let built = if updated {
+            debug!("Updated the changed frames of an aseprite");
+            // The atlas doesn't match its previous content anymore
+            cache.shared.retain(|_, owner| *owner != id);
+            true
+        } else {
+            build_atlas(
+                id,
+                &mut cache.shared,
+                &mut aseprites,
+                &mut images,
+                &mut atlases,
+                &mut diagnostics,
+                &mut load_diagnostics,
+            )
+        };
+        // Building marks the asset as modified, there is nothing left to
+        // build when that event comes back
+        if !built {
+            return false;
+        }
+
+        let ase = match aseprites.get(id) {
+            Some(ase) if ase.is_ready() => ase,
+            _ => return false,
+        };
+        ready_events.send(AsepriteReady { id });
+        build_tag_atlases(ase, &mut atlases, &mut tag_assets);
+        if let (Some(data), Some(atlas)) = (data, &ase.atlas) {
+            cache.previous.insert(
+                id,
+                PreviousBuild {
+                    data,
+                    settings: format!("{:?}", ase.settings),
+                    atlas: atlas.clone(),
+                    image: ase.image.clone(),
+                    frame_to_idx: ase.frame_to_idx.clone(),
+                    frame_rects: ase.frame_rects.clone(),
+                },
+            );
+        }
+        false
+    });
+}
 
+// Used instead of `process_load` when textures are disabled, only keeps the info
+pub(crate) fn process_info(
+    mut asset_events: EventReader<AssetEvent<Aseprite>>,
+    mut aseprites: ResMut<Assets<Aseprite>>,
+    mut ready_events: EventWriter<AsepriteReady>,
+) {
+    for event in asset_events.read() {
+        if let AssetEvent::Added { id } | AssetEvent::Modified { id } = event {
             let ase = match aseprites.get_mut(*id) {
                 Some(ase) => ase,
                 None => {
                     error!("Aseprite handle doesn't hold anything?");
-                    return;
+                    continue;
                 }
             };
-            let data = match ase.data.take() {
-                Some(data) => data,
-                None => {
-                    error!("Ase data is empty");
-                    return;
-                }
+            if let Some(data) = ase.data.take() {
+                ase.info = Some(ase.settings.info(data));
+                ready_events.send(AsepriteReady { id: *id });
+            }
+        }
+    }
+}
+
+// Returns false if the atlas was already built or couldn't be
+fn build_atlas(
+    id: AssetId<Aseprite>,
+    shared: &mut HashMap<u64, AssetId<Aseprite>>,
+    aseprites: &mut Assets<Aseprite>,
+    images: &mut Assets<Image>,
+    atlases: &mut Assets<TextureAtlas>,
+    diagnostics: &mut Diagnostics,
+    load_diagnostics: &mut AsepriteDiagnostics,
+) -> bool {
+    // Get the created/modified aseprite
+    let existing = match aseprites.get(id) {
+        Some(aseprite) => match aseprite.atlas.is_some() {
+            true => return false,
+            // Identical files share their textures instead of building them again
+            false => shared
+                .get(&aseprite.content_hash)
+                .and_then(|owner| aseprites.get(*owner))
+                .filter(|owner| owner.content_hash == aseprite.content_hash)
+                .and_then(SharedAtlas::of),
+        },
+        None => {
+            error!("Aseprite handle doesn't hold anything?");
+            return false;
+        }
+    };
+
+    let ase = match aseprites.get_mut(id) {
+        Some(ase) => ase,
+        None => {
+            error!("Aseprite handle doesn't hold anything?");
+            return false;
+        }
+    };
+    let data = match ase.data.take() {
+        Some(data) => data,
+        None => {
+            error!("Ase data is empty");
+            return false;
+        }
+    };
+    ase.source = None;
+
+    if let Some(existing) = existing {
+        debug!("Reusing the atlas of an identical aseprite");
+        existing.share_with(ase);
+        ase.info = Some(ase.settings.info(data));
+        return true;
+    }
+
+    // Build out texture atlas
+    let start = Instant::now();
+    let ase_images = match data
+        .frames()
+        .all()
+        .get_rgba_buffers_with(&ase.settings.composite_options())
+    {
+        Ok(ase_images) => ase_images,
+        Err(source) => {
+            let err = AsepriteLoaderError::Aseprite {
+                path: error_path(ase),
+                stage: AsepriteLoadStage::Composite,
+                source,
+            };
+            error!("{}", err);
+            return false;
+        }
+    };
+    let composite_time = start.elapsed();
+    #[cfg(feature = "debug")]
+    if let Some(dir) = &ase.settings.dump_frames_to {
+        crate::debug::dump_frames(dir, ase, &ase_images);
+    }
+    #[cfg(not(feature = "debug"))]
+    if ase.settings.dump_frames_to.is_some() {
+        warn!("Dumping frames needs the debug feature of bevy_aseprite");
+    }
+    let start = Instant::now();
+
+    let is_static = ase_images.len() == 1;
+    // Static files with their frame as is draw their image from the atlas too
+    let image_atlas = is_static
+        && ase.settings.extrude == 0
+        && !ase.settings.trim_frames
+        && !ase.settings.generate_mipmaps;
+    // Streamed files only get their slots, filled as their frames are shown
+    if let (Some(slots), false) = (ase.settings.stream_frames, is_static) {
+        let size = ase_images
+            .first()
+            .map_or(UVec2::ONE, |&(_, width, height)| UVec2::new(width, height));
+        let atlas = stream::ring_atlas(size, slots.max(1), images, &ase.settings);
+        ase.streamed = Some(stream::StreamedFrames {
+            frames: ase_images.into_iter().map(|(data, _, _)| data).collect(),
+            size,
+        });
+        load_diagnostics.record(
+            diagnostics,
+            id,
+            AsepriteLoadTimings {
+                parse: ase.parse_time,
+                composite: composite_time,
+                atlas_build: start.elapsed(),
+                atlas_size: atlas.size.as_uvec2(),
+            },
+        );
+        ase.info = Some(ase.settings.info(data));
+        ase.atlas = Some(atlases.add(atlas));
+        return true;
+    }
+    let mut textures = vec![];
+    let mut frame_rects = vec![];
+    for (mut data, width, height) in ase_images {
+        if image_atlas {
+            ase.image = Some(images.add(frame_image(data, width, height, &ase.settings)));
+            frame_rects.push(URect::new(0, 0, width, height));
+            continue;
+        }
+        if is_static {
+            ase.image = Some(images.add(frame_image(data.clone(), width, height, &ase.settings)));
+        }
+        let rect = match ase.settings.trim_frames {
+            true => content_rect(&data, width, height),
+            false => URect::new(0, 0, width, height),
+        };
+        if rect.size() != UVec2::new(width, height) {
+            data = crop(&data, width, rect);
+        }
+        textures.push(frame_texture(
+            data,
+            rect.width(),
+            rect.height(),
+            &ase.settings,
+        ));
+        frame_rects.push(rect);
+    }
+    ase.frame_rects = frame_rects;
+
+    let packed = match (&ase.image, image_atlas) {
+        (Some(image), true) => Ok(single_frame_atlas(image.clone(), ase.frame_rects[0].size())),
+        _ => pack_frames(textures, images, &ase.settings),
+    };
+    let (atlas, frame_to_idx) = match packed {
+        Ok(packed) => packed,
+        Err(err) => {
+            let err = AsepriteLoaderError::Atlas {
+                path: error_path(ase),
+                message: format!("{:?}", err),
             };
+            error!("{}", err);
+            return false;
+        }
+    };
+    ase.frame_to_idx = frame_to_idx;
+    #[cfg(feature = "debug")]
+    if let (Some(dir), Some(texture)) = (&ase.settings.dump_frames_to, images.get(&atlas.texture)) {
+        crate::debug::dump_atlas(dir, ase, texture);
+    }
+    if ase.settings.group_atlases {
+        ase.group_atlases = build_group_atlases(ase, &data, images, atlases);
+    }
+    ase.effect_textures = build_effect_textures(&atlas, images, &ase.settings);
+    ase.normal_texture =
+        build_layer_texture(ase, &data, &atlas, ase.settings.normal_layer(), images).map(
+            |mut image| {
+                // Normals aren't colors
+                image.texture_descriptor.format = TextureFormat::Rgba8Unorm;
+                images.add(image)
+            },
+        );
+    ase.emissive_texture =
+        build_layer_texture(ase, &data, &atlas, ase.settings.emissive_layer(), images)
+            .map(|image| images.add(image));
+
+    let (slice_atlas, slice_to_idx) =
+        build_slice_atlas(&data, &atlas, &ase.frame_to_idx, &ase.frame_rects);
+    let slice_atlas = atlases.add(slice_atlas);
+    ase.slice_atlas = Some(slice_atlas);
+    ase.slice_to_idx = slice_to_idx;
+
+    load_diagnostics.record(
+        diagnostics,
+        id,
+        AsepriteLoadTimings {
+            parse: ase.parse_time,
+            composite: composite_time,
+            atlas_build: start.elapsed(),
+            atlas_size: atlas.size.as_uvec2(),
+        },
+    );
+
+    shared.insert(ase.content_hash, id);
+    let atlas_handle = atlases.add(atlas);
+    ase.info = Some(ase.settings.info(data));
+    ase.atlas = Some(atlas_handle);
+    true
+}
 
-            // Build out texture atlas
-            let frames = data.frames();
-            let ase_images = frames
-                .get_for(&(0..frames.count() as u16))
-                .get_images()
-                .unwrap();
-
-            let mut frame_handles = vec![];
-            let mut atlas = TextureAtlasBuilder::default();
-
-            for (idx, image) in ase_images.into_iter().enumerate() {
-                let texture = Image::new(
-                    Extent3d {
-                        width: image.width(),
-                        height: image.height(),
-                        depth_or_array_layers: 1,
+// Every layer group gets an atlas of its own, with the frames composited
+// from its layers only
+fn build_group_atlases(
+    ase: &Aseprite,
+    data: &reader::Aseprite,
+    images: &mut Assets<Image>,
+    atlases: &mut Assets<TextureAtlas>,
+) -> HashMap<String, GroupAtlas> {
+    let mut group_atlases = HashMap::new();
+    let frames = data.frames();
+    for group in data.layers().groups() {
+        let options = reader::AsepriteCompositeOptions {
+            layer_group: Some(group.name().to_string()),
+            ..ase.settings.composite_options()
+        };
+        let group_images = match frames.all().get_rgba_buffers_with(&options) {
+            Ok(group_images) => group_images,
+            Err(source) => {
+                let err = AsepriteLoaderError::Aseprite {
+                    path: error_path(ase),
+                    stage: AsepriteLoadStage::Composite,
+                    source,
+                };
+                error!("{}", err);
+                continue;
+            }
+        };
+        let textures = group_images
+            .into_iter()
+            .map(|(data, width, height)| frame_texture(data, width, height, &ase.settings))
+            .collect();
+        match pack_frames(textures, images, &ase.settings) {
+            Ok((atlas, frame_to_idx)) => {
+                group_atlases.insert(
+                    group.name().to_string(),
+                    GroupAtlas {
+                        atlas: atlases.add(atlas),
+                        frame_to_idx,
                     },
-                    TextureDimension::D2,
-                    image.into_raw(),
-                    TextureFormat::Rgba8UnormSrgb,
                 );
-                let _label = format!("Frame{}", idx);
-                let texture_handle = images.add(texture.clone());
-                frame_handles.push(texture_handle.clone_weak());
-
-                atlas.add_texture(texture_handle.id(), &texture);
             }
-            let atlas = match atlas.finish(&mut *images) {
-                Ok(atlas) => atlas,
-                Err(err) => {
-                    error!("{:?}", err);
-                    return;
-                }
+            Err(err) => {
+                let err = AsepriteLoaderError::Atlas {
+                    path: error_path(ase),
+                    message: format!("{:?}", err),
+                };
+                error!("{}", err);
+            }
+        }
+    }
+    group_atlases
+}
+
+// A layer composited on its own into an image laid out like the frame atlas,
+// `None` if the file has no such layer
+fn build_layer_texture(
+    ase: &Aseprite,
+    data: &reader::Aseprite,
+    atlas: &TextureAtlas,
+    layer: &str,
+    images: &Assets<Image>,
+) -> Option<Image> {
+    data.layers().get_by_name(layer)?;
+    let composite_options = ase.settings.composite_options();
+    let options = reader::AsepriteCompositeOptions {
+        layer: Some(layer.to_string()),
+        missing_cel: composite_options.missing_cel,
+        transparent_color: composite_options.transparent_color,
+        ..Default::default()
+    };
+    let layer_images = match data.frames().all().get_rgba_buffers_with(&options) {
+        Ok(layer_images) => layer_images,
+        Err(source) => {
+            let err = AsepriteLoaderError::Aseprite {
+                path: error_path(ase),
+                stage: AsepriteLoadStage::Composite,
+                source,
             };
-            for handle in frame_handles {
-                let atlas_idx = atlas.get_texture_index(&handle).unwrap();
-                ase.frame_to_idx.push(atlas_idx);
+            error!("{}", err);
+            return None;
+        }
+    };
+    let size = images.get(&atlas.texture)?.texture_descriptor.size;
+    let mut texture = vec![0; (size.width * size.height * 4) as usize];
+    for (frame, (mut pixels, width, height)) in layer_images.into_iter().enumerate() {
+        // Trimmed frames keep the layer pixels inside of their rect
+        let rect = ase.frame_rects[frame];
+        if rect.size() != UVec2::new(width, height) {
+            pixels = crop(&pixels, width, rect);
+        }
+        let target = atlas.textures[ase.frame_to_idx[frame]];
+        blit_frame(
+            &mut texture,
+            size.width,
+            target,
+            &pixels,
+            rect.size(),
+            ase.settings.extrude,
+        );
+    }
+    let mut image = frame_image(texture, size.width, size.height, &ase.settings);
+    if ase.settings.generate_mipmaps {
+        generate_mipmaps(&mut image);
+    }
+    Some(image)
+}
+
+// Every effect gets an image with the layout of the frame atlas
+fn build_effect_textures(
+    atlas: &TextureAtlas,
+    images: &mut Assets<Image>,
+    settings: &AsepriteLoaderSettings,
+) -> Vec<Handle<Image>> {
+    let texture = match images.get(&atlas.texture) {
+        Some(texture) => texture,
+        None => return vec![],
+    };
+    let size = texture.texture_descriptor.size;
+    // Mipmaps come after the full size image
+    let data = texture.data[..(size.width * size.height * 4) as usize].to_vec();
+    let rects: Vec<_> = atlas
+        .textures
+        .iter()
+        .map(|rect| URect::from_corners(rect.min.as_uvec2(), rect.max.as_uvec2()))
+        .collect();
+    settings
+        .effects
+        .iter()
+        .map(|effect| {
+            let data = effects::apply_effect(
+                effect,
+                &data,
+                size.width,
+                &rects,
+                settings.premultiply_alpha,
+            );
+            let mut image = frame_image(data, size.width, size.height, settings);
+            if settings.generate_mipmaps {
+                generate_mipmaps(&mut image);
             }
-            let atlas_handle = atlases.add(atlas);
-            ase.info = Some(data.into());
-            ase.atlas = Some(atlas_handle);
+            images.add(image)
+        })
+        .collect()
+}
+
+// Slices point into the frame they are valid from, so they can share the
+// texture of the frame atlas
+fn build_slice_atlas(
+    data: &reader::Aseprite,
+    atlas: &TextureAtlas,
+    frame_to_idx: &[usize],
+    frame_rects: &[URect],
+) -> (TextureAtlas, HashMap<String, usize>) {
+    let mut slice_atlas = TextureAtlas::new_empty(atlas.texture.clone(), atlas.size);
+    let mut slice_to_idx = HashMap::new();
+    for slice in data.slices().get_all() {
+        let frame = slice.valid_frame as usize;
+        let (frame_rect, canvas_rect) = match (frame_to_idx.get(frame), frame_rects.get(frame)) {
+            (Some(&idx), Some(canvas_rect)) => (atlas.textures[idx], canvas_rect),
+            _ => {
+                error!("Slice {} references an invalid frame", slice.name);
+                continue;
+            }
+        };
+        // Trimmed frames only start at their content
+        let min =
+            Vec2::new(slice.position_x as f32, slice.position_y as f32) - canvas_rect.min.as_vec2();
+        let max = min + Vec2::new(slice.width as f32, slice.height as f32);
+        let rect = Rect::from_corners(
+            (frame_rect.min + min.max(Vec2::ZERO)).min(frame_rect.max),
+            (frame_rect.min + max.max(Vec2::ZERO)).min(frame_rect.max),
+        );
+        let slice_idx = slice_atlas.add_texture(rect);
+        slice_to_idx.insert(slice.name.clone(), slice_idx);
+    }
+    (slice_atlas, slice_to_idx)
+}
+
+// Updates the frames which changed since the previous build in the existing
+// atlas texture, returns false if the atlas has to be rebuilt instead
+fn update_atlas(
+    id: AssetId<Aseprite>,
+    previous: &PreviousBuild,
+    aseprites: &mut Assets<Aseprite>,
+    images: &mut Assets<Image>,
+    atlases: &mut Assets<TextureAtlas>,
+) -> bool {
+    // Other files sharing the atlas still show their own content
+    let atlas_id = previous.atlas.id();
+    if aseprites
+        .iter()
+        .any(|(other, ase)| other != id && ase.atlas.as_ref().map(Handle::id) == Some(atlas_id))
+    {
+        return false;
+    }
+
+    // Only borrowed mutably once updating, which marks the asset as modified
+    let ase = match aseprites.get(id) {
+        Some(ase) => ase,
+        None => return false,
+    };
+    let data = match &ase.data {
+        Some(data) => data,
+        None => return false,
+    };
+    if format!("{:?}", ase.settings) != previous.settings {
+        return false;
+    }
+    // Group atlases, effects, layer textures and streamed frames are rebuilt
+    // along with the whole atlas, held cels change the frames after the ones which changed
+    // too and trimmed frames change size
+    if ase.settings.group_atlases
+        || ase.settings.missing_cel == MissingCelPolicy::PreviousFrameHold
+        || ase.settings.trim_frames
+        || !ase.settings.effects.is_empty()
+        || ase.settings.stream_frames.is_some()
+        || ase
+            .settings
+            .texture_layers()
+            .iter()
+            .any(|layer| data.layers().get_by_name(layer).is_some())
+    {
+        return false;
+    }
+    let previous_data = match previous.data.decode(&ase.settings) {
+        Some(previous_data) => previous_data,
+        None => return false,
+    };
+    let diff = previous_data.diff(data);
+    if diff.dimensions_changed || data.frames().count() != previous_data.frames().count() {
+        return false;
+    }
+    let atlas = match atlases.get(&previous.atlas) {
+        Some(atlas) => atlas,
+        None => return false,
+    };
+
+    let frames = data.frames();
+    let changed = match diff
+        .changed_frames
+        .iter()
+        .map(|&index| {
+            frames
+                .get_for(&(index..index + 1))
+                .get_rgba_buffers_with(&ase.settings.composite_options())
+        })
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(changed) => changed,
+        Err(source) => {
+            let err = AsepriteLoaderError::Aseprite {
+                path: error_path(ase),
+                stage: AsepriteLoadStage::Composite,
+                source,
+            };
+            error!("{}", err);
+            return false;
         }
-    });
+    };
+
+    let texture = match images.get_mut(&atlas.texture) {
+        Some(texture) => texture,
+        None => return false,
+    };
+    let size = texture.texture_descriptor.size;
+    // Mipmaps get generated again from the updated texture
+    if ase.settings.generate_mipmaps {
+        texture
+            .data
+            .truncate((size.width * size.height * 4) as usize);
+        texture.texture_descriptor.mip_level_count = 1;
+    }
+    for (&index, frame) in diff.changed_frames.iter().zip(&changed) {
+        let (data, width, height) = &frame[0];
+        blit_frame(
+            &mut texture.data,
+            size.width,
+            atlas.textures[previous.frame_to_idx[index as usize]],
+            data,
+            UVec2::new(*width, *height),
+            ase.settings.extrude,
+        );
+    }
+    if ase.settings.generate_mipmaps {
+        generate_mipmaps(texture);
+    }
+
+    // Static files also draw their only frame as a plain image
+    let image = previous
+        .image
+        .as_ref()
+        .and_then(|image| images.get_mut(image));
+    if let (Some(image), Some(mut frame)) = (image, changed.into_iter().next()) {
+        image.data = frame.swap_remove(0).0;
+    }
+
+    let (slice_atlas, slice_to_idx) =
+        build_slice_atlas(data, atlas, &previous.frame_to_idx, &previous.frame_rects);
+    let ase = match aseprites.get_mut(id) {
+        Some(ase) => ase,
+        None => return false,
+    };
+    let data = match ase.data.take() {
+        Some(data) => data,
+        None => return false,
+    };
+    ase.source = None;
+    ase.slice_atlas = Some(atlases.add(slice_atlas));
+    ase.slice_to_idx = slice_to_idx;
+    ase.frame_to_idx = previous.frame_to_idx.clone();
+    ase.frame_rects = previous.frame_rects.clone();
+    ase.image = previous.image.clone();
+    ase.atlas = Some(previous.atlas.clone());
+    ase.info = Some(ase.settings.info(data));
+    true
 }
 
+// Only inserts the components which are missing, so values set by the user
+// (visibility, sprite color or flip, ...) are kept
 pub(crate) fn insert_sprite_sheet(
     mut commands: Commands,
-    aseprites: ResMut<Assets<Aseprite>>,
-    mut query: Query<
+    aseprites: Res<Assets<Aseprite>>,
+    query: Query<
         (
             Entity,
-            &Transform,
             &Handle<Aseprite>,
-            &mut AsepriteAnimation,
+            Option<&AsepriteGroup>,
+            Has<TextureAtlasSprite>,
+            Has<Transform>,
+            Has<GlobalTransform>,
+            Has<Visibility>,
+            Has<InheritedVisibility>,
+            Has<ViewVisibility>,
         ),
-        Without<TextureAtlasSprite>,
+        (With<AsepriteAnimation>, Without<Handle<TextureAtlas>>),
     >,
 ) {
-    for (entity, &transform, handle, _anim) in query.iter_mut() {
+    for (
+        entity,
+        handle,
+        group,
+        has_sprite,
+        has_transform,
+        has_global_transform,
+        has_visibility,
+        has_inherited_visibility,
+        has_view_visibility,
+    ) in query.iter()
+    {
         // FIXME The first time the query runs the aseprite atlas might not be ready
         // so failing to find it is expected.
         let aseprite = match aseprites.get(handle) {
@@ -145,18 +1361,630 @@ pub(crate) fn insert_sprite_sheet(
                 continue;
             }
         };
-        let mut atlas = match aseprite.atlas.clone() {
+        let atlas = match aseprite.atlas.clone() {
             Some(atlas) => atlas,
             None => {
                 debug!("Aseprite atlas not ready");
                 continue;
             }
         };
+        let atlas = match group {
+            Some(group) => match aseprite.group_atlas(&group.name) {
+                Some(group_atlas) => group_atlas.clone(),
+                None => {
+                    error!(
+                        "Layer group {} has no atlas, is group_atlases set when loading {}?",
+                        group.name,
+                        error_path(aseprite).display()
+                    );
+                    continue;
+                }
+            },
+            None => atlas,
+        };
+
+        let mut entity = commands.entity(entity);
+        entity.insert(atlas);
+        if !has_sprite {
+            entity.insert(TextureAtlasSprite::default());
+        }
+        insert_missing_spatial(
+            &mut entity,
+            has_transform,
+            has_global_transform,
+            has_visibility,
+            has_inherited_visibility,
+            has_view_visibility,
+        );
+    }
+}
+
+// Marks the static entities of files with several frames
+#[derive(Debug, Component)]
+pub(crate) struct NotStatic;
+
+pub(crate) fn insert_static_sprite(
+    mut commands: Commands,
+    aseprites: Res<Assets<Aseprite>>,
+    query: Query<
+        (
+            Entity,
+            &Handle<Aseprite>,
+            Has<Sprite>,
+            Has<Transform>,
+            Has<GlobalTransform>,
+            Has<Visibility>,
+            Has<InheritedVisibility>,
+            Has<ViewVisibility>,
+            Has<NotStatic>,
+        ),
+        (
+            Without<AsepriteAnimation>,
+            Without<Handle<Image>>,
+            // Text and UI buttons are drawn from slices instead
+            Without<AsepriteText>,
+            Without<Handle<TextureAtlas>>,
+        ),
+    >,
+) {
+    for (
+        entity,
+        handle,
+        has_sprite,
+        has_transform,
+        has_global_transform,
+        has_visibility,
+        has_inherited_visibility,
+        has_view_visibility,
+        not_static,
+    ) in query.iter()
+    {
+        let aseprite = match aseprites.get(handle) {
+            Some(aseprite) => aseprite,
+            None => {
+                debug!("Aseprite handle invalid");
+                continue;
+            }
+        };
+        if aseprite.info.is_none() {
+            debug!("Aseprite not ready");
+            continue;
+        }
+        // Only warned about once, until the file is reloaded with a single frame
+        let texture = match (aseprite.image.clone(), not_static) {
+            (Some(image), _) => image,
+            (None, true) => continue,
+            (None, false) => {
+                warn!(
+                    "Aseprite {} spawned as static but has more than one frame",
+                    error_path(aseprite).display()
+                );
+                commands.entity(entity).insert(NotStatic);
+                continue;
+            }
+        };
+
+        let mut entity = commands.entity(entity);
+        entity.insert(texture);
+        if not_static {
+            entity.remove::<NotStatic>();
+        }
+        if !has_sprite {
+            entity.insert(Sprite::default());
+        }
+        insert_missing_spatial(
+            &mut entity,
+            has_transform,
+            has_global_transform,
+            has_visibility,
+            has_inherited_visibility,
+            has_view_visibility,
+        );
+    }
+}
+
+// Sprites need these to be drawn, they are part of the sprite bundles
+fn insert_missing_spatial(
+    entity: &mut EntityCommands,
+    has_transform: bool,
+    has_global_transform: bool,
+    has_visibility: bool,
+    has_inherited_visibility: bool,
+    has_view_visibility: bool,
+) {
+    if !has_transform {
+        entity.insert(Transform::default());
+    }
+    if !has_global_transform {
+        entity.insert(GlobalTransform::default());
+    }
+    if !has_visibility {
+        entity.insert(Visibility::default());
+    }
+    if !has_inherited_visibility {
+        entity.insert(InheritedVisibility::default());
+    }
+    if !has_view_visibility {
+        entity.insert(ViewVisibility::default());
+    }
+}
+
+pub(crate) fn frame_image(
+    data: Vec<u8>,
+    width: u32,
+    height: u32,
+    settings: &AsepriteLoaderSettings,
+) -> Image {
+    let mut image = Image::new(
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    image.sampler = settings.sampler.clone();
+    image
+}
+
+// Smallest part of an RGBA buffer containing all of its visible pixels, a
+// single pixel in the middle when there are none
+fn content_rect(data: &[u8], width: u32, height: u32) -> URect {
+    let mut rect: Option<URect> = None;
+    for (index, pixel) in data.chunks_exact(4).enumerate() {
+        if pixel[3] == 0 {
+            continue;
+        }
+        let point = UVec2::new(index as u32 % width, index as u32 / width);
+        let pixel = URect::from_corners(point, point + 1);
+        rect = Some(match rect {
+            Some(rect) => rect.union(pixel),
+            None => pixel,
+        });
+    }
+    rect.unwrap_or_else(|| {
+        let center = UVec2::new(width, height) / 2;
+        URect::from_corners(center, center + 1)
+    })
+}
+
+// Copies a part of an RGBA buffer
+fn crop(data: &[u8], width: u32, rect: URect) -> Vec<u8> {
+    let row_len = (rect.width() * 4) as usize;
+    let mut cropped = Vec::with_capacity(row_len * rect.height() as usize);
+    for y in rect.min.y..rect.max.y {
+        let start = ((y * width + rect.min.x) * 4) as usize;
+        cropped.extend_from_slice(&data[start..start + row_len]);
+    }
+    cropped
+}
+
+// Texture of a composited frame, with its edges extruded as set
+fn frame_texture(
+    data: Vec<u8>,
+    width: u32,
+    height: u32,
+    settings: &AsepriteLoaderSettings,
+) -> Image {
+    let extrude = settings.extrude;
+    if extrude == 0 {
+        return frame_image(data, width, height, settings);
+    }
+    frame_image(
+        extrude_edges(&data, width, height, extrude),
+        width + extrude * 2,
+        height + extrude * 2,
+        settings,
+    )
+}
+
+// Atlas of a single frame drawn from the whole image
+fn single_frame_atlas(image: Handle<Image>, size: UVec2) -> (TextureAtlas, Vec<usize>) {
+    let mut atlas = TextureAtlas::new_empty(image, size.as_vec2());
+    atlas.add_texture(Rect::from_corners(Vec2::ZERO, size.as_vec2()));
+    (atlas, vec![0])
+}
+
+// Packs the frame textures into an atlas as set, also returns the atlas index
+// of each frame
+fn pack_frames(
+    textures: Vec<Image>,
+    images: &mut Assets<Image>,
+    settings: &AsepriteLoaderSettings,
+) -> Result<(TextureAtlas, Vec<usize>), TextureAtlasBuilderError> {
+    let (mut atlas, frame_to_idx) = match settings.packing {
+        AtlasPacking::Compact => pack_compact(textures, images)?,
+        AtlasPacking::Grid => pack_grid(textures, images, settings),
+    };
 
-        commands.entity(entity).insert(SpriteSheetBundle {
-            texture_atlas: atlas,
-            transform,
+    // The extruded edges are only there to avoid bleeding, the frames
+    // themselves are the inner part of each rect
+    let extrude = Vec2::splat(settings.extrude as f32);
+    for rect in atlas.textures.iter_mut() {
+        rect.min += extrude;
+        rect.max -= extrude;
+    }
+    if let Some(texture) = images.get_mut(&atlas.texture) {
+        texture.sampler = settings.sampler.clone();
+        if settings.generate_mipmaps {
+            generate_mipmaps(texture);
+        }
+    }
+    Ok((atlas, frame_to_idx))
+}
+
+// Packs the frames tightly, TextureAtlasBuilder might shift the index order
+fn pack_compact(
+    textures: Vec<Image>,
+    images: &mut Assets<Image>,
+) -> Result<(TextureAtlas, Vec<usize>), TextureAtlasBuilderError> {
+    let mut frame_handles = vec![];
+    let mut builder = TextureAtlasBuilder::default();
+    for texture in textures {
+        let texture_handle = images.add(texture.clone());
+        frame_handles.push(texture_handle.clone_weak());
+        builder.add_texture(texture_handle.id(), &texture);
+    }
+    let atlas = builder.finish(images)?;
+    let frame_to_idx = frame_handles
+        .iter()
+        .map(|handle| atlas.get_texture_index(handle).unwrap())
+        .collect();
+    Ok((atlas, frame_to_idx))
+}
+
+// Places the frames row by row on a grid, in frame order
+fn pack_grid(
+    textures: Vec<Image>,
+    images: &mut Assets<Image>,
+    settings: &AsepriteLoaderSettings,
+) -> (TextureAtlas, Vec<usize>) {
+    // Cells fit the largest frame, trimmed frames differ in size
+    let (width, height) = textures.iter().fold((0, 0), |(width, height), texture| {
+        (width.max(texture.width()), height.max(texture.height()))
+    });
+    let columns = (textures.len() as f32).sqrt().ceil().max(1.) as u32;
+    let rows = (textures.len() as u32).div_ceil(columns);
+    let (atlas_width, atlas_height) = (width * columns, height * rows.max(1));
+
+    let mut data = vec![0; (atlas_width * atlas_height * 4) as usize];
+    let mut rects = vec![];
+    for (idx, texture) in textures.iter().enumerate() {
+        let (x, y) = (idx as u32 % columns * width, idx as u32 / columns * height);
+        let (texture_width, texture_height) = (texture.width(), texture.height());
+        for row in 0..texture_height {
+            let src = (row * texture_width * 4) as usize;
+            let dst = (((y + row) * atlas_width + x) * 4) as usize;
+            let len = (texture_width * 4) as usize;
+            data[dst..dst + len].copy_from_slice(&texture.data[src..src + len]);
+        }
+        rects.push(Rect::new(
+            x as f32,
+            y as f32,
+            (x + texture_width) as f32,
+            (y + texture_height) as f32,
+        ));
+    }
+
+    let texture = images.add(frame_image(data, atlas_width, atlas_height, settings));
+    let mut atlas =
+        TextureAtlas::new_empty(texture, Vec2::new(atlas_width as f32, atlas_height as f32));
+    for rect in rects {
+        atlas.add_texture(rect);
+    }
+    (atlas, (0..textures.len()).collect())
+}
+
+// Repeats the edge pixels of an RGBA buffer `amount` times outwards
+fn extrude_edges(data: &[u8], width: u32, height: u32, amount: u32) -> Vec<u8> {
+    let new_width = width + amount * 2;
+    let new_height = height + amount * 2;
+    let mut extruded = Vec::with_capacity((new_width * new_height * 4) as usize);
+    for y in 0..new_height {
+        let src_y = y.saturating_sub(amount).min(height - 1);
+        for x in 0..new_width {
+            let src_x = x.saturating_sub(amount).min(width - 1);
+            let idx = ((src_x + src_y * width) * 4) as usize;
+            extruded.extend_from_slice(&data[idx..idx + 4]);
+        }
+    }
+    extruded
+}
+
+// Copies a frame with its edges extruded into its rect of an atlas texture
+pub(crate) fn blit_frame(
+    texture: &mut [u8],
+    texture_width: u32,
+    rect: Rect,
+    data: &[u8],
+    size: UVec2,
+    extrude: u32,
+) {
+    let (width, height) = (size.x + extrude * 2, size.y + extrude * 2);
+    let pixels = extrude_edges(data, size.x, size.y, extrude);
+    let (x, y) = (rect.min.x as u32 - extrude, rect.min.y as u32 - extrude);
+    let row_len = (width * 4) as usize;
+    for row in 0..height {
+        let src = row as usize * row_len;
+        let dst = (((y + row) * texture_width + x) * 4) as usize;
+        texture[dst..dst + row_len].copy_from_slice(&pixels[src..src + row_len]);
+    }
+}
+
+// Appends a box filtered mip chain to an RGBA image
+fn generate_mipmaps(image: &mut Image) {
+    let mut width = image.texture_descriptor.size.width;
+    let mut height = image.texture_descriptor.size.height;
+    let mut level_start = 0;
+    let mut mip_level_count = 1;
+
+    while width > 1 || height > 1 {
+        let next_width = (width / 2).max(1);
+        let next_height = (height / 2).max(1);
+        let mut level = Vec::with_capacity((next_width * next_height * 4) as usize);
+        for y in 0..next_height {
+            for x in 0..next_width {
+                let mut sum = [0u32; 4];
+                for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                    let src_x = (x * 2 + dx).min(width - 1);
+                    let src_y = (y * 2 + dy).min(height - 1);
+                    let idx = level_start + ((src_x + src_y * width) * 4) as usize;
+                    for (sum, value) in sum.iter_mut().zip(&image.data[idx..idx + 4]) {
+                        *sum += *value as u32;
+                    }
+                }
+                level.extend(sum.iter().map(|sum| (sum / 4) as u8));
+            }
+        }
+
+        level_start += (width * height * 4) as usize;
+        image.data.extend(level);
+        width = next_width;
+        height = next_height;
+        mip_level_count += 1;
+    }
+
+    image.texture_descriptor.mip_level_count = mip_level_count;
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::prelude::*;
+
+    use std::time::Duration;
+
+    use bevy::asset::AssetPlugin;
+
+    use super::{
+        content_rect, frame_image, pack_grid, slice_rect, AsepriteLoaderSettings, AtlasPacking,
+        NotStatic,
+    };
+    use crate::{reader, Aseprite, AsepritePlugin, AsepriteReady, AsepriteStaticBundle};
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .init_asset::<Image>()
+            .init_asset::<TextureAtlas>()
+            .add_plugins(AsepritePlugin {
+                rebuild_debounce: Duration::ZERO,
+                ..Default::default()
+            });
+        app
+    }
+
+    fn crow(settings: AsepriteLoaderSettings) -> Aseprite {
+        Aseprite::from_bytes(include_bytes!("../assets/crow.aseprite"), settings).unwrap()
+    }
+
+    // Runs until the asset events have been processed, returns how many
+    // aseprites got ready
+    fn run(app: &mut App) -> usize {
+        for _ in 0..3 {
+            app.update();
+        }
+        app.world
+            .resource_mut::<Events<AsepriteReady>>()
+            .drain()
+            .count()
+    }
+
+    fn atlas_size(app: &App, handle: &Handle<Aseprite>) -> Vec2 {
+        let aseprite = app
+            .world
+            .resource::<Assets<Aseprite>>()
+            .get(handle)
+            .unwrap();
+        let atlases = app.world.resource::<Assets<TextureAtlas>>();
+        atlases.get(aseprite.atlas.as_ref().unwrap()).unwrap().size
+    }
+
+    #[test]
+    fn grid_packing_is_in_frame_order() {
+        let settings = AsepriteLoaderSettings::default();
+        let textures = (0..5u8)
+            .map(|idx| frame_image(vec![idx; 2 * 3 * 4], 2, 3, &settings))
+            .collect();
+        let mut images = Assets::<Image>::default();
+
+        let (atlas, frame_to_idx) = pack_grid(textures, &mut images, &settings);
+        assert_eq!(frame_to_idx, vec![0, 1, 2, 3, 4]);
+        assert_eq!(atlas.size, Vec2::new(6., 6.));
+        assert_eq!(atlas.textures[4], Rect::new(2., 3., 4., 6.));
+
+        let texture = images.get(&atlas.texture).unwrap();
+        let pixel = |x: usize, y: usize| texture.data[(y * 6 + x) * 4];
+        assert_eq!(pixel(0, 0), 0);
+        assert_eq!(pixel(5, 2), 2);
+        assert_eq!(pixel(3, 4), 4);
+        assert_eq!(pixel(5, 5), 0);
+    }
+
+    #[test]
+    fn content_rect_skips_transparent_edges() {
+        let mut data = vec![0; 4 * 3 * 4];
+        let alpha = |x: usize, y: usize| (y * 4 + x) * 4 + 3;
+        data[alpha(1, 1)] = 255;
+        data[alpha(2, 2)] = 1;
+        assert_eq!(content_rect(&data, 4, 3), URect::new(1, 1, 3, 3));
+        assert_eq!(content_rect(&[0; 16], 2, 2), URect::new(1, 1, 2, 2));
+    }
+
+    #[test]
+    fn slice_rects_are_clamped_to_the_canvas() {
+        let data = reader::Aseprite::from_bytes(include_bytes!(
+            "../reader/tests/test_cases/slices_out_of_bounds.aseprite"
+        ))
+        .unwrap();
+        let slices = data.slices();
+        let rect = |name: &str| slice_rect(slices.get_by_name(name).unwrap(), Vec2::splat(8.));
+        assert_eq!(rect("inside"), Rect::new(1., 1., 3., 3.));
+        assert_eq!(rect("left"), Rect::new(0., 1., 2., 4.));
+        assert_eq!(rect("corner"), Rect::new(6., 6., 8., 8.));
+        assert!(rect("outside").is_empty());
+    }
+
+    #[test]
+    fn trimmed_frames_keep_their_place() {
+        let mut app = app();
+        // Compact atlases don't shrink below their initial size
+        let untrimmed =
+            app.world
+                .resource_mut::<Assets<Aseprite>>()
+                .add(crow(AsepriteLoaderSettings {
+                    packing: AtlasPacking::Grid,
+                    ..Default::default()
+                }));
+        let trimmed =
+            app.world
+                .resource_mut::<Assets<Aseprite>>()
+                .add(crow(AsepriteLoaderSettings {
+                    trim_frames: true,
+                    packing: AtlasPacking::Grid,
+                    ..Default::default()
+                }));
+        assert_eq!(run(&mut app), 2);
+
+        let aseprites = app.world.resource::<Assets<Aseprite>>();
+        let aseprite = aseprites.get(&trimmed).unwrap();
+        let (width, height) = aseprite.info().unwrap().dimensions;
+        let canvas = URect::new(0, 0, width as u32, height as u32);
+        for frame in 0..6 {
+            let rect = aseprite.frame_rect(frame).unwrap();
+            assert_eq!(rect.union(canvas), canvas);
+        }
+        let size = atlas_size(&app, &trimmed);
+        let untrimmed_size = atlas_size(&app, &untrimmed);
+        assert!(size.x * size.y < untrimmed_size.x * untrimmed_size.y);
+    }
+
+    #[test]
+    fn added_assets_get_built() {
+        let mut app = app();
+        let handle = app
+            .world
+            .resource_mut::<Assets<Aseprite>>()
+            .add(crow(AsepriteLoaderSettings::default()));
+
+        assert_eq!(run(&mut app), 1);
+        let aseprites = app.world.resource::<Assets<Aseprite>>();
+        let aseprite = aseprites.get(&handle).unwrap();
+        assert!(aseprite.is_ready());
+        assert!(aseprite.data.is_none());
+        assert_eq!(aseprite.frame_to_idx.len(), 6);
+        assert!(aseprite.slice_index("head").is_some());
+    }
+
+    #[test]
+    fn modified_assets_get_rebuilt() {
+        let mut app = app();
+        let handle =
+            app.world
+                .resource_mut::<Assets<Aseprite>>()
+                .add(crow(AsepriteLoaderSettings {
+                    packing: AtlasPacking::Grid,
+                    ..Default::default()
+                }));
+        run(&mut app);
+        let size = atlas_size(&app, &handle);
+
+        let extruded = crow(AsepriteLoaderSettings {
+            extrude: 1,
+            packing: AtlasPacking::Grid,
             ..Default::default()
         });
+        app.world
+            .resource_mut::<Assets<Aseprite>>()
+            .insert(handle.id(), extruded);
+        assert_eq!(run(&mut app), 1);
+        assert!(atlas_size(&app, &handle).x > size.x || atlas_size(&app, &handle).y > size.y);
+    }
+
+    #[test]
+    fn removed_assets_free_their_textures() {
+        let mut app = app();
+        let handle = app
+            .world
+            .resource_mut::<Assets<Aseprite>>()
+            .add(crow(AsepriteLoaderSettings::default()));
+        run(&mut app);
+        assert!(!app.world.resource::<Assets<TextureAtlas>>().is_empty());
+
+        app.world
+            .resource_mut::<Assets<Aseprite>>()
+            .remove(handle.id());
+        assert_eq!(run(&mut app), 0);
+        assert!(app.world.resource::<Assets<TextureAtlas>>().is_empty());
+        assert!(app.world.resource::<Assets<Image>>().is_empty());
+    }
+
+    #[test]
+    fn static_files_draw_their_image() {
+        let mut app = app();
+        let simple = Aseprite::from_bytes(
+            include_bytes!("../reader/tests/test_cases/simple.aseprite"),
+            AsepriteLoaderSettings::default(),
+        )
+        .unwrap();
+        let handle = app.world.resource_mut::<Assets<Aseprite>>().add(simple);
+        assert_eq!(run(&mut app), 1);
+
+        // The atlas doesn't get a copy of the frame
+        let aseprite = app
+            .world
+            .resource::<Assets<Aseprite>>()
+            .get(&handle)
+            .unwrap();
+        let image = aseprite.image().unwrap();
+        let atlases = app.world.resource::<Assets<TextureAtlas>>();
+        let atlas = atlases.get(aseprite.atlas.as_ref().unwrap()).unwrap();
+        assert_eq!(atlas.texture, *image);
+        let (width, height) = aseprite.info().unwrap().dimensions;
+        let canvas = Rect::new(0., 0., width as f32, height as f32);
+        assert_eq!(atlas.textures, vec![canvas]);
+        assert_eq!(aseprite.frame_to_idx, vec![0]);
+    }
+
+    #[test]
+    fn animated_files_are_not_drawn_as_static() {
+        let mut app = app();
+        let handle = app
+            .world
+            .resource_mut::<Assets<Aseprite>>()
+            .add(crow(AsepriteLoaderSettings::default()));
+        let entity = app
+            .world
+            .spawn(AsepriteStaticBundle {
+                aseprite: handle,
+                ..Default::default()
+            })
+            .id();
+        run(&mut app);
+
+        // Only warned about once
+        assert!(app.world.get::<NotStatic>(entity).is_some());
+        assert!(app.world.get::<Handle<Image>>(entity).is_none());
     }
 }
@@ -0,0 +1,216 @@
+use bevy::{prelude::*, sprite::Anchor};
+
+use crate::{Aseprite, SliceMeta};
+
+/// A nine-patch slice of an aseprite file, stretched to a size
+///
+/// The corners of the slice keep their size, its edges and center are
+/// stretched to fill the rest, e.g. for world-space speech bubbles and
+/// panels. The slice needs nine-patch info, set in Aseprite's slice
+/// properties.
+#[derive(Debug, Component, Clone, Default, PartialEq)]
+pub struct AsepriteNinePatchSlice {
+    /// Name of the slice
+    pub name: String,
+    /// Size the slice is stretched to, in pixels
+    pub size: Vec2,
+}
+
+impl AsepriteNinePatchSlice {
+    /// Stretch a slice to a size
+    pub fn new(name: impl Into<String>, size: Vec2) -> Self {
+        Self {
+            name: name.into(),
+            size,
+        }
+    }
+}
+
+/// A bundle defining a nine-patch slice stretched to a size
+///
+/// Each of the nine patches is spawned as a child sprite, centered on the
+/// entity.
+#[derive(Debug, Bundle, Default)]
+pub struct AsepriteNinePatchBundle {
+    pub nine_patch: AsepriteNinePatchSlice,
+    pub aseprite: Handle<Aseprite>,
+    pub transform: Transform,
+    pub global_transform: GlobalTransform,
+    pub visibility: Visibility,
+    pub inherited_visibility: InheritedVisibility,
+    pub view_visibility: ViewVisibility,
+}
+
+/// The slice and size the patch children were laid out for, other children
+/// are left alone when laying it out again
+#[derive(Debug, Component)]
+pub(crate) struct AsepriteNinePatchLayout {
+    nine_patch: AsepriteNinePatchSlice,
+    patches: Vec<Entity>,
+}
+
+// Edges of the columns (or rows) of the slice, with the center clamped to the
+// slice like when cutting its nine slices in the reader
+fn source_edges(center: i32, center_size: u32, length: f32) -> [f32; 4] {
+    let start = (center as f32).clamp(0., length);
+    let end = (center as f32 + center_size as f32).clamp(start, length);
+    [0., start, end, length]
+}
+
+// Edges of the columns (or rows) of the patches, the middle one being
+// stretched so they add up to the size. Sizes smaller than the corners scale
+// them down instead.
+fn patch_edges(center_start: f32, center_end: f32, length: f32, size: f32) -> [f32; 4] {
+    let size = size.max(0.);
+    let end = length - center_end;
+    let corners = center_start + end;
+    if size < corners {
+        let start = center_start * size / corners;
+        return [0., start, start, size];
+    }
+    [0., center_start, size - end, size]
+}
+
+pub(crate) fn layout_nine_patches(
+    mut commands: Commands,
+    aseprites: Res<Assets<Aseprite>>,
+    atlases: Res<Assets<TextureAtlas>>,
+    query: Query<(
+        Entity,
+        &AsepriteNinePatchSlice,
+        &Handle<Aseprite>,
+        Option<&AsepriteNinePatchLayout>,
+    )>,
+) {
+    for (entity, nine_patch, handle, layout) in query.iter() {
+        if layout
+            .map(|layout| &layout.nine_patch == nine_patch)
+            .unwrap_or(false)
+        {
+            continue;
+        }
+        let aseprite = match aseprites.get(handle) {
+            Some(aseprite) => aseprite,
+            None => {
+                debug!("Aseprite handle invalid");
+                continue;
+            }
+        };
+        let (info, slice_atlas) = match (aseprite.info(), aseprite.slice_atlas()) {
+            (Some(info), Some(slice_atlas)) => (info, slice_atlas),
+            _ => {
+                debug!("Aseprite slice atlas not ready");
+                continue;
+            }
+        };
+        let atlas = match atlases.get(slice_atlas) {
+            Some(atlas) => atlas,
+            None => {
+                debug!("Aseprite slice atlas not ready");
+                continue;
+            }
+        };
+        let (slice, index) = match (
+            info.slices.get(&nine_patch.name),
+            aseprite.slice_index(&nine_patch.name),
+        ) {
+            (Some(slice), Some(index)) => (slice, index),
+            _ => {
+                error!("Slice {} wasn't found", nine_patch.name);
+                continue;
+            }
+        };
+        let center = match &slice.nine_patch_info {
+            Some(center) => center,
+            None => {
+                error!("Slice {} has no nine-patch info", nine_patch.name);
+                continue;
+            }
+        };
+
+        // Patches are measured from the top left corner, like in aseprite
+        let rect = atlas.textures[index];
+        let source_x = source_edges(center.x_center, center.width, rect.width());
+        let source_y = source_edges(center.y_center, center.height, rect.height());
+        let target_x = patch_edges(source_x[1], source_x[2], source_x[3], nine_patch.size.x);
+        let target_y = patch_edges(source_y[1], source_y[2], source_y[3], nine_patch.size.y);
+        let top_left = Vec2::new(-target_x[3], target_y[3]) / 2.;
+
+        for patch in layout.iter().flat_map(|layout| &layout.patches) {
+            if let Some(patch) = commands.get_entity(*patch) {
+                patch.despawn_recursive();
+            }
+        }
+        let mut patches = vec![];
+        let mut entity_commands = commands.entity(entity);
+        match slice.text() {
+            Some(text) => entity_commands.insert(SliceMeta(text.to_string())),
+            None => entity_commands.remove::<SliceMeta>(),
+        };
+        entity_commands
+            .with_children(|parent| {
+                for row in 0..3 {
+                    for column in 0..3 {
+                        let source = Rect::new(
+                            source_x[column],
+                            source_y[row],
+                            source_x[column + 1],
+                            source_y[row + 1],
+                        );
+                        let size = Vec2::new(
+                            target_x[column + 1] - target_x[column],
+                            target_y[row + 1] - target_y[row],
+                        );
+                        if source.is_empty() || size.x <= 0. || size.y <= 0. {
+                            continue;
+                        }
+
+                        let patch = parent.spawn(SpriteBundle {
+                            sprite: Sprite {
+                                rect: Some(Rect {
+                                    min: rect.min + source.min,
+                                    max: rect.min + source.max,
+                                }),
+                                custom_size: Some(size),
+                                anchor: Anchor::TopLeft,
+                                ..Default::default()
+                            },
+                            texture: atlas.texture.clone(),
+                            transform: Transform::from_translation(
+                                (top_left + Vec2::new(target_x[column], -target_y[row])).extend(0.),
+                            ),
+                            ..Default::default()
+                        });
+                        patches.push(patch.id());
+                    }
+                }
+            })
+            .insert(AsepriteNinePatchLayout {
+                nine_patch: nine_patch.clone(),
+                patches,
+            });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{patch_edges, source_edges};
+
+    #[test]
+    fn source_edges_are_clamped() {
+        assert_eq!(source_edges(2, 4, 8.), [0., 2., 6., 8.]);
+        // Centers past the bounds of the slice are clamped to them
+        assert_eq!(source_edges(-2, 4, 8.), [0., 0., 2., 8.]);
+        assert_eq!(source_edges(6, 4, 8.), [0., 6., 8., 8.]);
+        assert_eq!(source_edges(10, 4, 8.), [0., 8., 8., 8.]);
+    }
+
+    #[test]
+    fn edges_keep_the_corners() {
+        assert_eq!(patch_edges(2., 6., 8., 20.), [0., 2., 18., 20.]);
+        // Too small to fit both corners, they are scaled down and the center
+        // disappears
+        assert_eq!(patch_edges(2., 6., 8., 3.), [0., 1.5, 1.5, 3.]);
+        assert_eq!(patch_edges(2., 6., 8., -1.), [0., 0., 0., 0.]);
+    }
+}
@@ -0,0 +1,140 @@
+//! Animations split across several files, loaded as a single aseprite
+//!
+//! A set is a RON manifest with the `aseset` extension, listing the files of
+//! each state relative to it:
+//!
+//! ```ron
+//! (
+//!     files: [
+//!         ("Idle", "hero/Idle.aseprite"),
+//!         ("Run", "hero/Run.aseprite"),
+//!     ],
+//! )
+//! ```
+//!
+//! Loading it gives a `Handle<Aseprite>` with the frames of every file in one
+//! atlas, so the animation can switch between states without swapping
+//! handles. Each state is a tag covering the frames of its file, the tags of
+//! the files are renamed `<state>/<tag>`:
+//!
+//! ```rust,ignore
+//! commands.spawn(AsepriteBundle {
+//!     aseprite: asset_server.load("hero.aseset"),
+//!     animation: AsepriteAnimation::from("Run"),
+//!     ..Default::default()
+//! });
+//! ```
+//!
+//! See [`Aseprite::merge`](bevy_aseprite_reader::Aseprite::merge) for how
+//! the files are combined.
+
+use bevy::{
+    asset::{
+        io::{AssetSourceId, Reader},
+        AssetLoader, AsyncReadExt, LoadContext,
+    },
+    prelude::*,
+    utils::{BoxedFuture, Instant},
+};
+use bevy_aseprite_reader as reader;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::{
+    error::{AsepriteLoadStage, AsepriteLoaderError},
+    loader, Aseprite, AsepriteLoaderSettings,
+};
+
+/// The manifest of a set of files
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AsepriteSet {
+    /// The name of each state with its file, relative to the manifest
+    pub files: Vec<(String, String)>,
+}
+
+#[derive(Debug, Default)]
+pub struct AsepriteSetLoader {
+    // Only sets from these sources are loaded, if set
+    pub(crate) sources: Option<Vec<AssetSourceId<'static>>>,
+    // Slice sub-assets come with an image, only added when textures are
+    pub(crate) load_textures: bool,
+}
+
+impl AssetLoader for AsepriteSetLoader {
+    type Asset = Aseprite;
+    type Settings = AsepriteLoaderSettings;
+    type Error = AsepriteLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        settings: &'a Self::Settings,
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            debug!("Loading aseprite set at {:?}", load_context.path());
+
+            let path = load_context.path().to_path_buf();
+            loader::check_source(&self.sources, load_context)?;
+
+            let mut buffer = vec![];
+            if let Err(source) = reader.read_to_end(&mut buffer).await {
+                return Err(AsepriteLoaderError::Io { path, source });
+            }
+            let set_error = |message: String| AsepriteLoaderError::Set {
+                path: path.clone(),
+                message,
+            };
+            let set: AsepriteSet =
+                ron::de::from_bytes(&buffer).map_err(|err| set_error(err.to_string()))?;
+
+            // Identical sets share their atlas, the hash covers every file
+            let mut bytes = buffer;
+            let mut files = vec![];
+            let mut parse_time = Duration::ZERO;
+            for (name, file) in &set.files {
+                let file_path = load_context
+                    .asset_path()
+                    .resolve_embed(file)
+                    .map_err(|err| set_error(format!("{}: {}", file, err)))?;
+                let file_bytes = load_context
+                    .read_asset_bytes(file_path.clone())
+                    .await
+                    .map_err(|err| set_error(err.to_string()))?;
+                bytes.extend_from_slice(&file_bytes);
+
+                let start = Instant::now();
+                let data = loader::parse_data(file_bytes, settings).map_err(|source| {
+                    AsepriteLoaderError::Aseprite {
+                        path: file_path.path().to_path_buf(),
+                        stage: AsepriteLoadStage::Parse,
+                        source,
+                    }
+                })?;
+                parse_time += start.elapsed();
+                files.push((name.as_str(), data));
+            }
+
+            let start = Instant::now();
+            let data = reader::Aseprite::merge(files.iter().map(|(name, data)| (*name, data)))
+                .map_err(|source| AsepriteLoaderError::Aseprite {
+                    path: path.clone(),
+                    stage: AsepriteLoadStage::Parse,
+                    source,
+                })?;
+            parse_time += start.elapsed();
+
+            let content_hash = loader::content_hash(&bytes, settings);
+            let mut aseprite = loader::new_aseprite(data, content_hash, parse_time, settings);
+            aseprite.path = Some(path);
+            if self.load_textures {
+                loader::add_sub_assets(load_context, &mut aseprite, settings);
+            }
+            Ok(aseprite)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["aseset"]
+    }
+}
@@ -0,0 +1,154 @@
+//! Frames streamed into a small texture instead of a whole atlas
+//!
+//! Files loaded with
+//! [`stream_frames`](crate::AsepriteLoaderSettings::stream_frames) keep their
+//! composited frames in memory. Their atlas only has a few slots, the frames
+//! shown by their sprites (and the ones shown next) are copied into the least
+//! recently used slots as they are needed.
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{anim::AsepriteAnimation, loader, Aseprite, AsepriteLoaderSettings};
+
+// Composited RGBA pixels of every frame of a streamed file
+#[derive(Debug, Clone)]
+pub(crate) struct StreamedFrames {
+    pub(crate) frames: Vec<Vec<u8>>,
+    pub(crate) size: UVec2,
+}
+
+impl Aseprite {
+    /// Returns `true` if the frames of the file are streamed into a few
+    /// slots instead of being packed in an atlas
+    ///
+    /// See [`AsepriteLoaderSettings::stream_frames`]
+    pub fn is_streamed(&self) -> bool {
+        self.streamed.is_some()
+    }
+}
+
+// Atlas of the slots of a streamed file, side by side and empty at first
+pub(crate) fn ring_atlas(
+    size: UVec2,
+    slots: usize,
+    images: &mut Assets<Image>,
+    settings: &AsepriteLoaderSettings,
+) -> TextureAtlas {
+    let width = size.x * slots as u32;
+    let data = vec![0; (width * size.y * 4) as usize];
+    let texture = images.add(loader::frame_image(data, width, size.y, settings));
+    let mut atlas = TextureAtlas::new_empty(texture, UVec2::new(width, size.y).as_vec2());
+    for slot in 0..slots as u32 {
+        let min = UVec2::new(slot * size.x, 0).as_vec2();
+        atlas.add_texture(Rect::from_corners(min, min + size.as_vec2()));
+    }
+    atlas
+}
+
+// Frame held by each slot of a streamed file, and when it was last shown
+#[derive(Debug)]
+pub(crate) struct FrameRing {
+    atlas: AssetId<TextureAtlas>,
+    frames: Vec<Option<usize>>,
+    last_used: Vec<u64>,
+}
+
+impl FrameRing {
+    fn new(atlas: AssetId<TextureAtlas>, slots: usize) -> Self {
+        Self {
+            atlas,
+            frames: vec![None; slots],
+            last_used: vec![0; slots],
+        }
+    }
+
+    // Slot holding a frame, uploaded over the least recently used one first
+    // if no slot holds it
+    fn slot(&mut self, frame: usize, tick: u64, upload: impl FnOnce(usize)) -> usize {
+        let slot = match self.frames.iter().position(|&held| held == Some(frame)) {
+            Some(slot) => slot,
+            None => {
+                let slot = (0..self.frames.len())
+                    .min_by_key(|&slot| self.last_used[slot])
+                    .unwrap_or_default();
+                upload(slot);
+                self.frames[slot] = Some(frame);
+                slot
+            }
+        };
+        self.last_used[slot] = tick;
+        slot
+    }
+
+    // Whether a slot wasn't shown during this update, so it can be replaced
+    fn has_spare_slot(&self, tick: u64) -> bool {
+        self.last_used.iter().any(|&used| used < tick)
+    }
+}
+
+// Uploads the frames shown by the sprites of streamed files, along with the
+// frame each of them shows next, and points the sprites to their slot
+pub(crate) fn stream_frames(
+    mut tick: Local<u64>,
+    mut rings: Local<HashMap<AssetId<Aseprite>, FrameRing>>,
+    aseprites: Res<Assets<Aseprite>>,
+    atlases: Res<Assets<TextureAtlas>>,
+    mut images: ResMut<Assets<Image>>,
+    mut sprites_query: Query<(
+        &Handle<Aseprite>,
+        &AsepriteAnimation,
+        &mut TextureAtlasSprite,
+    )>,
+) {
+    *tick += 1;
+    rings.retain(|id, _| aseprites.contains(*id));
+    for (handle, animation, mut sprite) in sprites_query.iter_mut() {
+        let aseprite = match aseprites.get(handle) {
+            Some(aseprite) => aseprite,
+            None => continue,
+        };
+        let (streamed, info, atlas_handle) =
+            match (&aseprite.streamed, &aseprite.info, &aseprite.atlas) {
+                (Some(streamed), Some(info), Some(atlas)) => (streamed, info, atlas),
+                _ => continue,
+            };
+        let atlas = match atlases.get(atlas_handle) {
+            Some(atlas) => atlas,
+            None => continue,
+        };
+
+        // Rebuilt files start over with empty slots
+        let ring = rings
+            .entry(handle.id())
+            .or_insert_with(|| FrameRing::new(atlas_handle.id(), atlas.len()));
+        if ring.atlas != atlas_handle.id() {
+            *ring = FrameRing::new(atlas_handle.id(), atlas.len());
+        }
+
+        let mut upload = |frame: usize, slot: usize| {
+            let (texture, data) = match (images.get_mut(&atlas.texture), streamed.frames.get(frame))
+            {
+                (Some(texture), Some(data)) => (texture, data),
+                _ => return,
+            };
+            let width = texture.texture_descriptor.size.width;
+            loader::blit_frame(
+                &mut texture.data,
+                width,
+                atlas.textures[slot],
+                data,
+                streamed.size,
+                0,
+            );
+        };
+        let frame = animation.current_frame;
+        let slot = ring.slot(frame, *tick, |slot| upload(frame, slot));
+        // Frames shown during this update are never replaced by upcoming ones
+        if let (Some(next), true) = (animation.next_frame_index(info), ring.has_spare_slot(*tick)) {
+            ring.slot(next, *tick, |slot| upload(next, slot));
+        }
+        if sprite.index != slot {
+            sprite.index = slot;
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use bevy::{prelude::*, sprite::Anchor};
+
+use crate::Aseprite;
+
+/// Prefix of the slices used as glyphs
+///
+/// A slice named `glyph_A` is drawn for the character `A`. Slices named
+/// after the character itself (`A`) are used as a fallback.
+pub const GLYPH_SLICE_PREFIX: &str = "glyph_";
+
+/// Text drawn with a bitmap font authored as slices of an aseprite file
+#[derive(Debug, Component, Clone, PartialEq)]
+pub struct AsepriteText {
+    /// The text to lay out
+    pub text: String,
+    /// Extra pixels added after each glyph
+    pub letter_spacing: f32,
+    /// Advance used for characters without a glyph, such as spaces
+    pub space_width: f32,
+}
+
+impl Default for AsepriteText {
+    fn default() -> Self {
+        Self {
+            text: String::new(),
+            letter_spacing: 1.,
+            space_width: 4.,
+        }
+    }
+}
+
+impl AsepriteText {
+    /// Create a new text with the default spacing
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            ..Default::default()
+        }
+    }
+}
+
+/// A bundle defining a text drawn from aseprite glyph slices
+///
+/// Each glyph is spawned as a child sprite, starting at the origin of the
+/// entity and going right.
+#[derive(Debug, Bundle, Default)]
+pub struct AsepriteTextBundle {
+    pub text: AsepriteText,
+    pub aseprite: Handle<Aseprite>,
+    pub transform: Transform,
+    pub global_transform: GlobalTransform,
+    pub visibility: Visibility,
+    pub inherited_visibility: InheritedVisibility,
+    pub view_visibility: ViewVisibility,
+}
+
+/// The text the glyph children were laid out for, other children are left
+/// alone when laying it out again
+#[derive(Debug, Component)]
+pub(crate) struct AsepriteTextLayout {
+    text: AsepriteText,
+    glyphs: Vec<Entity>,
+}
+
+fn glyph_index(aseprite: &Aseprite, c: char) -> Option<usize> {
+    aseprite
+        .slice_index(&format!("{}{}", GLYPH_SLICE_PREFIX, c))
+        .or_else(|| aseprite.slice_index(c.encode_utf8(&mut [0; 4])))
+}
+
+pub(crate) fn layout_text(
+    mut commands: Commands,
+    aseprites: Res<Assets<Aseprite>>,
+    atlases: Res<Assets<TextureAtlas>>,
+    query: Query<(
+        Entity,
+        &AsepriteText,
+        &Handle<Aseprite>,
+        Option<&AsepriteTextLayout>,
+    )>,
+) {
+    for (entity, text, handle, layout) in query.iter() {
+        if layout.map(|layout| &layout.text == text).unwrap_or(false) {
+            continue;
+        }
+        let aseprite = match aseprites.get(handle) {
+            Some(aseprite) => aseprite,
+            None => {
+                debug!("Aseprite handle invalid");
+                continue;
+            }
+        };
+        let slice_atlas = match aseprite.slice_atlas() {
+            Some(slice_atlas) => slice_atlas,
+            None => {
+                debug!("Aseprite slice atlas not ready");
+                continue;
+            }
+        };
+        let atlas = match atlases.get(slice_atlas) {
+            Some(atlas) => atlas,
+            None => {
+                debug!("Aseprite slice atlas not ready");
+                continue;
+            }
+        };
+
+        for glyph in layout.iter().flat_map(|layout| &layout.glyphs) {
+            if let Some(glyph) = commands.get_entity(*glyph) {
+                glyph.despawn_recursive();
+            }
+        }
+        let mut glyphs = vec![];
+        commands
+            .entity(entity)
+            .with_children(|parent| {
+                let mut x = 0.;
+                for c in text.text.chars() {
+                    let index = match glyph_index(aseprite, c) {
+                        Some(index) => index,
+                        None => {
+                            x += text.space_width;
+                            continue;
+                        }
+                    };
+
+                    let glyph = parent.spawn(SpriteSheetBundle {
+                        sprite: TextureAtlasSprite {
+                            index,
+                            anchor: Anchor::BottomLeft,
+                            ..Default::default()
+                        },
+                        texture_atlas: slice_atlas.clone(),
+                        transform: Transform::from_xyz(x, 0., 0.),
+                        ..Default::default()
+                    });
+                    glyphs.push(glyph.id());
+                    x += atlas.textures[index].width() + text.letter_spacing;
+                }
+            })
+            .insert(AsepriteTextLayout {
+                text: text.clone(),
+                glyphs,
+            });
+    }
+}
@@ -0,0 +1,105 @@
+use bevy::{prelude::*, ui::UiTextureAtlasImage};
+
+use crate::{Aseprite, SliceMeta};
+
+/// Suffix of the slice shown when the button is not interacted with
+pub const BUTTON_NORMAL_SUFFIX: &str = "_normal";
+/// Suffix of the slice shown when the button is hovered
+pub const BUTTON_HOVER_SUFFIX: &str = "_hover";
+/// Suffix of the slice shown when the button is pressed
+pub const BUTTON_PRESSED_SUFFIX: &str = "_pressed";
+
+/// A UI button drawn from slices of an aseprite file
+///
+/// The slice is picked from the [`Interaction`] of the button, a button named
+/// `play` uses the slices `play_normal`, `play_hover` and `play_pressed`.
+/// Missing hover or pressed slices fall back to the normal one.
+#[derive(Debug, Default, Component, Clone, PartialEq, Eq)]
+pub struct AsepriteButton {
+    /// Name of the button, the slice names without their suffix
+    pub name: String,
+}
+
+impl AsepriteButton {
+    /// Create a new button from its name
+    pub fn new(name: impl Into<String>) -> Self {
+        Self { name: name.into() }
+    }
+
+    fn slice_index(&self, aseprite: &Aseprite, interaction: Interaction) -> Option<usize> {
+        let suffix = match interaction {
+            Interaction::Pressed => BUTTON_PRESSED_SUFFIX,
+            Interaction::Hovered => BUTTON_HOVER_SUFFIX,
+            Interaction::None => BUTTON_NORMAL_SUFFIX,
+        };
+        aseprite
+            .slice_index(&format!("{}{}", self.name, suffix))
+            .or_else(|| aseprite.slice_index(&format!("{}{}", self.name, BUTTON_NORMAL_SUFFIX)))
+    }
+}
+
+/// A bundle defining a UI button drawn from aseprite slices
+#[derive(Bundle, Default)]
+pub struct AsepriteButtonBundle {
+    pub button: AsepriteButton,
+    pub aseprite: Handle<Aseprite>,
+    pub atlas_image: AtlasImageBundle,
+    pub marker: Button,
+    pub interaction: Interaction,
+}
+
+pub(crate) fn update_buttons(
+    mut commands: Commands,
+    aseprites: Res<Assets<Aseprite>>,
+    mut query: Query<(
+        Entity,
+        &AsepriteButton,
+        &Handle<Aseprite>,
+        &Interaction,
+        &mut Handle<TextureAtlas>,
+        &mut UiTextureAtlasImage,
+    )>,
+) {
+    for (entity, button, handle, &interaction, mut atlas, mut image) in query.iter_mut() {
+        let aseprite = match aseprites.get(handle) {
+            Some(aseprite) => aseprite,
+            None => {
+                debug!("Aseprite handle invalid");
+                continue;
+            }
+        };
+        let slice_atlas = match aseprite.slice_atlas() {
+            Some(slice_atlas) => slice_atlas,
+            None => {
+                debug!("Aseprite slice atlas not ready");
+                continue;
+            }
+        };
+        let index = match button.slice_index(aseprite, interaction) {
+            Some(index) => index,
+            None => {
+                error!("No slice found for button {}", button.name);
+                continue;
+            }
+        };
+
+        if *atlas != *slice_atlas {
+            *atlas = slice_atlas.clone();
+            // The text of the normal slice, looked up again when the file is rebuilt
+            let text = aseprite
+                .info()
+                .and_then(|info| {
+                    info.slices
+                        .get(&format!("{}{}", button.name, BUTTON_NORMAL_SUFFIX))
+                })
+                .and_then(|slice| slice.text());
+            match text {
+                Some(text) => commands.entity(entity).insert(SliceMeta(text.to_string())),
+                None => commands.entity(entity).remove::<SliceMeta>(),
+            };
+        }
+        if image.index != index {
+            image.index = index;
+        }
+    }
+}
@@ -0,0 +1,701 @@
+//! Headless tests of the whole pipeline, from loading a file through the asset
+//! server to the frames shown by the sprites
+//!
+//! `assets/directions.aseprite` has four frames of 100ms, tagged `forward`,
+//! `reverse` and `ping_pong` with the matching directions, and `end` for the
+//! last two frames. The `layers` test case of the reader has a layer group,
+//! `companion_layers` has a `normal` layer. `layers.aseset` merges `layers`
+//! with itself as the `Idle` and `Run` states. `slice_user_data` has a
+//! nine-patch slice with text in its user data.
+
+use std::{path::Path, time::Duration};
+
+use bevy::{
+    asset::io::{
+        memory::{Dir, MemoryAssetReader},
+        AssetSource,
+    },
+    prelude::*,
+    render::primitives::Aabb,
+    time::TimeUpdateStrategy,
+};
+use bevy_aseprite::{
+    anim::{
+        AsepriteAnimation, AsepriteMissing, AsepriteSequencer, FrameSequencer,
+        MissingAsepritePolicy,
+    },
+    diagnostics::AsepriteDiagnostics,
+    effects::AsepriteMaterialOverride,
+    group::{AsepriteGroup, AsepriteGroupBundle},
+    nine_patch::{AsepriteNinePatchBundle, AsepriteNinePatchSlice},
+    Aseprite, AsepriteAnimationDirection, AsepriteBundle, AsepriteInfo, AsepriteLoaderSettings,
+    AsepritePlugin, AsepriteReady, AsepriteTag, AsepriteTagAsset, AtlasPacking, SliceMeta,
+};
+
+const FRAME_DURATION: Duration = Duration::from_millis(100);
+
+fn app() -> App {
+    app_with(AsepritePlugin::default())
+}
+
+fn app_with(plugin: AsepritePlugin) -> App {
+    let assets = Dir::default();
+    assets.insert_asset(
+        Path::new("directions.aseprite"),
+        include_bytes!("assets/directions.aseprite").to_vec(),
+    );
+    assets.insert_asset(
+        Path::new("layers.aseprite"),
+        include_bytes!("../reader/tests/test_cases/layers.aseprite").to_vec(),
+    );
+    assets.insert_asset(
+        Path::new("layers.aseset"),
+        br#"(files: [("Idle", "layers.aseprite"), ("Run", "layers.aseprite")])"#.to_vec(),
+    );
+    assets.insert_asset(
+        Path::new("slice_user_data.aseprite"),
+        include_bytes!("../reader/tests/test_cases/slice_user_data.aseprite").to_vec(),
+    );
+    assets.insert_asset(
+        Path::new("duplicate_tags.aseprite"),
+        include_bytes!("../reader/tests/test_cases/duplicate_tags.aseprite").to_vec(),
+    );
+    assets.insert_asset(
+        Path::new("companion_layers.aseprite"),
+        include_bytes!("../reader/tests/test_cases/companion_layers.aseprite").to_vec(),
+    );
+
+    let mut app = App::new();
+    app.register_asset_source(
+        "memory",
+        AssetSource::build().with_reader(move || {
+            Box::new(MemoryAssetReader {
+                root: assets.clone(),
+            })
+        }),
+    )
+    .add_plugins((MinimalPlugins, AssetPlugin::default()))
+    .init_asset::<Image>()
+    .init_asset::<TextureAtlas>()
+    .add_plugins(plugin)
+    // Every update shows the next frame
+    .insert_resource(TimeUpdateStrategy::ManualDuration(FRAME_DURATION));
+    app
+}
+
+// Loads the file and waits until its atlas is built
+fn load(app: &mut App) -> Handle<Aseprite> {
+    // Grid packing keeps the frames in order, so atlas indices are frames
+    load_with(app, "directions.aseprite", |settings| {
+        settings.packing = AtlasPacking::Grid
+    })
+}
+
+fn load_with(
+    app: &mut App,
+    path: &str,
+    settings: fn(&mut AsepriteLoaderSettings),
+) -> Handle<Aseprite> {
+    let handle = app
+        .world
+        .resource::<AssetServer>()
+        .load_with_settings(format!("memory://{}", path), settings);
+    for _ in 0..1000 {
+        app.update();
+        let aseprites = app.world.resource::<Assets<Aseprite>>();
+        if aseprites.get(&handle).is_some_and(Aseprite::is_ready) {
+            return handle;
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    panic!("{} didn't load", path);
+}
+
+// The atlas index of the sprite after each update
+fn indices(app: &mut App, entity: Entity, updates: usize) -> Vec<usize> {
+    (0..updates)
+        .map(|_| {
+            app.update();
+            app.world.get::<TextureAtlasSprite>(entity).unwrap().index
+        })
+        .collect()
+}
+
+fn play(tag: &str, updates: usize) -> Vec<usize> {
+    let mut app = app();
+    let aseprite = load(&mut app);
+    let entity = app
+        .world
+        .spawn(AsepriteBundle {
+            aseprite,
+            animation: AsepriteAnimation::from(tag),
+            ..Default::default()
+        })
+        .id();
+    indices(&mut app, entity, updates)
+}
+
+#[test]
+fn ready_is_sent_once_built() {
+    let mut app = app();
+    let aseprite = load(&mut app);
+    let ready: Vec<_> = app
+        .world
+        .resource_mut::<Events<AsepriteReady>>()
+        .drain()
+        .collect();
+    assert_eq!(ready, vec![AsepriteReady { id: aseprite.id() }]);
+
+    // Building modifies the asset, which isn't rebuilt after the debounce
+    let debounce = AsepritePlugin::default().rebuild_debounce;
+    for _ in 0..=debounce.as_millis() / FRAME_DURATION.as_millis() {
+        app.update();
+        let mut events = app.world.resource_mut::<Events<AsepriteReady>>();
+        assert_eq!(events.drain().count(), 0);
+    }
+}
+
+#[test]
+fn layer_group() {
+    let mut app = app();
+    let aseprite = load_with(&mut app, "layers.aseprite", |settings| {
+        settings.group_atlases = true
+    });
+    let group_atlas = app
+        .world
+        .resource::<Assets<Aseprite>>()
+        .get(&aseprite)
+        .unwrap()
+        .group_atlas("Group")
+        .cloned()
+        .unwrap();
+    let entity = app
+        .world
+        .spawn(AsepriteGroupBundle {
+            group: AsepriteGroup::new("Group"),
+            aseprite,
+            animation: AsepriteAnimation::from("bounce"),
+            ..Default::default()
+        })
+        .id();
+    app.update();
+
+    let atlas = app.world.get::<Handle<TextureAtlas>>(entity).unwrap();
+    assert_eq!(*atlas, group_atlas);
+    let atlases = app.world.resource::<Assets<TextureAtlas>>();
+    assert_eq!(atlases.get(atlas).unwrap().len(), 3);
+}
+
+#[test]
+fn aabb_follows_the_sprite_size() {
+    let mut app = app();
+    let aseprite = load(&mut app);
+    let entity = app
+        .world
+        .spawn(AsepriteBundle {
+            aseprite,
+            animation: AsepriteAnimation::from("forward"),
+            ..Default::default()
+        })
+        .id();
+    app.update();
+    assert!(app.world.get::<Aabb>(entity).is_some());
+
+    let size = Vec2::new(64., 32.);
+    let mut animation = app.world.get_mut::<AsepriteAnimation>(entity).unwrap();
+    animation.custom_size = Some(size);
+    app.world
+        .get_mut::<TextureAtlasSprite>(entity)
+        .unwrap()
+        .anchor = bevy::sprite::Anchor::BottomLeft;
+    app.update();
+    app.update();
+
+    let aabb = app.world.get::<Aabb>(entity).unwrap();
+    assert_eq!(Vec3::from(aabb.half_extents), Vec3::new(32., 16., 0.));
+    assert_eq!(Vec3::from(aabb.center), Vec3::new(32., 16., 0.));
+}
+
+#[test]
+fn without_animation() {
+    let mut app = app_with(AsepritePlugin::default().without_animation());
+    let aseprite = load(&mut app);
+    let entity = app
+        .world
+        .spawn(AsepriteBundle {
+            aseprite,
+            animation: AsepriteAnimation::from("forward"),
+            ..Default::default()
+        })
+        .id();
+    assert_eq!(indices(&mut app, entity, 3), vec![0, 0, 0]);
+}
+
+// Shows the frames in a fixed order, one per update
+struct Steps(Vec<usize>);
+
+impl FrameSequencer for Steps {
+    fn advance(
+        &mut self,
+        animation: &mut AsepriteAnimation,
+        _: &AsepriteInfo,
+        _: Duration,
+    ) -> bool {
+        match self.0.pop() {
+            Some(frame) => {
+                animation.current_frame = frame;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[test]
+fn custom_sequencer() {
+    let mut app = app();
+    let aseprite = load(&mut app);
+    let entity = app
+        .world
+        .spawn((
+            AsepriteBundle {
+                aseprite,
+                animation: AsepriteAnimation::from("forward"),
+                ..Default::default()
+            },
+            AsepriteSequencer::new(Steps(vec![1, 3, 2, 0])),
+        ))
+        .id();
+    // The sprite is only inserted at the end of the first update
+    app.update();
+    assert_eq!(indices(&mut app, entity, 4), vec![2, 3, 1, 1]);
+}
+
+#[test]
+fn material_override() {
+    let mut app = app();
+    let aseprite = load(&mut app);
+    let entity = app
+        .world
+        .spawn(AsepriteBundle {
+            aseprite,
+            animation: AsepriteAnimation::from("forward"),
+            ..Default::default()
+        })
+        .id();
+    app.update();
+    let atlas = app
+        .world
+        .get::<Handle<TextureAtlas>>(entity)
+        .unwrap()
+        .clone();
+
+    let silhouette = app
+        .world
+        .resource_mut::<Assets<Image>>()
+        .add(Image::default());
+    app.world
+        .entity_mut(entity)
+        .insert(AsepriteMaterialOverride::new(silhouette.clone()));
+    app.update();
+    let overridden = app.world.get::<Handle<TextureAtlas>>(entity).unwrap();
+    let atlases = app.world.resource::<Assets<TextureAtlas>>();
+    assert_eq!(atlases.get(overridden).unwrap().texture, silhouette);
+    assert_eq!(
+        atlases.get(overridden).unwrap().textures,
+        atlases.get(&atlas).unwrap().textures
+    );
+
+    app.world
+        .entity_mut(entity)
+        .remove::<AsepriteMaterialOverride>();
+    app.update();
+    assert_eq!(
+        *app.world.get::<Handle<TextureAtlas>>(entity).unwrap(),
+        atlas
+    );
+}
+
+#[test]
+fn bundle_can_be_parented_before_loading() {
+    let mut app = app();
+    let aseprite = app
+        .world
+        .resource::<AssetServer>()
+        .load("memory://directions.aseprite");
+    let parent = app.world.spawn(SpatialBundle::default()).id();
+    let child = app
+        .world
+        .spawn(AsepriteBundle {
+            aseprite,
+            visibility: Visibility::Hidden,
+            ..Default::default()
+        })
+        .set_parent(parent)
+        .id();
+    assert!(app.world.get::<InheritedVisibility>(child).is_some());
+    assert!(app.world.get::<ViewVisibility>(child).is_some());
+
+    // Visibility set in the bundle is kept once the sprite is inserted
+    load(&mut app);
+    app.update();
+    assert!(app.world.get::<TextureAtlasSprite>(child).is_some());
+    assert_eq!(
+        app.world.get::<Visibility>(child),
+        Some(&Visibility::Hidden)
+    );
+}
+
+#[test]
+fn aseprite_set() {
+    let mut app = app();
+    let handle = load_with(&mut app, "layers.aseset", |_| {});
+    let aseprites = app.world.resource::<Assets<Aseprite>>();
+    let info = aseprites.get(&handle).unwrap().info().unwrap();
+    assert_eq!(info.frame_count, 6);
+    assert_eq!(info.tags["Idle"].frames.as_range(), 0..3);
+    assert_eq!(info.tags["Run"].frames.as_range(), 3..6);
+    assert_eq!(info.tags["Run/bounce"].frames.as_range(), 3..6);
+}
+
+#[test]
+fn streamed_frames() {
+    let mut app = app();
+    let aseprite = load_with(&mut app, "directions.aseprite", |settings| {
+        settings.stream_frames = Some(2)
+    });
+    let entity = app
+        .world
+        .spawn(AsepriteBundle {
+            aseprite: aseprite.clone(),
+            animation: AsepriteAnimation::from("forward"),
+            ..Default::default()
+        })
+        .id();
+    let frames =
+        bevy_aseprite::reader::Aseprite::from_bytes(include_bytes!("assets/directions.aseprite"))
+            .unwrap()
+            .frames()
+            .all()
+            .get_rgba_buffers()
+            .unwrap();
+
+    // The sprite is inserted during the first update, each one after it
+    // shows the next frame, copied into one of the two slots
+    app.update();
+    for frame in [1, 2, 3, 0, 1] {
+        app.update();
+        let world = &app.world;
+        assert!(world
+            .resource::<Assets<Aseprite>>()
+            .get(&aseprite)
+            .unwrap()
+            .is_streamed());
+        let atlas_handle = world.get::<Handle<TextureAtlas>>(entity).unwrap();
+        let atlas = world
+            .resource::<Assets<TextureAtlas>>()
+            .get(atlas_handle)
+            .unwrap();
+        assert_eq!(atlas.len(), 2);
+
+        let slot = atlas.textures[world.get::<TextureAtlasSprite>(entity).unwrap().index];
+        let texture = world
+            .resource::<Assets<Image>>()
+            .get(&atlas.texture)
+            .unwrap();
+        let (pixels, width, height) = &frames[frame];
+        for row in 0..*height as usize {
+            let start = (row * atlas.size.x as usize + slot.min.x as usize) * 4;
+            let row_len = *width as usize * 4;
+            assert_eq!(
+                texture.data[start..start + row_len],
+                pixels[row * row_len..(row + 1) * row_len]
+            );
+        }
+    }
+}
+
+#[test]
+fn tag_sub_assets_match_the_info() {
+    let mut app = app();
+    let handle = load_with(&mut app, "duplicate_tags.aseprite", |_| {});
+    let walk: Handle<AsepriteTagAsset> = app
+        .world
+        .resource::<AssetServer>()
+        .load("memory://duplicate_tags.aseprite#tag/walk");
+    app.update();
+
+    let info = app
+        .world
+        .resource::<Assets<Aseprite>>()
+        .get(&handle)
+        .unwrap()
+        .info()
+        .unwrap();
+    // The last of the tags sharing the name, which plays in reverse
+    let tag = &info.tags["walk"];
+    assert_eq!(tag.animation_direction, AsepriteAnimationDirection::Reverse);
+    let frames: Vec<_> = tag.frames.as_range().rev().collect();
+    let walk = app
+        .world
+        .resource::<Assets<AsepriteTagAsset>>()
+        .get(&walk)
+        .unwrap();
+    assert_eq!(walk.frames, frames);
+    assert_eq!(walk.frames, vec![3, 2]);
+}
+
+#[test]
+fn slice_meta() {
+    let mut app = app();
+    let aseprite = load_with(&mut app, "slice_user_data.aseprite", |_| {});
+    let entity = app
+        .world
+        .spawn(AsepriteNinePatchBundle {
+            nine_patch: AsepriteNinePatchSlice::new("panel", Vec2::new(16., 16.)),
+            aseprite,
+            ..Default::default()
+        })
+        .id();
+    app.update();
+    assert_eq!(
+        app.world.get::<SliceMeta>(entity),
+        Some(&SliceMeta("menu.title".to_string()))
+    );
+}
+
+#[test]
+fn nine_patch_keeps_other_children() {
+    let mut app = app();
+    let aseprite = load_with(&mut app, "slice_user_data.aseprite", |_| {});
+    let entity = app
+        .world
+        .spawn(AsepriteNinePatchBundle {
+            nine_patch: AsepriteNinePatchSlice::new("panel", Vec2::new(16., 16.)),
+            aseprite,
+            ..Default::default()
+        })
+        .id();
+    let child = app.world.spawn_empty().set_parent(entity).id();
+    app.update();
+    let children = app.world.get::<Children>(entity).unwrap().len();
+
+    // Resizing lays the patches out again
+    app.world
+        .get_mut::<AsepriteNinePatchSlice>(entity)
+        .unwrap()
+        .size = Vec2::new(32., 32.);
+    app.update();
+    let after = app.world.get::<Children>(entity).unwrap();
+    assert_eq!(after.len(), children);
+    assert!(after.contains(&child));
+}
+
+#[test]
+fn memory_usage() {
+    let mut app = app();
+    let handle = load(&mut app);
+    app.update();
+
+    let world = &app.world;
+    let report = world
+        .resource::<Assets<Aseprite>>()
+        .get(&handle)
+        .unwrap()
+        .memory_usage(
+            world.resource::<Assets<TextureAtlas>>(),
+            world.resource::<Assets<Image>>(),
+        );
+    assert_eq!(report.frames, 4);
+    assert!(report.atlas_bytes > 0);
+    assert!(report.info_bytes > 0);
+    assert_eq!(world.resource::<AsepriteDiagnostics>().memory, report);
+}
+
+#[test]
+fn reader_types_at_the_root() {
+    let mut app = app();
+    let handle = load(&mut app);
+    let aseprites = app.world.resource::<Assets<Aseprite>>();
+    let tag: &AsepriteTag = &aseprites.get(&handle).unwrap().info().unwrap().tags["reverse"];
+    assert_eq!(tag.animation_direction, AsepriteAnimationDirection::Reverse);
+}
+
+#[test]
+fn normal_map_layer() {
+    let mut app = app();
+    let handle = load_with(&mut app, "companion_layers.aseprite", |settings| {
+        settings.packing = AtlasPacking::Grid
+    });
+    let aseprites = app.world.resource::<Assets<Aseprite>>();
+    let aseprite = aseprites.get(&handle).unwrap();
+    let atlases = app.world.resource::<Assets<TextureAtlas>>();
+    let atlas = atlases.get(aseprite.slice_atlas().unwrap()).unwrap();
+    let images = app.world.resource::<Assets<Image>>();
+    let texture = images.get(&atlas.texture).unwrap();
+    let normal = images.get(aseprite.normal_texture().unwrap()).unwrap();
+    assert_eq!(
+        normal.texture_descriptor.size,
+        texture.texture_descriptor.size
+    );
+
+    // The first frame is in the top left corner of a grid
+    let pixel = |image: &Image, x: u32, y: u32| {
+        let index = ((y * image.texture_descriptor.size.width + x) * 4) as usize;
+        image.data[index..index + 4].to_vec()
+    };
+    assert_eq!(pixel(texture, 2, 2), vec![200, 30, 40, 255]);
+    assert_eq!(pixel(normal, 2, 2), vec![128, 128, 255, 255]);
+    assert_eq!(pixel(normal, 0, 0), vec![0, 0, 0, 0]);
+}
+
+#[test]
+fn time_passing_isnt_a_change() {
+    let mut app = app();
+    let aseprite = load(&mut app);
+    let entity = app
+        .world
+        .spawn(AsepriteBundle {
+            aseprite,
+            animation: AsepriteAnimation::from("forward").speed(0.25),
+            ..Default::default()
+        })
+        .id();
+    app.update();
+
+    let state = |app: &App| {
+        let entity = app.world.entity(entity);
+        (
+            entity.get::<TextureAtlasSprite>().unwrap().index,
+            entity
+                .get_ref::<AsepriteAnimation>()
+                .unwrap()
+                .last_changed(),
+        )
+    };
+    let (mut index, mut last_changed) = state(&app);
+    let mut changes = 0;
+    for _ in 0..8 {
+        app.update();
+        let (new_index, new_last_changed) = state(&app);
+        assert_eq!(new_index != index, new_last_changed != last_changed);
+        changes += (new_index != index) as usize;
+        (index, last_changed) = (new_index, new_last_changed);
+    }
+    assert_eq!(changes, 2);
+}
+
+#[test]
+fn single_frame_loops_leave_the_sprite_alone() {
+    let mut app = app();
+    let aseprite = load(&mut app);
+    let entity = app
+        .world
+        .spawn(AsepriteBundle {
+            aseprite,
+            animation: AsepriteAnimation::frames(1..2),
+            ..Default::default()
+        })
+        .id();
+    app.update();
+    app.update();
+
+    let last_changed = |app: &App| {
+        app.world
+            .entity(entity)
+            .get_ref::<TextureAtlasSprite>()
+            .unwrap()
+            .last_changed()
+    };
+    let before = last_changed(&app);
+    for _ in 0..4 {
+        app.update();
+    }
+    assert_eq!(last_changed(&app), before);
+    assert_eq!(
+        app.world.get::<TextureAtlasSprite>(entity).unwrap().index,
+        1
+    );
+}
+
+#[test]
+fn unloaded_files_hide_their_sprites() {
+    let mut app = app();
+    let aseprite = load(&mut app);
+    let entity = app
+        .world
+        .spawn(AsepriteBundle {
+            aseprite: aseprite.clone(),
+            ..Default::default()
+        })
+        .id();
+    app.update();
+
+    app.world
+        .resource_mut::<Assets<Aseprite>>()
+        .remove(&aseprite);
+    app.update();
+    assert_eq!(
+        app.world.get::<Visibility>(entity),
+        Some(&Visibility::Hidden)
+    );
+    assert!(app.world.get::<AsepriteMissing>(entity).is_some());
+}
+
+#[test]
+fn unloaded_files_despawn_their_entities() {
+    let mut app =
+        app_with(AsepritePlugin::default().on_missing_aseprite(MissingAsepritePolicy::Despawn));
+    let aseprite = load(&mut app);
+    let entity = app
+        .world
+        .spawn(AsepriteBundle {
+            aseprite: aseprite.clone(),
+            ..Default::default()
+        })
+        .id();
+    app.update();
+
+    app.world
+        .resource_mut::<Assets<Aseprite>>()
+        .remove(&aseprite);
+    app.update();
+    assert!(app.world.get_entity(entity).is_none());
+}
+
+#[test]
+fn forward() {
+    assert_eq!(play("forward", 6), vec![0, 1, 2, 3, 0, 1]);
+}
+
+#[test]
+fn reverse() {
+    assert_eq!(play("reverse", 6), vec![3, 2, 1, 0, 3, 2]);
+}
+
+#[test]
+fn ping_pong() {
+    assert_eq!(play("ping_pong", 8), vec![0, 1, 2, 3, 2, 1, 0, 1]);
+}
+
+#[test]
+fn switching_tags() {
+    let mut app = app();
+    let aseprite = load(&mut app);
+    let entity = app
+        .world
+        .spawn(AsepriteBundle {
+            aseprite,
+            animation: AsepriteAnimation::from("reverse"),
+            ..Default::default()
+        })
+        .id();
+    assert_eq!(indices(&mut app, entity, 4), vec![3, 2, 1, 0]);
+
+    // The current frame is outside of the new tag
+    *app.world.get_mut::<AsepriteAnimation>(entity).unwrap() = AsepriteAnimation::from("end");
+    assert_eq!(indices(&mut app, entity, 4), vec![2, 3, 2, 3]);
+
+    *app.world.get_mut::<AsepriteAnimation>(entity).unwrap() = AsepriteAnimation::from("ping_pong");
+    assert_eq!(indices(&mut app, entity, 5), vec![0, 1, 2, 3, 2]);
+}